@@ -7,24 +7,69 @@ QUERY read_record(id: ID) =>
     record <- N<Record>(id)
     RETURN record
 
+QUERY read_record_id(id: ID) =>
+    record <- N<Record>(id)::ID
+    RETURN record
+
 QUERY update_record(id: ID, data: String) =>
     record <- N<Record>(id)::UPDATE({
         data: data
     })
     RETURN record
 
+QUERY upsert_record(id: ID, data: String) =>
+    record <- N<Record>(id)::UpsertN({
+        data: data
+    })
+    RETURN record
+
 QUERY delete_record(id: ID) =>
     DROP N<Record>(id)
     RETURN "NONE"
 
+QUERY delete_records(ids: [ID]) =>
+    FOR id IN ids {
+        DROP N<Record>(id)
+    }
+    RETURN "NONE"
+
+QUERY delete_all_records() =>
+    DROP N<Record>
+    RETURN "NONE"
+
 QUERY scan_records(limit: I32, offset: I32) =>
     records <- N<Record>::RANGE(offset, limit)
     RETURN records
 
+QUERY scan_record_ids(limit: I32, offset: I32) =>
+    records <- N<Record>::RANGE(offset, limit)::ID
+    RETURN records
+
+QUERY read_records_batch(ids: [ID]) =>
+    records <- N<Record>(ids)
+    RETURN records
+
+QUERY read_records_by_data(data: String) =>
+    records <- N<Record>({ data: data })
+    RETURN records
+
+QUERY filter_records_by_data(data: String) =>
+    records <- N<Record>::WHERE(_::{data}::EQ(data))
+    RETURN records
+
 QUERY count_records() =>
     count <- N<Record>::COUNT
     RETURN count
 
+QUERY transaction(records: [{data: String, updated: String}]) =>
+    created <- FOR {data, updated} IN records {
+        record <- AddN<Record>({ data: data })
+        read <- N<Record>(record::ID)
+        N<Record>(record::ID)::UPDATE({ data: updated })
+        record::ID
+    }
+    RETURN created
+
 QUERY create_vector(vec: [F64]) =>
     AddV<Embedding>(vec)
     RETURN "SUCCESS"
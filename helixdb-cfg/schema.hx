@@ -35,7 +35,7 @@
 // or checkout our GitHub at https://github.com/HelixDB/helix-db
 
 N::Record {
-    data: String
+    INDEX data: String
 }
 
 V::Embedding {
@@ -0,0 +1,379 @@
+use crate::runner::Runner;
+use crate::types::{
+    Benchmark, BenchmarkClient, BenchmarkEngine, Projection, Scan, Workload, CLEANUP_BATCH,
+};
+use crate::utils::{parse_uuids, upsert_key};
+use anyhow::Result;
+use async_trait::async_trait;
+use scylla::client::session::Session;
+use scylla::client::session_builder::SessionBuilder;
+use scylla::response::PagingState;
+use scylla::serialize::row::SerializeRow;
+use scylla::statement::prepared::PreparedStatement;
+use scylla::value::{CqlValue, Row};
+use std::ops::ControlFlow;
+use std::sync::{Arc, Mutex};
+use uuid::Uuid;
+
+const CREATE_KEYSPACE: &str = "CREATE KEYSPACE IF NOT EXISTS helix WITH replication = \
+    {'class': 'SimpleStrategy', 'replication_factor': 1}";
+
+// ScyllaDB refuses more than 100 partition keys in one `IN` by default
+const DELETE_BATCH: usize = 100;
+
+// Statements prepared once by the engine and shared by its clients, so the driver can route
+// each request straight to a replica that owns its token
+struct Statements {
+    insert: PreparedStatement,
+    read: PreparedStatement,
+    read_id: PreparedStatement,
+    update: PreparedStatement,
+    delete: PreparedStatement,
+    delete_many: PreparedStatement,
+    scan: PreparedStatement,
+    scan_ids: PreparedStatement,
+    count: PreparedStatement,
+}
+
+impl Statements {
+    // Prepared against `table` in the `helix` keyspace
+    async fn prepare(session: &Session, table: &str) -> Result<Self> {
+        let prepare = |statement: String| session.prepare(statement);
+        Ok(Self {
+            // Writes in CQL insert or overwrite, so creates, updates and upserts are all the
+            // same statement
+            insert: prepare(format!("INSERT INTO helix.{} (id, data) VALUES (?, ?)", table)).await?,
+            read: prepare(format!("SELECT data FROM helix.{} WHERE id = ?", table)).await?,
+            read_id: prepare(format!("SELECT id FROM helix.{} WHERE id = ?", table)).await?,
+            update: prepare(format!("UPDATE helix.{} SET data = ? WHERE id = ?", table)).await?,
+            delete: prepare(format!("DELETE FROM helix.{} WHERE id = ?", table)).await?,
+            delete_many: prepare(format!("DELETE FROM helix.{} WHERE id IN ?", table)).await?,
+            scan: prepare(format!("SELECT id, data FROM helix.{}", table)).await?,
+            scan_ids: prepare(format!("SELECT id FROM helix.{}", table)).await?,
+            count: prepare(format!("SELECT COUNT(*) FROM helix.{}", table)).await?,
+        })
+    }
+}
+
+pub struct CassandraClient {
+    session: Arc<Session>,
+    statements: Arc<Statements>,
+    runner: Runner,
+    workload: Workload,
+    ids: Vec<Uuid>,
+}
+
+impl CassandraClient {
+    fn new(
+        session: Arc<Session>,
+        statements: Arc<Statements>,
+        runner: Runner,
+        workload: Workload,
+    ) -> Self {
+        Self {
+            session,
+            statements,
+            runner,
+            workload,
+            ids: Vec::new(),
+        }
+    }
+
+    async fn execute(
+        &self,
+        statement: &PreparedStatement,
+        values: impl SerializeRow,
+    ) -> Result<()> {
+        self.session.execute_unpaged(statement, values).await?;
+        Ok(())
+    }
+
+    async fn insert(&self, id: Uuid, data: &str) -> Result<()> {
+        self.execute(&self.statements.insert, (id, data)).await
+    }
+
+    // The record's data, or its id under an id-only read projection; `None` if it doesn't exist
+    async fn select(&self, id: Uuid, projection: Projection) -> Result<Option<String>> {
+        let res = match projection {
+            Projection::Id => {
+                let res = self.session.execute_unpaged(&self.statements.read_id, (id,)).await?;
+                let row = res.into_rows_result()?.maybe_first_row::<(Uuid,)>()?;
+                row.map(|(id,)| id.to_string())
+            }
+            _ => {
+                let res = self.session.execute_unpaged(&self.statements.read, (id,)).await?;
+                let row = res.into_rows_result()?.maybe_first_row::<(String,)>()?;
+                row.map(|(data,)| data)
+            }
+        };
+        Ok(res)
+    }
+
+    async fn remove(&self, id: Uuid) -> Result<()> {
+        self.execute(&self.statements.delete, (id,)).await
+    }
+}
+
+#[async_trait]
+impl BenchmarkClient for CassandraClient {
+    async fn startup(&self) -> Result<()> {
+        Ok(()) // the engine creates the schema before preparing statements against it
+    }
+
+    async fn create_records(&mut self, count: usize) -> Result<()> {
+        let new_ids = self.workload.new_ids();
+        let this = &*self;
+        let ids = this
+            .runner
+            .run("Create", count, |i| async move {
+                let id = new_ids.id(i);
+                this.insert(id, this.workload.value(i)).await?;
+                Ok(id)
+            })
+            .await?;
+        self.ids.extend(ids);
+        Ok(())
+    }
+
+    async fn read_records(&self) -> Result<()> {
+        let keys = self.workload.read_keys(self.ids.len())?;
+        let keys = &keys;
+        self.runner
+            .run("Read", keys.len(), |i| async move {
+                let key = keys[i % keys.len()];
+                let projection = self.workload.read_projection;
+                let row = self.select(self.ids[key], projection).await?;
+                let expected = self.workload.checked_value(i, key);
+                if let (Projection::Full, Some(expected)) = (projection, expected) {
+                    if row.as_deref() != Some(expected) {
+                        let id = &self.ids[key];
+                        return Err(anyhow::anyhow!("Read of {} returned the wrong data", id));
+                    }
+                }
+                Ok(())
+            })
+            .await?;
+        Ok(())
+    }
+
+    async fn update_records(&self) -> Result<()> {
+        let order = &self.workload.working_set(self.ids.len());
+        self.runner
+            .run("Update", self.ids.len(), |i| async move {
+                let id = self.ids[order[i % order.len()]];
+                let values = (self.workload.updated_value(i), id);
+                self.execute(&self.statements.update, values).await
+            })
+            .await?;
+        Ok(())
+    }
+
+    async fn upsert_records(&mut self, count: usize) -> Result<()> {
+        let new_ids = self.workload.new_ids();
+        let this = &*self;
+        let ids = this
+            .runner
+            .run("Upsert", count, |i| async move {
+                let existing = upsert_key(&this.ids, i);
+                let id = existing.unwrap_or_else(|| new_ids.id(i));
+                this.insert(id, this.workload.value(i)).await?;
+                Ok(existing.is_none().then_some(id))
+            })
+            .await?;
+        self.ids.extend(ids.into_iter().flatten());
+        Ok(())
+    }
+
+    async fn delete_records(&self) -> Result<()> {
+        let order = &self.workload.order(self.ids.len());
+        self.runner
+            .run("Delete", self.ids.len(), |i| {
+                self.remove(self.ids[order[i % order.len()]])
+            })
+            .await?;
+        Ok(())
+    }
+
+    async fn cleanup(&mut self) -> Result<()> {
+        for batch in self.ids.chunks(CLEANUP_BATCH) {
+            for keys in batch.chunks(DELETE_BATCH) {
+                self.execute(&self.statements.delete_many, (keys,)).await?;
+            }
+        }
+        self.ids.clear();
+        Ok(())
+    }
+
+    // Pages follow the driver's paging state rather than an offset, which CQL doesn't have
+    async fn scan_records(&self) -> Result<()> {
+        let scan = self.workload.scan(&self.ids);
+        let paging = Mutex::new(Some(PagingState::start()));
+        let paging = &paging;
+        self.runner.scan(scan, |scan| async move { self.scan(&scan, paging).await }).await?;
+        Ok(())
+    }
+
+    async fn read_records_by_data(&self) -> Result<()> {
+        Err(anyhow::anyhow!("read_by_data isn't supported for Cassandra"))
+    }
+
+    async fn transaction(&mut self, _count: usize) -> Result<()> {
+        Err(anyhow::anyhow!("txn isn't supported for Cassandra"))
+    }
+
+    async fn batch_read(&self) -> Result<()> {
+        Err(anyhow::anyhow!("batch_read isn't supported for Cassandra"))
+    }
+
+    async fn count_records(&self) -> Result<usize> {
+        let res = self.session.execute_unpaged(&self.statements.count, ()).await?;
+        let (count,) = res.into_rows_result()?.single_row::<(i64,)>()?;
+        Ok(count as usize)
+    }
+
+    fn tracked_ids(&self) -> usize {
+        self.ids.len()
+    }
+
+    fn load_ids(&mut self, ids: &[String]) -> Result<()> {
+        self.ids.extend(parse_uuids(ids)?);
+        Ok(())
+    }
+
+    async fn reset(&mut self, purge: bool) -> Result<()> {
+        if purge {
+            return Err(anyhow::anyhow!("--purge isn't supported for Cassandra"));
+        }
+        self.ids.clear();
+        Ok(())
+    }
+
+    async fn create_record(&self, data: &str) -> Result<String> {
+        let id = self.workload.new_ids().id(0);
+        self.insert(id, data).await?;
+        Ok(id.to_string())
+    }
+
+    async fn read_record(&self, key: &str) -> Result<()> {
+        self.select(key.parse()?, self.workload.read_projection).await?;
+        Ok(())
+    }
+
+    async fn update_record(&self, key: &str, data: &str) -> Result<()> {
+        let id: Uuid = key.parse()?;
+        self.execute(&self.statements.update, (data, id)).await
+    }
+
+    async fn delete_record(&self, key: &str) -> Result<()> {
+        self.remove(key.parse()?).await
+    }
+
+    async fn create_vectors(&self, _count: usize) -> Result<()> {
+        Err(anyhow::anyhow!("vectors aren't supported for Cassandra"))
+    }
+
+    async fn search_vectors(&self, _count: usize) -> Result<()> {
+        Err(anyhow::anyhow!("vectors aren't supported for Cassandra"))
+    }
+
+    async fn dry_run(&mut self) -> Vec<(String, Result<()>)> {
+        let id = Uuid::new_v4();
+        let mut checks = Vec::new();
+
+        let created = self.insert(id, "test_value").await;
+        let create_ok = created.is_ok();
+        checks.push(("INSERT".to_string(), created));
+
+        let read = if create_ok {
+            match self.select(id, Projection::Full).await {
+                Ok(Some(data)) if data == "test_value" => Ok(()),
+                Ok(data) => Err(anyhow::anyhow!("unexpected row: {:?}", data)),
+                Err(e) => Err(e),
+            }
+        } else {
+            Err(anyhow::anyhow!("skipped, create failed"))
+        };
+        checks.push(("SELECT".to_string(), read));
+
+        let deleted = if create_ok {
+            self.remove(id).await
+        } else {
+            Err(anyhow::anyhow!("skipped, create failed"))
+        };
+        checks.push(("DELETE".to_string(), deleted));
+
+        checks
+    }
+}
+
+impl CassandraClient {
+    // One page of `scan.limit` rows from where the last page left off, fetching further driver
+    // pages if one comes back short. `paging` is `None` once the table is exhausted.
+    async fn scan(&self, scan: &Scan, paging: &Mutex<Option<PagingState>>) -> Result<usize> {
+        let mut statement = match scan.projection()? {
+            Projection::Count => return self.count_records().await,
+            Projection::Id => self.statements.scan_ids.clone(),
+            Projection::Full => self.statements.scan.clone(),
+        };
+        let limit = scan.limit.unwrap_or(usize::MAX);
+        statement.set_page_size(limit.min(i32::MAX as usize) as i32);
+        let mut rows = 0;
+        while rows < limit {
+            let Some(state) = paging.lock().unwrap().take() else {
+                break;
+            };
+            let (res, next) = self.session.execute_single_page(&statement, (), state).await?;
+            let res = res.into_rows_result()?;
+            rows += res.rows_num();
+            if scan.checking() {
+                for row in res.rows::<Row>()? {
+                    let id = row?.columns[0].as_ref().and_then(CqlValue::as_uuid);
+                    scan.saw(id.map(|id| id.to_string()));
+                }
+            }
+            if let ControlFlow::Continue(state) = next.into_paging_control_flow() {
+                *paging.lock().unwrap() = Some(state);
+            }
+        }
+        Ok(rows)
+    }
+}
+
+// Engine for Cassandra or ScyllaDB over CQL; the endpoint is a contact point, `host:port`
+pub struct CassandraEngine {
+    session: Arc<Session>,
+    statements: Arc<Statements>,
+    runner: Runner,
+    workload: Workload,
+}
+
+#[async_trait]
+impl BenchmarkEngine for CassandraEngine {
+    async fn setup(options: &Benchmark) -> Result<Self> {
+        let node = options.endpoint.as_deref().unwrap_or("localhost:9042");
+        let session = SessionBuilder::new().known_node(node).build().await?;
+        session.query_unpaged(CREATE_KEYSPACE, ()).await?;
+        let table = options.workload.table();
+        let create_table =
+            format!("CREATE TABLE IF NOT EXISTS helix.{} (id uuid PRIMARY KEY, data text)", table);
+        session.query_unpaged(create_table, ()).await?;
+        let statements = Statements::prepare(&session, table).await?;
+        Ok(Self {
+            session: Arc::new(session),
+            statements: Arc::new(statements),
+            runner: options.runner.clone(),
+            workload: options.workload.clone(),
+        })
+    }
+
+    async fn create_client(&self) -> Result<Box<dyn BenchmarkClient>> {
+        let client = CassandraClient::new(
+            self.session.clone(),
+            self.statements.clone(),
+            self.runner.clone(),
+            self.workload.clone(),
+        );
+        client.startup().await?;
+        Ok(Box::new(client))
+    }
+}
@@ -0,0 +1,427 @@
+use crate::runner::Runner;
+use crate::types::{
+    Benchmark, BenchmarkClient, BenchmarkEngine, Projection, Scan, StatusError, Workload,
+    CLEANUP_BATCH,
+};
+use crate::utils::{http_client_builder, parse_uuids, upsert_key};
+use anyhow::Result;
+use async_trait::async_trait;
+use reqwest::Client;
+use serde_json::{json, Value};
+use tracing::{debug, trace};
+use uuid::Uuid;
+
+// The statements every client runs, against the `--dataset-name` table or `records`
+struct Statements {
+    create_table: String,
+    insert: String,
+    read: String,
+    read_id: String,
+    update: String,
+    delete: String,
+    delete_many: String,
+    scan: String,
+    scan_ids: String,
+    count: String,
+}
+
+impl Statements {
+    fn new(table: &str) -> Self {
+        Self {
+            // ReplacingMergeTree keeps the last row written per id once parts merge, which is
+            // the closest ClickHouse gets to an upsert
+            create_table: format!(
+                "CREATE TABLE IF NOT EXISTS {} (id UUID, data String) \
+                ENGINE = ReplacingMergeTree ORDER BY id",
+                table
+            ),
+            insert: format!("INSERT INTO {} FORMAT JSONEachRow", table),
+            read: format!(
+                "SELECT id, data FROM {} WHERE id = {{id:UUID}} FORMAT JSONEachRow",
+                table
+            ),
+            read_id: format!("SELECT id FROM {} WHERE id = {{id:UUID}} FORMAT JSONEachRow", table),
+            // Updates and deletes are mutations that rewrite parts in the background, so they
+            // are expected to be slow; they are measured as they are rather than skipped
+            update: format!(
+                "ALTER TABLE {} UPDATE data = {{data:String}} WHERE id = {{id:UUID}}",
+                table
+            ),
+            delete: format!("DELETE FROM {} WHERE id = {{id:UUID}}", table),
+            delete_many: format!("DELETE FROM {} WHERE has({{ids:Array(UUID)}}, id)", table),
+            scan: format!(
+                "SELECT id, data FROM {} LIMIT {{limit:UInt64}} OFFSET {{offset:UInt64}} \
+                FORMAT JSONEachRow",
+                table
+            ),
+            scan_ids: format!(
+                "SELECT id FROM {} LIMIT {{limit:UInt64}} OFFSET {{offset:UInt64}} \
+                FORMAT JSONEachRow",
+                table
+            ),
+            // FINAL collapses rows an upsert replaced but that haven't been merged away yet
+            count: format!("SELECT count() FROM {} FINAL", table),
+        }
+    }
+}
+
+// Rows per INSERT; ClickHouse creates a part per insert, so single-row inserts would mostly
+// measure part creation
+const INSERT_BATCH: usize = 1000;
+
+pub struct ClickHouseClient {
+    endpoint: String,
+    client: Client,
+    statements: Statements,
+    runner: Runner,
+    workload: Workload,
+    ids: Vec<Uuid>,
+}
+
+impl ClickHouseClient {
+    pub fn new(endpoint: String, client: Client, runner: Runner, workload: Workload) -> Self {
+        Self {
+            endpoint,
+            client,
+            statements: Statements::new(workload.table()),
+            runner,
+            workload,
+            ids: Vec::new(),
+        }
+    }
+
+    // Runs a statement over the HTTP interface. Query parameters are bound server-side from
+    // `param_<name>` URL parameters, and `body` carries the rows of an INSERT.
+    async fn execute(
+        &self,
+        statement: &str,
+        params: &[(&str, String)],
+        body: Option<String>,
+    ) -> Result<String> {
+        let mut query = vec![("query".to_string(), statement.to_string())];
+        query.extend(
+            params
+                .iter()
+                .map(|(name, value)| (format!("param_{}", name), value.clone())),
+        );
+        trace!(%statement, ?params, ?body, "request");
+        let response = self
+            .client
+            .post(&self.endpoint)
+            .query(&query)
+            .body(body.unwrap_or_default())
+            .send()
+            .await?;
+        debug!(%statement, status = %response.status(), "response");
+        let status = response.status();
+        let text = response.text().await?;
+        trace!(response = %text, "response body");
+        if status.is_success() {
+            Ok(text)
+        } else {
+            Err(StatusError::new(status, text.trim()).into())
+        }
+    }
+
+    async fn insert(&self, rows: &[(Uuid, &str)]) -> Result<()> {
+        let body = rows
+            .iter()
+            .map(|(id, data)| json!({"id": id.to_string(), "data": data}).to_string())
+            .collect::<Vec<_>>()
+            .join("\n");
+        self.execute(&self.statements.insert, &[], Some(body)).await?;
+        Ok(())
+    }
+
+    async fn select(&self, id: Uuid) -> Result<Option<String>> {
+        let res = self
+            .execute(&self.statements.read, &[("id", id.to_string())], None)
+            .await?;
+        match res.lines().next() {
+            Some(line) => {
+                let row: Value = serde_json::from_str(line)?;
+                Ok(row["data"].as_str().map(String::from))
+            }
+            None => Ok(None),
+        }
+    }
+
+    async fn remove(&self, id: Uuid) -> Result<()> {
+        self.execute(&self.statements.delete, &[("id", id.to_string())], None)
+            .await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl BenchmarkClient for ClickHouseClient {
+    async fn startup(&self) -> Result<()> {
+        self.execute(&self.statements.create_table, &[], None).await?;
+        Ok(())
+    }
+
+    // Inserts go out in batches of `INSERT_BATCH` rows, so with a duration set the completed
+    // count is in batches rather than records
+    async fn create_records(&mut self, count: usize) -> Result<()> {
+        let new_ids = self.workload.new_ids();
+        let this = &*self;
+        let batches = count.div_ceil(INSERT_BATCH);
+        let ids = this
+            .runner
+            .run("Create", batches, |batch| async move {
+                let start = batch * INSERT_BATCH;
+                let end = match this.runner.duration() {
+                    Some(_) => start + INSERT_BATCH,
+                    None => count.min(start + INSERT_BATCH),
+                };
+                let rows: Vec<(Uuid, &str)> = (start..end)
+                    .map(|i| (new_ids.id(i), this.workload.value(i)))
+                    .collect();
+                this.insert(&rows).await?;
+                Ok(rows.into_iter().map(|(id, _)| id).collect::<Vec<_>>())
+            })
+            .await?;
+        self.ids.extend(ids.into_iter().flatten());
+        Ok(())
+    }
+
+    async fn read_records(&self) -> Result<()> {
+        let keys = self.workload.read_keys(self.ids.len())?;
+        let keys = &keys;
+        self.runner
+            .run("Read", keys.len(), |i| async move {
+                let id = self.ids[keys[i % keys.len()]];
+                match self.workload.read_projection {
+                    Projection::Id => {
+                        self.execute(&self.statements.read_id, &[("id", id.to_string())], None)
+                            .await?;
+                    }
+                    _ => {
+                        self.select(id).await?;
+                    }
+                }
+                Ok(())
+            })
+            .await?;
+        Ok(())
+    }
+
+    async fn update_records(&self) -> Result<()> {
+        let order = &self.workload.working_set(self.ids.len());
+        self.runner
+            .run("Update", self.ids.len(), |i| async move {
+                let id = self.ids[order[i % order.len()]];
+                let params = [
+                    ("id", id.to_string()),
+                    ("data", self.workload.updated_value(i).to_string()),
+                ];
+                self.execute(&self.statements.update, &params, None).await?;
+                Ok(())
+            })
+            .await?;
+        Ok(())
+    }
+
+    // A plain insert, ReplacingMergeTree drops the older row for the id when parts merge
+    async fn upsert_records(&mut self, count: usize) -> Result<()> {
+        let new_ids = self.workload.new_ids();
+        let this = &*self;
+        let ids = this
+            .runner
+            .run("Upsert", count, |i| async move {
+                let existing = upsert_key(&this.ids, i);
+                let id = existing.unwrap_or_else(|| new_ids.id(i));
+                this.insert(&[(id, this.workload.value(i))]).await?;
+                Ok(existing.is_none().then_some(id))
+            })
+            .await?;
+        self.ids.extend(ids.into_iter().flatten());
+        Ok(())
+    }
+
+    async fn delete_records(&self) -> Result<()> {
+        let order = &self.workload.order(self.ids.len());
+        self.runner
+            .run("Delete", self.ids.len(), |i| {
+                self.remove(self.ids[order[i % order.len()]])
+            })
+            .await?;
+        Ok(())
+    }
+
+    async fn cleanup(&mut self) -> Result<()> {
+        for batch in self.ids.chunks(CLEANUP_BATCH) {
+            let ids: Vec<String> = batch.iter().map(|id| format!("'{}'", id)).collect();
+            let ids = format!("[{}]", ids.join(","));
+            self.execute(&self.statements.delete_many, &[("ids", ids)], None).await?;
+        }
+        self.ids.clear();
+        Ok(())
+    }
+
+    async fn scan_records(&self) -> Result<()> {
+        let scan = self.workload.scan(&self.ids);
+        self.runner.scan(scan, |scan| async move { self.scan(&scan).await }).await?;
+        Ok(())
+    }
+
+    async fn read_records_by_data(&self) -> Result<()> {
+        Err(anyhow::anyhow!("read_by_data isn't supported for ClickHouse"))
+    }
+
+    async fn transaction(&mut self, _count: usize) -> Result<()> {
+        Err(anyhow::anyhow!("txn isn't supported for ClickHouse"))
+    }
+
+    async fn batch_read(&self) -> Result<()> {
+        Err(anyhow::anyhow!("batch_read isn't supported for ClickHouse"))
+    }
+
+    async fn count_records(&self) -> Result<usize> {
+        let res = self.execute(&self.statements.count, &[], None).await?;
+        res.trim()
+            .parse::<usize>()
+            .map_err(|_| anyhow::anyhow!("no count in response: {}", res))
+    }
+
+    fn tracked_ids(&self) -> usize {
+        self.ids.len()
+    }
+
+    fn load_ids(&mut self, ids: &[String]) -> Result<()> {
+        self.ids.extend(parse_uuids(ids)?);
+        Ok(())
+    }
+
+    async fn reset(&mut self, purge: bool) -> Result<()> {
+        if purge {
+            return Err(anyhow::anyhow!("--purge isn't supported for ClickHouse"));
+        }
+        self.ids.clear();
+        Ok(())
+    }
+
+    async fn create_record(&self, data: &str) -> Result<String> {
+        let id = self.workload.new_ids().id(0);
+        self.insert(&[(id, data)]).await?;
+        Ok(id.to_string())
+    }
+
+    async fn read_record(&self, key: &str) -> Result<()> {
+        match self.workload.read_projection {
+            Projection::Id => {
+                self.execute(&self.statements.read_id, &[("id", key.to_string())], None)
+                    .await?;
+            }
+            _ => {
+                self.select(key.parse::<Uuid>()?).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn update_record(&self, key: &str, data: &str) -> Result<()> {
+        let params = [("id", key.to_string()), ("data", data.to_string())];
+        self.execute(&self.statements.update, &params, None).await?;
+        Ok(())
+    }
+
+    async fn delete_record(&self, key: &str) -> Result<()> {
+        self.remove(key.parse::<Uuid>()?).await
+    }
+
+    async fn create_vectors(&self, _count: usize) -> Result<()> {
+        Err(anyhow::anyhow!("vectors aren't supported for ClickHouse"))
+    }
+
+    async fn search_vectors(&self, _count: usize) -> Result<()> {
+        Err(anyhow::anyhow!("vectors aren't supported for ClickHouse"))
+    }
+
+    async fn dry_run(&mut self) -> Vec<(String, Result<()>)> {
+        let id = Uuid::new_v4();
+        let mut checks = Vec::new();
+
+        let created = self.insert(&[(id, "test_value")]).await;
+        let create_ok = created.is_ok();
+        checks.push(("INSERT".to_string(), created));
+
+        let read = if create_ok {
+            match self.select(id).await {
+                Ok(Some(data)) if data == "test_value" => Ok(()),
+                Ok(data) => Err(anyhow::anyhow!("unexpected row: {:?}", data)),
+                Err(e) => Err(e),
+            }
+        } else {
+            Err(anyhow::anyhow!("skipped, create failed"))
+        };
+        checks.push(("SELECT".to_string(), read));
+
+        let deleted = if create_ok {
+            self.remove(id).await
+        } else {
+            Err(anyhow::anyhow!("skipped, create failed"))
+        };
+        checks.push(("DELETE".to_string(), deleted));
+
+        checks
+    }
+}
+
+impl ClickHouseClient {
+    async fn scan(&self, scan: &Scan) -> Result<usize> {
+        let limit = scan.limit.unwrap_or(100);
+        let offset = scan.start.unwrap_or(0);
+        let statement = match scan.projection()? {
+            Projection::Count => return self.count_records().await,
+            Projection::Id => &self.statements.scan_ids,
+            Projection::Full => &self.statements.scan,
+        };
+        let params = [("limit", limit.to_string()), ("offset", offset.to_string())];
+        let res = self.execute(statement, &params, None).await?;
+        let rows: Vec<&str> = res.lines().filter(|line| !line.is_empty()).collect();
+        if scan.checking() {
+            for row in &rows {
+                let row: Value = serde_json::from_str(row)?;
+                scan.saw(row["id"].as_str().map(str::to_string));
+            }
+        }
+        Ok(rows.len())
+    }
+}
+
+// Engine for ClickHouse over its HTTP interface, as the default user
+pub struct ClickHouseEngine {
+    endpoint: String,
+    client: Client,
+    runner: Runner,
+    workload: Workload,
+}
+
+#[async_trait]
+impl BenchmarkEngine for ClickHouseEngine {
+    async fn setup(options: &Benchmark) -> Result<Self> {
+        let endpoint = options
+            .endpoint
+            .as_deref()
+            .unwrap_or("http://localhost:8123")
+            .to_string();
+        Ok(Self {
+            endpoint,
+            client: http_client_builder(options)?.build()?,
+            runner: options.runner.clone(),
+            workload: options.workload.clone(),
+        })
+    }
+
+    async fn create_client(&self) -> Result<Box<dyn BenchmarkClient>> {
+        let client = ClickHouseClient::new(
+            self.endpoint.clone(),
+            self.client.clone(),
+            self.runner.clone(),
+            self.workload.clone(),
+        );
+        client.startup().await?;
+        Ok(Box::new(client))
+    }
+}
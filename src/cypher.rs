@@ -0,0 +1,47 @@
+// Cypher statements shared by the engines that speak Cypher (Neo4j over HTTP, Memgraph over Bolt)
+
+use crate::types::UpdateMode;
+use std::borrow::Cow;
+
+pub const PING: &str = "RETURN 1";
+pub const CREATE_RECORD: &str = "CREATE (n:Record {id: $id, data: $data})";
+pub const READ_RECORD: &str = "MATCH (n:Record {id: $id}) RETURN n";
+pub const READ_RECORD_ID: &str = "MATCH (n:Record {id: $id}) RETURN n.id";
+pub const READ_RECORDS: &str = "MATCH (n:Record) WHERE n.id IN $ids RETURN n";
+pub const READ_RECORD_DATA: &str = "MATCH (n:Record {id: $id}) RETURN n.data AS data";
+pub const UPDATE_RECORD: &str = "MATCH (n:Record {id: $id}) SET n.data = $data";
+// Replaces the whole property map, rewriting the id as well as the data
+pub const UPDATE_RECORD_FULL: &str = "MATCH (n:Record {id: $id}) SET n = {id: $id, data: $data}";
+pub const UPSERT_RECORD: &str = "MERGE (n:Record {id: $id}) SET n.data = $data";
+pub const DELETE_RECORD: &str = "MATCH (n:Record {id: $id}) DELETE n";
+pub const DELETE_RECORDS: &str = "MATCH (n:Record) WHERE n.id IN $ids DETACH DELETE n";
+pub const DELETE_ALL_RECORDS: &str = "MATCH (n:Record) DETACH DELETE n";
+pub const SCAN_RECORDS: &str = "MATCH (n:Record) RETURN n SKIP $offset LIMIT $limit";
+pub const SCAN_RECORD_IDS: &str = "MATCH (n:Record) RETURN n.id SKIP $offset LIMIT $limit";
+pub const COUNT_RECORDS: &str = "MATCH (n:Record) RETURN count(n) AS count";
+// Counts rather than returns the matches, so the lookup is timed rather than the transfer
+pub const READ_RECORDS_BY_DATA: &str = "MATCH (n:Record {data: $data}) RETURN count(n) AS count";
+
+pub fn update_record(mode: UpdateMode) -> &'static str {
+    match mode {
+        UpdateMode::Partial => UPDATE_RECORD,
+        UpdateMode::Full => UPDATE_RECORD_FULL,
+    }
+}
+
+// `query` against nodes labelled `label` instead of `Record`, left as it is without a
+// `--dataset-name`
+pub fn with_label<'a>(query: &'a str, label: Option<&str>) -> Cow<'a, str> {
+    match label {
+        Some(label) => Cow::Owned(query.replace(":Record", &format!(":{}", label))),
+        None => Cow::Borrowed(query),
+    }
+}
+
+// A write of `n` that also sets the `--schema` fields passed as `$fields`, when there are any
+pub fn with_fields(query: &'static str, fields: bool) -> Cow<'static, str> {
+    match fields {
+        true => Cow::Owned(format!("{} SET n += $fields", query)),
+        false => Cow::Borrowed(query),
+    }
+}
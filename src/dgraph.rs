@@ -0,0 +1,367 @@
+use crate::runner::Runner;
+use crate::types::{
+    Benchmark, BenchmarkClient, BenchmarkEngine, Projection, Scan, StatusError, Workload,
+    CLEANUP_BATCH,
+};
+use crate::utils::{http_client_builder, upsert_key};
+use anyhow::Result;
+use async_trait::async_trait;
+use reqwest::Client;
+use serde_json::{json, Value};
+use tracing::{debug, trace};
+use uuid::Uuid;
+
+const SCHEMA: &str =
+    "id: string @index(exact) @upsert .\ndata: string .\ntype Record {\n    id\n    data\n}";
+const READ_RECORD: &str = "query q($uid: string) { q(func: uid($uid)) { uid data } }";
+const READ_RECORD_ID: &str = "query q($uid: string) { q(func: uid($uid)) { uid } }";
+const SCAN_RECORDS: &str =
+    "query q($first: int, $offset: int) { q(func: type(Record), first: $first, offset: $offset) { uid data } }";
+const SCAN_RECORD_IDS: &str =
+    "query q($first: int, $offset: int) { q(func: type(Record), first: $first, offset: $offset) { uid } }";
+const COUNT_RECORDS: &str = "{ q(func: type(Record)) { count(uid) } }";
+
+pub struct DgraphClient {
+    endpoint: String,
+    client: Client,
+    runner: Runner,
+    workload: Workload,
+    // Dgraph assigns its own uids, so these are kept as the hex strings it returns
+    ids: Vec<String>,
+}
+
+impl DgraphClient {
+    pub fn new(endpoint: String, client: Client, runner: Runner, workload: Workload) -> Self {
+        Self {
+            endpoint,
+            client,
+            runner,
+            workload,
+            ids: Vec::new(),
+        }
+    }
+
+    // Dgraph reports query and mutation errors with a 200 status, so they are pulled out of
+    // the body as well
+    async fn post(&self, path: &str, body: Value) -> Result<Value> {
+        let url = format!("{}{}", self.endpoint, path);
+        trace!(%path, %body, "request");
+        let response = self.client.post(&url).json(&body).send().await?;
+        debug!(%path, status = %response.status(), "response");
+        if !response.status().is_success() {
+            return Err(StatusError::new(response.status(), "").into());
+        }
+        let res = response.json::<Value>().await?;
+        trace!(response = %res, "response body");
+        match res["errors"].as_array() {
+            Some(errors) if !errors.is_empty() => {
+                Err(anyhow::anyhow!("Dgraph error: {}", errors[0]["message"]))
+            }
+            _ => Ok(res),
+        }
+    }
+
+    async fn query(&self, query: &str, variables: Value) -> Result<Value> {
+        self.post("/query", json!({"query": query, "variables": variables}))
+            .await
+    }
+
+    async fn mutate(&self, mutation: Value) -> Result<Value> {
+        self.post("/mutate?commitNow=true", mutation).await
+    }
+
+    async fn insert(&self, data: &str) -> Result<String> {
+        let res = self
+            .mutate(json!({"set": [{"uid": "_:r", "dgraph.type": "Record", "data": data}]}))
+            .await?;
+        res["data"]["uids"]["r"]
+            .as_str()
+            .map(String::from)
+            .ok_or_else(|| anyhow::anyhow!("no uid in response: {}", res))
+    }
+
+    async fn remove(&self, uid: &str) -> Result<()> {
+        self.mutate(json!({"delete": [{"uid": uid}]})).await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl BenchmarkClient for DgraphClient {
+    // Registering the schema is idempotent, so it doubles as the connectivity check
+    async fn startup(&self) -> Result<()> {
+        let url = format!("{}/alter", self.endpoint);
+        let response = self.client.post(&url).body(SCHEMA).send().await?;
+        debug!(status = %response.status(), "alter response");
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            Err(StatusError::new(response.status(), "").into())
+        }
+    }
+
+    async fn create_records(&mut self, count: usize) -> Result<()> {
+        let this = &*self;
+        let ids = this
+            .runner
+            .run("Create", count, |i| this.insert(this.workload.value(i)))
+            .await?;
+        self.ids.extend(ids);
+        Ok(())
+    }
+
+    async fn read_records(&self) -> Result<()> {
+        let query = match self.workload.read_projection {
+            Projection::Id => READ_RECORD_ID,
+            _ => READ_RECORD,
+        };
+        let keys = self.workload.read_keys(self.ids.len())?;
+        let keys = &keys;
+        self.runner
+            .run("Read", keys.len(), |i| async move {
+                let uid = &self.ids[keys[i % keys.len()]];
+                self.query(query, json!({"$uid": uid})).await?;
+                Ok(())
+            })
+            .await?;
+        Ok(())
+    }
+
+    // An upsert block, so the uid lookup and the write happen in one transaction
+    async fn update_records(&self) -> Result<()> {
+        let order = &self.workload.working_set(self.ids.len());
+        self.runner
+            .run("Update", self.ids.len(), |i| async move {
+                let uid = &self.ids[order[i % order.len()]];
+                let upsert = json!({
+                    "query": format!("{{ v as var(func: uid({})) }}", uid),
+                    "set": [{"uid": "uid(v)", "data": self.workload.updated_value(i)}]
+                });
+                self.mutate(upsert).await?;
+                Ok(())
+            })
+            .await?;
+        Ok(())
+    }
+
+    // Existing keys are matched on uid. New ones are matched on the `id` predicate, which
+    // finds nothing, so Dgraph creates the node and reports the uid it assigned.
+    async fn upsert_records(&mut self, count: usize) -> Result<()> {
+        let this = &*self;
+        let ids = this
+            .runner
+            .run("Upsert", count, |i| async move {
+                let existing = upsert_key(&this.ids, i);
+                let upsert = match &existing {
+                    Some(uid) => json!({
+                        "query": format!("{{ v as var(func: uid({})) }}", uid),
+                        "set": [{"uid": "uid(v)", "data": this.workload.value(i)}]
+                    }),
+                    None => {
+                        let id = Uuid::new_v4().to_string();
+                        json!({
+                            "query": format!("{{ v as var(func: eq(id, \"{}\")) }}", id),
+                            "set": [{
+                                "uid": "uid(v)",
+                                "dgraph.type": "Record",
+                                "id": id,
+                                "data": this.workload.value(i)
+                            }]
+                        })
+                    }
+                };
+                let res = this.mutate(upsert).await?;
+                if existing.is_some() {
+                    return Ok(None);
+                }
+                let uid = res["data"]["uids"]["uid(v)"]
+                    .as_str()
+                    .ok_or_else(|| anyhow::anyhow!("no uid in response: {}", res))?;
+                Ok(Some(uid.to_string()))
+            })
+            .await?;
+        self.ids.extend(ids.into_iter().flatten());
+        Ok(())
+    }
+
+    async fn delete_records(&self) -> Result<()> {
+        let order = &self.workload.order(self.ids.len());
+        self.runner
+            .run("Delete", self.ids.len(), |i| {
+                self.remove(&self.ids[order[i % order.len()]])
+            })
+            .await?;
+        Ok(())
+    }
+
+    async fn cleanup(&mut self) -> Result<()> {
+        for batch in self.ids.chunks(CLEANUP_BATCH) {
+            let nodes: Vec<Value> = batch.iter().map(|uid| json!({"uid": uid})).collect();
+            self.mutate(json!({"delete": nodes})).await?;
+        }
+        self.ids.clear();
+        Ok(())
+    }
+
+    async fn scan_records(&self) -> Result<()> {
+        let scan = self.workload.scan(&self.ids);
+        self.runner.scan(scan, |scan| async move { self.scan(&scan).await }).await?;
+        Ok(())
+    }
+
+    async fn read_records_by_data(&self) -> Result<()> {
+        Err(anyhow::anyhow!("read_by_data isn't supported for Dgraph"))
+    }
+
+    async fn transaction(&mut self, _count: usize) -> Result<()> {
+        Err(anyhow::anyhow!("txn isn't supported for Dgraph"))
+    }
+
+    async fn batch_read(&self) -> Result<()> {
+        Err(anyhow::anyhow!("batch_read isn't supported for Dgraph"))
+    }
+
+    async fn count_records(&self) -> Result<usize> {
+        let res = self.query(COUNT_RECORDS, json!({})).await?;
+        res["data"]["q"][0]["count"]
+            .as_u64()
+            .map(|count| count as usize)
+            .ok_or_else(|| anyhow::anyhow!("no count in response: {}", res))
+    }
+
+    fn tracked_ids(&self) -> usize {
+        self.ids.len()
+    }
+
+    fn load_ids(&mut self, ids: &[String]) -> Result<()> {
+        self.ids.extend(ids.iter().cloned());
+        Ok(())
+    }
+
+    async fn reset(&mut self, purge: bool) -> Result<()> {
+        if purge {
+            return Err(anyhow::anyhow!("--purge isn't supported for Dgraph"));
+        }
+        self.ids.clear();
+        Ok(())
+    }
+
+    async fn create_record(&self, data: &str) -> Result<String> {
+        self.insert(data).await
+    }
+
+    async fn read_record(&self, key: &str) -> Result<()> {
+        let query = match self.workload.read_projection {
+            Projection::Id => READ_RECORD_ID,
+            _ => READ_RECORD,
+        };
+        self.query(query, json!({"$uid": key})).await?;
+        Ok(())
+    }
+
+    async fn update_record(&self, key: &str, data: &str) -> Result<()> {
+        let upsert = json!({
+            "query": format!("{{ v as var(func: uid({})) }}", key),
+            "set": [{"uid": "uid(v)", "data": data}]
+        });
+        self.mutate(upsert).await?;
+        Ok(())
+    }
+
+    async fn delete_record(&self, key: &str) -> Result<()> {
+        self.remove(key).await
+    }
+
+    async fn create_vectors(&self, _count: usize) -> Result<()> {
+        Err(anyhow::anyhow!("vectors aren't supported for Dgraph"))
+    }
+
+    async fn search_vectors(&self, _count: usize) -> Result<()> {
+        Err(anyhow::anyhow!("vectors aren't supported for Dgraph"))
+    }
+
+    async fn dry_run(&mut self) -> Vec<(String, Result<()>)> {
+        let mut checks = Vec::new();
+
+        let created = self.insert("test_value").await;
+        let uid = created.as_ref().ok().cloned();
+        checks.push(("/mutate set".to_string(), created.map(|_| ())));
+
+        let read = match &uid {
+            Some(uid) => self
+                .query(READ_RECORD, json!({"$uid": uid}))
+                .await
+                .and_then(|res| {
+                    if res["data"]["q"][0]["data"] == "test_value" {
+                        Ok(())
+                    } else {
+                        Err(anyhow::anyhow!("unexpected read response: {}", res))
+                    }
+                }),
+            None => Err(anyhow::anyhow!("skipped, create failed")),
+        };
+        checks.push(("/query".to_string(), read));
+
+        let deleted = match &uid {
+            Some(uid) => self.remove(uid).await,
+            None => Err(anyhow::anyhow!("skipped, create failed")),
+        };
+        checks.push(("/mutate delete".to_string(), deleted));
+
+        checks
+    }
+}
+
+impl DgraphClient {
+    async fn scan(&self, scan: &Scan) -> Result<usize> {
+        let limit = scan.limit.unwrap_or(100);
+        let offset = scan.start.unwrap_or(0);
+        // Variables go over HTTP as strings whatever their declared type
+        let variables = json!({"$first": limit.to_string(), "$offset": offset.to_string()});
+        let query = match scan.projection()? {
+            Projection::Count => return self.count_records().await,
+            Projection::Id => SCAN_RECORD_IDS,
+            Projection::Full => SCAN_RECORDS,
+        };
+        let res = self.query(query, variables).await?;
+        let rows = res["data"]["q"].as_array().map(Vec::as_slice).unwrap_or_default();
+        scan.saw(rows.iter().filter_map(|row| row["uid"].as_str().map(str::to_string)));
+        Ok(rows.len())
+    }
+}
+
+// Engine for Dgraph, talking to an Alpha node over its HTTP API
+pub struct DgraphEngine {
+    endpoint: String,
+    client: Client,
+    runner: Runner,
+    workload: Workload,
+}
+
+#[async_trait]
+impl BenchmarkEngine for DgraphEngine {
+    async fn setup(options: &Benchmark) -> Result<Self> {
+        let endpoint = options
+            .endpoint
+            .as_deref()
+            .unwrap_or("http://localhost:8080")
+            .to_string();
+        Ok(Self {
+            endpoint,
+            client: http_client_builder(options)?.build()?,
+            runner: options.runner.clone(),
+            workload: options.workload.clone(),
+        })
+    }
+
+    async fn create_client(&self) -> Result<Box<dyn BenchmarkClient>> {
+        let client = DgraphClient::new(
+            self.endpoint.clone(),
+            self.client.clone(),
+            self.runner.clone(),
+            self.workload.clone(),
+        );
+        client.startup().await?;
+        Ok(Box::new(client))
+    }
+}
@@ -0,0 +1,384 @@
+use crate::runner::Runner;
+use crate::types::{
+    Benchmark, BenchmarkClient, BenchmarkEngine, Projection, Scan, Workload, CLEANUP_BATCH,
+};
+use crate::utils::{parse_uuids, upsert_key};
+use ::duckdb::{params, Connection, OptionalExt};
+use anyhow::Result;
+use async_trait::async_trait;
+use std::sync::{Arc, Mutex};
+use uuid::Uuid;
+
+pub struct DuckDbClient {
+    conn: Arc<Mutex<Connection>>,
+    runner: Runner,
+    workload: Workload,
+    ids: Vec<Uuid>,
+}
+
+impl DuckDbClient {
+    pub fn new(conn: Connection, runner: Runner, workload: Workload) -> Self {
+        Self {
+            conn: Arc::new(Mutex::new(conn)),
+            runner,
+            workload,
+            ids: Vec::new(),
+        }
+    }
+
+    fn table(&self) -> &str {
+        self.workload.table()
+    }
+
+    // The duckdb crate is synchronous, so every statement runs on the blocking thread pool
+    async fn with_conn<T, F>(&self, f: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Connection) -> ::duckdb::Result<T> + Send + 'static,
+    {
+        let conn = self.conn.clone();
+        let res = tokio::task::spawn_blocking(move || f(&conn.lock().unwrap())).await?;
+        res.map_err(Into::into)
+    }
+
+    async fn execute(&self, statement: String, id: Uuid, data: Option<String>) -> Result<()> {
+        self.with_conn(move |conn| {
+            let mut stmt = conn.prepare_cached(&statement)?;
+            match data {
+                Some(data) => stmt.execute(params![id.to_string(), data]),
+                None => stmt.execute(params![id.to_string()]),
+            }
+            .map(|_| ())
+        })
+        .await
+    }
+
+    async fn insert(&self, id: Uuid, data: &str) -> Result<()> {
+        let data = Some(data.to_string());
+        let statement = format!("INSERT INTO {} (id, data) VALUES (?, ?)", self.table());
+        self.execute(statement, id, data).await
+    }
+
+    // The record's data, or its id under an id-only read projection; `None` if it doesn't exist
+    async fn select(&self, id: Uuid, projection: Projection) -> Result<Option<String>> {
+        let column = match projection {
+            Projection::Id => "id",
+            _ => "data",
+        };
+        let statement = format!("SELECT {} FROM {} WHERE id = ?", column, self.table());
+        self.with_conn(move |conn| {
+            conn.prepare_cached(&statement)?
+                .query_row(params![id.to_string()], |row| row.get(0))
+                .optional()
+        })
+        .await
+    }
+
+    async fn update(&self, id: Uuid, data: String) -> Result<()> {
+        let statement = format!("UPDATE {} SET data = ? WHERE id = ?", self.table());
+        self.with_conn(move |conn| {
+            conn.prepare_cached(&statement)?
+                .execute(params![data, id.to_string()])
+                .map(|_| ())
+        })
+        .await
+    }
+
+    async fn remove(&self, id: Uuid) -> Result<()> {
+        self.execute(format!("DELETE FROM {} WHERE id = ?", self.table()), id, None).await
+    }
+}
+
+#[async_trait]
+impl BenchmarkClient for DuckDbClient {
+    async fn startup(&self) -> Result<()> {
+        let statement = format!(
+            "CREATE TABLE IF NOT EXISTS {} (id VARCHAR PRIMARY KEY, data VARCHAR)",
+            self.table()
+        );
+        self.with_conn(move |conn| conn.execute_batch(&statement)).await
+    }
+
+    async fn create_records(&mut self, count: usize) -> Result<()> {
+        let new_ids = self.workload.new_ids();
+        let this = &*self;
+        let ids = this
+            .runner
+            .run("Create", count, |i| async move {
+                let id = new_ids.id(i);
+                this.insert(id, this.workload.value(i)).await?;
+                Ok(id)
+            })
+            .await?;
+        self.ids.extend(ids);
+        Ok(())
+    }
+
+    async fn read_records(&self) -> Result<()> {
+        let keys = self.workload.read_keys(self.ids.len())?;
+        let keys = &keys;
+        self.runner
+            .run("Read", keys.len(), |i| async move {
+                let key = keys[i % keys.len()];
+                let projection = self.workload.read_projection;
+                let data = self.select(self.ids[key], projection).await?;
+                if data.is_none() && self.workload.verifies(i) {
+                    return Err(anyhow::anyhow!("Record {} wasn't found", self.ids[key]));
+                }
+                if let Projection::Id = projection {
+                    return Ok(());
+                }
+                if let Some(expected) = self.workload.checked_value(i, key) {
+                    if data.as_deref() != Some(expected) {
+                        let id = &self.ids[key];
+                        return Err(anyhow::anyhow!("Read of {} returned the wrong data", id));
+                    }
+                }
+                Ok(())
+            })
+            .await?;
+        Ok(())
+    }
+
+    async fn update_records(&self) -> Result<()> {
+        let order = &self.workload.working_set(self.ids.len());
+        self.runner
+            .run("Update", self.ids.len(), |i| async move {
+                let id = self.ids[order[i % order.len()]];
+                self.update(id, self.workload.updated_value(i).to_string()).await
+            })
+            .await?;
+        Ok(())
+    }
+
+    async fn upsert_records(&mut self, count: usize) -> Result<()> {
+        let new_ids = self.workload.new_ids();
+        let this = &*self;
+        let ids = this
+            .runner
+            .run("Upsert", count, |i| async move {
+                let existing = upsert_key(&this.ids, i);
+                let id = existing.unwrap_or_else(|| new_ids.id(i));
+                let data = this.workload.value(i).to_string();
+                let statement = format!(
+                    "INSERT INTO {} (id, data) VALUES (?, ?) \
+                    ON CONFLICT (id) DO UPDATE SET data = excluded.data",
+                    this.table()
+                );
+                this.execute(statement, id, Some(data)).await?;
+                Ok(existing.is_none().then_some(id))
+            })
+            .await?;
+        self.ids.extend(ids.into_iter().flatten());
+        Ok(())
+    }
+
+    async fn delete_records(&self) -> Result<()> {
+        let order = &self.workload.order(self.ids.len());
+        self.runner
+            .run("Delete", self.ids.len(), |i| {
+                self.remove(self.ids[order[i % order.len()]])
+            })
+            .await?;
+        Ok(())
+    }
+
+    // One transaction per batch, so each batch commits once
+    async fn cleanup(&mut self) -> Result<()> {
+        for batch in self.ids.chunks(CLEANUP_BATCH) {
+            let ids: Vec<String> = batch.iter().map(Uuid::to_string).collect();
+            let statement = format!("DELETE FROM {} WHERE id = ?", self.table());
+            self.with_conn(move |conn| {
+                conn.execute_batch("BEGIN TRANSACTION")?;
+                let mut stmt = conn.prepare_cached(&statement)?;
+                for id in &ids {
+                    stmt.execute(params![id])?;
+                }
+                conn.execute_batch("COMMIT")
+            })
+            .await?;
+        }
+        self.ids.clear();
+        Ok(())
+    }
+
+    async fn scan_records(&self) -> Result<()> {
+        let scan = self.workload.scan(&self.ids);
+        self.runner.scan(scan, |scan| async move { self.scan(&scan).await }).await?;
+        Ok(())
+    }
+
+    async fn read_records_by_data(&self) -> Result<()> {
+        let keys = self.workload.read_keys(self.ids.len())?;
+        let keys = &keys;
+        self.runner
+            .run("Read by data", keys.len(), |i| async move {
+                let data = self.workload.value(keys[i % keys.len()]).to_string();
+                let value = data.clone();
+                let statement = format!("SELECT COUNT(*) FROM {} WHERE data = ?", self.table());
+                let matches: i64 = self
+                    .with_conn(move |conn| {
+                        conn.prepare_cached(&statement)?
+                            .query_row(params![data], |row| row.get(0))
+                    })
+                    .await?;
+                if matches == 0 && self.workload.verifies(i) {
+                    return Err(anyhow::anyhow!("No record holds the data {:?}", value));
+                }
+                Ok(())
+            })
+            .await?;
+        Ok(())
+    }
+
+    async fn transaction(&mut self, _count: usize) -> Result<()> {
+        Err(anyhow::anyhow!("txn isn't supported for DuckDB"))
+    }
+
+    async fn batch_read(&self) -> Result<()> {
+        Err(anyhow::anyhow!("batch_read isn't supported for DuckDB"))
+    }
+
+    async fn count_records(&self) -> Result<usize> {
+        let statement = format!("SELECT COUNT(*) FROM {}", self.table());
+        self.with_conn(move |conn| {
+            conn.query_row(&statement, [], |row| row.get::<_, i64>(0))
+                .map(|count| count as usize)
+        })
+        .await
+    }
+
+    fn tracked_ids(&self) -> usize {
+        self.ids.len()
+    }
+
+    fn load_ids(&mut self, ids: &[String]) -> Result<()> {
+        self.ids.extend(parse_uuids(ids)?);
+        Ok(())
+    }
+
+    async fn reset(&mut self, purge: bool) -> Result<()> {
+        if purge {
+            return Err(anyhow::anyhow!("--purge isn't supported for DuckDB"));
+        }
+        self.ids.clear();
+        Ok(())
+    }
+
+    async fn create_record(&self, data: &str) -> Result<String> {
+        let id = self.workload.new_ids().id(0);
+        self.insert(id, data).await?;
+        Ok(id.to_string())
+    }
+
+    async fn read_record(&self, key: &str) -> Result<()> {
+        self.select(key.parse::<Uuid>()?, self.workload.read_projection).await?;
+        Ok(())
+    }
+
+    async fn update_record(&self, key: &str, data: &str) -> Result<()> {
+        self.update(key.parse::<Uuid>()?, data.to_string()).await
+    }
+
+    async fn delete_record(&self, key: &str) -> Result<()> {
+        self.remove(key.parse::<Uuid>()?).await
+    }
+
+    async fn create_vectors(&self, _count: usize) -> Result<()> {
+        Err(anyhow::anyhow!("vectors aren't supported for DuckDB"))
+    }
+
+    async fn search_vectors(&self, _count: usize) -> Result<()> {
+        Err(anyhow::anyhow!("vectors aren't supported for DuckDB"))
+    }
+
+    async fn dry_run(&mut self) -> Vec<(String, Result<()>)> {
+        let id = Uuid::new_v4();
+        let mut checks = Vec::new();
+
+        let created = self.insert(id, "test_value").await;
+        let create_ok = created.is_ok();
+        checks.push(("INSERT".to_string(), created));
+
+        let read = if create_ok {
+            match self.select(id, Projection::Full).await {
+                Ok(Some(data)) if data == "test_value" => Ok(()),
+                Ok(data) => Err(anyhow::anyhow!("unexpected row: {:?}", data)),
+                Err(e) => Err(e),
+            }
+        } else {
+            Err(anyhow::anyhow!("skipped, create failed"))
+        };
+        checks.push(("SELECT".to_string(), read));
+
+        let deleted = if create_ok {
+            self.remove(id).await
+        } else {
+            Err(anyhow::anyhow!("skipped, create failed"))
+        };
+        checks.push(("DELETE".to_string(), deleted));
+
+        checks
+    }
+}
+
+impl DuckDbClient {
+    async fn scan(&self, scan: &Scan) -> Result<usize> {
+        let limit = scan.limit.unwrap_or(100) as i64;
+        let offset = scan.start.unwrap_or(0) as i64;
+        let columns = match scan.projection()? {
+            Projection::Count => return self.count_records().await,
+            Projection::Id => "id",
+            Projection::Full => "id, data",
+        };
+        let query = format!("SELECT {} FROM {} LIMIT ? OFFSET ?", columns, self.table());
+        let checking = scan.checking();
+        let (count, ids) = self
+            .with_conn(move |conn| {
+                let mut stmt = conn.prepare_cached(&query)?;
+                let mut rows = stmt.query(params![limit, offset])?;
+                let (mut count, mut ids) = (0, Vec::new());
+                while let Some(row) = rows.next()? {
+                    count += 1;
+                    if checking {
+                        ids.push(row.get(0)?);
+                    }
+                }
+                Ok((count, ids))
+            })
+            .await?;
+        scan.saw(ids);
+        Ok(count)
+    }
+}
+
+// Engine for DuckDB, the endpoint is the database file path or `:memory:`. A database file can
+// only be opened once per process, so clients get connections cloned from the engine's, which
+// also has them share one in-memory database rather than each get their own.
+pub struct DuckDbEngine {
+    conn: Mutex<Connection>,
+    runner: Runner,
+    workload: Workload,
+}
+
+#[async_trait]
+impl BenchmarkEngine for DuckDbEngine {
+    async fn setup(options: &Benchmark) -> Result<Self> {
+        let conn = match options.endpoint.as_deref().unwrap_or(":memory:") {
+            ":memory:" => Connection::open_in_memory()?,
+            path => Connection::open(path)?,
+        };
+        Ok(Self {
+            conn: Mutex::new(conn),
+            runner: options.runner.clone(),
+            workload: options.workload.clone(),
+        })
+    }
+
+    async fn create_client(&self) -> Result<Box<dyn BenchmarkClient>> {
+        let conn = self.conn.lock().unwrap().try_clone()?;
+        let client = DuckDbClient::new(conn, self.runner.clone(), self.workload.clone());
+        client.startup().await?;
+        Ok(Box::new(client))
+    }
+}
@@ -0,0 +1,131 @@
+// Every engine the benchmark can run, registered once by the name `--database` takes, so adding
+// an engine means a `Database` value and a line in `ENGINES` rather than a match arm per use
+
+use crate::cassandra::CassandraEngine;
+use crate::clickhouse::ClickHouseEngine;
+use crate::dgraph::DgraphEngine;
+use crate::duckdb::DuckDbEngine;
+use crate::helixdb::HelixDBEngine;
+use crate::libsql::LibSqlEngine;
+use crate::memgraph::MemgraphEngine;
+use crate::milvus::MilvusEngine;
+use crate::mysql::MySqlEngine;
+use crate::neo4j::Neo4jEngine;
+use crate::opensearch::OpenSearchEngine;
+use crate::sqlite::SqliteEngine;
+use crate::types::{Benchmark, BenchmarkClient, BenchmarkEngine, Database};
+use crate::weaviate::WeaviateEngine;
+use anyhow::Result;
+use clap::ValueEnum;
+use futures::future::BoxFuture;
+use std::time::{Duration, Instant};
+use tracing::debug;
+
+type Setup = for<'a> fn(&'a Benchmark) -> BoxFuture<'a, Result<Box<dyn BenchmarkEngine>>>;
+
+pub struct Engine {
+    /// Name `--database` takes, the `Database` value without its aliases
+    pub name: &'static str,
+    /// Name results are printed under
+    pub display_name: &'static str,
+    /// Whether the engine runs `create_vectors` and `search_vectors`
+    pub vectors: bool,
+    pub setup: Setup,
+}
+
+const fn engine<E: BenchmarkEngine + 'static>(
+    name: &'static str,
+    display_name: &'static str,
+    vectors: bool,
+) -> Engine {
+    Engine {
+        name,
+        display_name,
+        vectors,
+        setup: setup_engine::<E>,
+    }
+}
+
+fn setup_engine<E: BenchmarkEngine + 'static>(
+    options: &Benchmark,
+) -> BoxFuture<'_, Result<Box<dyn BenchmarkEngine>>> {
+    Box::pin(async move { Ok(Box::new(E::setup(options).await?) as Box<dyn BenchmarkEngine>) })
+}
+
+pub const ENGINES: &[Engine] = &[
+    engine::<HelixDBEngine>("helixdb", "HelixDB", true),
+    engine::<Neo4jEngine>("neo4j", "Neo4j", false),
+    engine::<SqliteEngine>("sqlite", "SQLite", false),
+    engine::<DuckDbEngine>("duckdb", "DuckDB", false),
+    engine::<LibSqlEngine>("libsql", "libSQL", false),
+    engine::<MemgraphEngine>("memgraph", "Memgraph", false),
+    engine::<DgraphEngine>("dgraph", "Dgraph", false),
+    engine::<ClickHouseEngine>("clickhouse", "ClickHouse", false),
+    engine::<WeaviateEngine>("weaviate", "Weaviate", true),
+    engine::<MySqlEngine>("mysql", "MySQL", false),
+    engine::<CassandraEngine>("cassandra", "Cassandra", false),
+    engine::<MilvusEngine>("milvus", "Milvus", true),
+    engine::<OpenSearchEngine>("opensearch", "OpenSearch", true),
+];
+
+/// The engine registered for a `--database` value
+pub fn lookup(database: Database) -> Result<&'static Engine> {
+    let value = database.to_possible_value().expect("every database has a name");
+    ENGINES
+        .iter()
+        .find(|engine| engine.name == value.get_name())
+        .ok_or_else(|| anyhow::anyhow!("No engine registered for {}", value.get_name()))
+}
+
+/// Whether the database runs the vector operations
+pub fn supports_vectors(database: Database) -> bool {
+    lookup(database).is_ok_and(|engine| engine.vectors)
+}
+
+/// Connects to the database the options name and gets it ready for clients
+pub async fn setup(options: &Benchmark) -> Result<Box<dyn BenchmarkEngine>> {
+    (lookup(options.database)?.setup)(options).await
+}
+
+/// Sets the engine up and starts its first client. With `wait_for_ready` a failure of either is
+/// retried, backing off from a quarter of a second up to five, until the wait runs out.
+pub async fn connect(
+    options: &Benchmark,
+) -> Result<(Box<dyn BenchmarkEngine>, Box<dyn BenchmarkClient>)> {
+    let attempt = || async {
+        let engine = setup(options).await?;
+        let client = engine.create_client().await?;
+        Ok::<_, anyhow::Error>((engine, client))
+    };
+    let Some(wait) = options.wait_for_ready else {
+        return attempt().await;
+    };
+    let deadline = Instant::now() + wait;
+    let mut backoff = Duration::from_millis(250);
+    let mut waiting = false;
+    loop {
+        let error = match attempt().await {
+            Ok(ready) => return Ok(ready),
+            Err(e) => e,
+        };
+        let endpoint = match &options.endpoint {
+            Some(endpoint) => endpoint.as_str(),
+            None => lookup(options.database)?.display_name,
+        };
+        let now = Instant::now();
+        if now >= deadline {
+            return Err(error.context(format!(
+                "{} wasn't ready within {}s",
+                endpoint,
+                wait.as_secs()
+            )));
+        }
+        if !waiting {
+            eprintln!("Waiting for {}...", endpoint);
+            waiting = true;
+        }
+        debug!(error = %format!("{:#}", error), ?backoff, "not ready");
+        tokio::time::sleep(backoff.min(deadline - now)).await;
+        backoff = (backoff * 2).min(Duration::from_secs(5));
+    }
+}
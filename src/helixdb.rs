@@ -1,211 +1,896 @@
 use crate::{
-    types::{Benchmark, BenchmarkClient, BenchmarkEngine, Projection, Scan},
+    runner::Runner,
+    types::{
+        Benchmark, BenchmarkClient, BenchmarkEngine, BodyEncoding, EndpointMap, Precision,
+        Projection, Protocol, Scan, StatusError, UpdateMode, Workload, CLEANUP_BATCH,
+    },
     utils::*,
 };
 use anyhow::Result;
 use async_trait::async_trait;
-use reqwest::Client;
+use flate2::{write::GzEncoder, Compression};
+use reqwest::header::{ACCEPT, CONTENT_ENCODING, CONTENT_TYPE};
+use reqwest::{Client, Version};
 use serde_json::{json, Value};
-use uuid::Uuid;
-use indicatif::{ProgressBar, ProgressStyle};
-use std::time::Duration;
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::Path;
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+use tonic::codegen::http::uri::PathAndQuery;
+use tonic::transport::Channel;
+use tracing::{debug, info, trace, warn};
+
+// The one method of the `helix.HelixDB` service in `helixdb-cfg/helix.proto`. Its messages are
+// written out here rather than generated, so building doesn't need protoc.
+const GRPC_QUERY: &str = "/helix.HelixDB/Query";
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct QueryRequest {
+    /// Route name of the query, without the leading slash
+    #[prost(string, tag = "1")]
+    query: String,
+    /// The JSON body the REST route takes
+    #[prost(bytes = "vec", tag = "2")]
+    body: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct QueryResponse {
+    /// The JSON the REST route answers with
+    #[prost(bytes = "vec", tag = "1")]
+    body: Vec<u8>,
+}
+
+fn encode(encoding: BodyEncoding, body: &Value) -> Result<Vec<u8>> {
+    match encoding {
+        BodyEncoding::Json => Ok(serde_json::to_vec(body)?),
+        BodyEncoding::Msgpack => Ok(rmp_serde::to_vec_named(body)?),
+        BodyEncoding::Cbor => {
+            let mut bytes = Vec::new();
+            ciborium::into_writer(body, &mut bytes)?;
+            Ok(bytes)
+        }
+    }
+}
+
+// A response body by its Content-Type, JSON unless it says MessagePack or CBOR
+fn decode(content_type: Option<&str>, bytes: &[u8]) -> Result<Value> {
+    match content_type {
+        Some(ty) if ty.contains("msgpack") => Ok(rmp_serde::from_slice(bytes)?),
+        Some(ty) if ty.contains("cbor") => Ok(ciborium::from_reader(bytes)?),
+        _ => Ok(serde_json::from_slice(bytes)?),
+    }
+}
+
+fn gzip(body: &[u8]) -> Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::fast());
+    encoder.write_all(body)?;
+    Ok(encoder.finish()?)
+}
+
+// The id HelixDB assigned a created record. Ids are normally UUIDs, but string and integer keys
+// are tracked as they are rather than rejected.
+fn record_id(res: &Value) -> Result<String> {
+    match &res["record"][0]["id"] {
+        Value::String(id) => Ok(id.clone()),
+        Value::Number(id) => Ok(id.to_string()),
+        _ => Err(anyhow::anyhow!("no record id in response: {}", res)),
+    }
+}
+
+// The ids of the `count` records a `transaction` created, bare or as records
+fn created_ids(res: &Value, count: usize) -> Result<Vec<String>> {
+    let created = res["created"].as_array().map(Vec::as_slice).unwrap_or_default();
+    let ids: Vec<String> = created
+        .iter()
+        .filter_map(|record| match record.get("id").unwrap_or(record) {
+            Value::String(id) => Some(id.clone()),
+            Value::Number(id) => Some(id.to_string()),
+            _ => None,
+        })
+        .collect();
+    match ids.len() == count {
+        true => Ok(ids),
+        false => Err(anyhow::anyhow!("expected {} created ids in response: {}", count, res)),
+    }
+}
+
+// Queries from `--hql-file`, keyed by the route each stands in for. A query is one or more
+// lines starting at `QUERY name(param: Type, ...) =>`, as in `helixdb-cfg/queries.hx`, and is
+// posted to the `hql` route as `{"query": <its source>, "parameters": {...}}`. Each parameter is
+// bound to the field of the same name in the body its route would have been sent, so a query
+// taking `id` can replace `read_record`, and one returning `record` is read back the same way.
+#[derive(Default)]
+struct HqlQueries(HashMap<String, HqlQuery>);
+
+struct HqlQuery {
+    name: String,
+    source: String,
+    params: Vec<String>,
+}
+
+impl HqlQueries {
+    fn load(path: &Path, paths: &EndpointMap) -> Result<Self> {
+        let source = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("Can't read --hql-file {}: {}", path.display(), e))?;
+        let queries = Self::parse(&source)?;
+        let routes = query_routes(paths);
+        if let Some(name) = queries.0.keys().find(|route| !routes.contains(&route.as_str())) {
+            return Err(anyhow::anyhow!(
+                "HQL query {} isn't named after a route the benchmark sends",
+                name.trim_start_matches('/')
+            ));
+        }
+        Ok(queries)
+    }
+
+    fn parse(source: &str) -> Result<Self> {
+        let mut sources: Vec<String> = Vec::new();
+        for line in source.lines() {
+            if line.trim_start().starts_with("QUERY ") {
+                sources.push(String::new());
+            }
+            if let Some(query) = sources.last_mut() {
+                query.push_str(line);
+                query.push('\n');
+            }
+        }
+        let mut queries = HashMap::new();
+        for source in sources {
+            let query = HqlQuery::parse(source.trim_end().to_string())?;
+            let route = format!("/{}", query.name);
+            if let Some(query) = queries.insert(route, query) {
+                return Err(anyhow::anyhow!("HQL query {} is defined twice", query.name));
+            }
+        }
+        if queries.is_empty() {
+            return Err(anyhow::anyhow!("--hql-file has no QUERY definitions"));
+        }
+        Ok(Self(queries))
+    }
+
+    fn get(&self, path: &str) -> Option<&HqlQuery> {
+        self.0.get(path)
+    }
+}
+
+impl HqlQuery {
+    // Takes the name and parameter names from the `QUERY name(param: Type, ...)` signature
+    fn parse(source: String) -> Result<Self> {
+        let signature = source.trim_start().trim_start_matches("QUERY").trim_start();
+        let (name, rest) = signature
+            .split_once('(')
+            .ok_or_else(|| anyhow::anyhow!("HQL query without parameters: {}", source))?;
+        let (params, _) = rest
+            .split_once(')')
+            .ok_or_else(|| anyhow::anyhow!("HQL query with unclosed parameters: {}", source))?;
+        let name = name.trim().to_string();
+        if !is_identifier(&name) {
+            return Err(anyhow::anyhow!("HQL query has an invalid name: {}", source));
+        }
+        let params = params
+            .split(',')
+            .filter(|param| !param.trim().is_empty())
+            .map(|param| match param.split_once(':') {
+                Some((param, _)) if is_identifier(param.trim()) => Ok(param.trim().to_string()),
+                _ => Err(anyhow::anyhow!("HQL query {} has an invalid parameter {}", name, param)),
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { name, source, params })
+    }
+
+    // The query's parameters, taken by name from the body its route would have been sent
+    fn bind(&self, body: &Value) -> Result<Value> {
+        let mut parameters = serde_json::Map::new();
+        for param in &self.params {
+            let value = body.get(param).ok_or_else(|| {
+                anyhow::anyhow!(
+                    "HQL query {} takes {}, which isn't in the body its route is sent: {}",
+                    self.name,
+                    param,
+                    body
+                )
+            })?;
+            parameters.insert(param.clone(), value.clone());
+        }
+        Ok(Value::Object(parameters))
+    }
+}
+
+// Routes of the queries the benchmark sends, which a `--hql-file` query can stand in for
+fn query_routes(paths: &EndpointMap) -> Vec<&str> {
+    [
+        &paths.create_record,
+        &paths.read_record,
+        &paths.read_record_id,
+        &paths.update_record,
+        &paths.upsert_record,
+        &paths.delete_record,
+        &paths.delete_records,
+        &paths.delete_all_records,
+        &paths.scan_records,
+        &paths.scan_record_ids,
+        &paths.read_records_by_data,
+        &paths.filter_records_by_data,
+        &paths.count_records,
+        &paths.create_vector,
+        &paths.search_vector,
+        &paths.transaction,
+        &paths.read_records_batch,
+    ]
+    .into_iter()
+    .map(String::as_str)
+    .collect()
+}
 
 struct HelixDBClient {
     endpoint: String,
-    client: Client,
-    ids: Vec<Uuid>,
+    protocol: Protocol,
+    paths: EndpointMap,
+    client: ReconnectingClient,
+    runner: Runner,
+    workload: Workload,
+    precision: Precision,
+    http2: bool,
+    // Whether vector bodies are gzipped
+    compress: bool,
+    encoding: BodyEncoding,
+    phases: Option<PhaseTimer>,
+    hql: Arc<HqlQueries>,
+    // Connection queries go over with `Protocol::Grpc`
+    grpc: Option<Channel>,
+    // HTTP version of the first response, reported once
+    http_version: OnceLock<Version>,
+    // Kept as the server returned them, as ids need not be UUIDs
+    ids: Vec<String>,
 }
 
 impl HelixDBClient {
-    fn new(endpoint: String) -> Self {
+    // Clients share the engine's settings and connection pool
+    fn new(engine: &HelixDBEngine) -> Self {
         Self {
-            endpoint,
-            client: Client::new(),
+            endpoint: engine.endpoint.clone(),
+            protocol: engine.protocol,
+            paths: engine.paths.clone(),
+            client: engine.client.clone(),
+            runner: engine.runner.clone(),
+            workload: engine.workload.clone(),
+            precision: engine.precision,
+            http2: engine.http2,
+            compress: engine.compress,
+            encoding: engine.encoding,
+            phases: engine.phases.clone(),
+            hql: engine.hql.clone(),
+            grpc: engine.grpc.clone(),
+            http_version: OnceLock::new(),
             ids: Vec::new(),
         }
     }
 
+    // Vectors are generated per request so millions of them never sit in memory at once
+    fn random_vector(&self) -> Value {
+        match self.precision {
+            Precision::F32 => json!(generate_random_vector_f32(self.workload.dims)),
+            Precision::F64 => json!(generate_random_vector(self.workload.dims)),
+        }
+    }
+
+    // A record's `data`, plus fresh values for the schema's fields and its id when it has one
+    fn record_body(&self, id: Option<&str>, data: &str) -> Value {
+        let mut body = serde_json::Map::new();
+        if let Some(id) = id {
+            body.insert("id".to_string(), json!(id));
+        }
+        body.insert("data".to_string(), json!(data));
+        body.extend(self.workload.fields().unwrap_or_default());
+        Value::Object(body)
+    }
+
+    // The `i`th update: only the id and new `data` by default, or the whole record under
+    // `--update-mode full`, with the payload template's fields and the schema's
+    fn update_body(&self, id: &str, i: usize) -> Value {
+        let data = self.workload.updated_value(i);
+        if self.workload.update_mode == UpdateMode::Partial {
+            return json!({"id": id, "data": data});
+        }
+        let mut body = match self.workload.payload.as_ref().map(|payload| payload.render(i)) {
+            Some(Value::Object(fields)) => fields,
+            _ => serde_json::Map::new(),
+        };
+        body.insert("id".to_string(), json!(id));
+        body.insert("data".to_string(), json!(data));
+        body.extend(self.workload.fields().unwrap_or_default());
+        Value::Object(body)
+    }
+
     async fn make_request(&self, method: &str, path: &str, body: Option<Value>) -> Result<Value> {
+        if let Some(query) = self.hql.get(path) {
+            return self.hql_request(query, body).await;
+        }
+        match self.protocol {
+            Protocol::Rest => self.send(method, path, body).await,
+            Protocol::Graphql => self.graphql_request(path, body).await,
+            Protocol::Grpc => self.grpc_request(path, body).await,
+        }
+    }
+
+    async fn send(&self, method: &str, path: &str, body: Option<Value>) -> Result<Value> {
         let url = format!("{}{}", self.endpoint, path);
-        let request = match method {
-            "POST" => self.client.post(&url),
-            _ => unreachable!(),
-        };
-        let request = if let Some(body) = body {
-            request.json(&body)
-        } else {
-            request
+        // The body is encoded and the response decoded by hand rather than with `json`, so each
+        // phase can be timed on its own
+        let start = Instant::now();
+        let body = match body {
+            Some(body) => {
+                trace!(%method, %path, %body, "request");
+                let body = encode(self.encoding, &body)?;
+                Some(match self.compresses(path) {
+                    true => gzip(&body)?,
+                    false => body,
+                })
+            }
+            None => {
+                trace!(%method, %path, "request");
+                None
+            }
         };
-        let response = request.send().await.map_err(
-            |e| {
-                println!("Request failed: {}", e);
-                anyhow::anyhow!("Request failed: {}", e)
+        let serialize = start.elapsed();
+        let start = Instant::now();
+        // Built afresh for each attempt, as the client may have been rebuilt in between
+        let request = |client: &Client| {
+            let request = match method {
+                "POST" => client.post(&url),
+                _ => unreachable!(),
+            };
+            let Some(body) = &body else {
+                return request;
+            };
+            let content_type = self.encoding.content_type();
+            let request = request.header(CONTENT_TYPE, content_type).header(ACCEPT, content_type);
+            match self.compresses(path) {
+                true => request.header(CONTENT_ENCODING, "gzip").body(body.clone()),
+                false => request.body(body.clone()),
             }
-        )?;
+        };
+        // Only reads are sent again after a reconnect, as a write may already have been applied
+        let response = match self.is_query(path) {
+            true => self.client.send_idempotent(request).await,
+            false => self.client.send(request).await,
+        };
+        let response = response.map_err(|e| {
+            debug!(%method, %path, error = %e, "request failed");
+            e
+        })?;
+        debug!(%method, %path, status = %response.status(), "response");
+        if self.http_version.set(response.version()).is_ok() {
+            info!(version = ?response.version(), "HelixDB responded");
+        }
         if response.status().is_success() {
-            response.json::<Value>().await.map_err(Into::into)
+            let content_type = response.headers().get(CONTENT_TYPE).cloned();
+            let bytes = response.bytes().await?;
+            let network = start.elapsed();
+            let start = Instant::now();
+            let res = decode(content_type.as_ref().and_then(|ty| ty.to_str().ok()), &bytes)?;
+            self.record_phases(path, serialize, network, start.elapsed());
+            trace!(%path, response = %res, "response body");
+            Ok(res)
         } else {
-            Err(anyhow::anyhow!("Request failed: {}", response.status()))
+            Err(StatusError::new(response.status(), "").into())
+        }
+    }
+
+    fn record_phases(
+        &self,
+        path: &str,
+        serialize: Duration,
+        network: Duration,
+        deserialize: Duration,
+    ) {
+        if let Some(phases) = &self.phases {
+            phases.record(path, serialize, network, deserialize);
         }
     }
+
+    // Posts a query as a GraphQL operation instead. The gateway is expected to expose each
+    // query as a field named after its route, taking the same arguments and returning the REST
+    // response as a JSON scalar, so callers see the same response either way.
+    async fn graphql_request(&self, path: &str, body: Option<Value>) -> Result<Value> {
+        let field = path.trim_start_matches('/');
+        let variables = body.unwrap_or_else(|| json!({}));
+        let args = variables.as_object().map(|args| {
+            args.iter()
+                .map(|(name, value)| (name.as_str(), graphql_type(name, value)))
+                .collect::<Vec<_>>()
+        });
+        let args = args.unwrap_or_default();
+        let kind = match self.is_query(path) {
+            true => "query",
+            false => "mutation",
+        };
+        let document = match args.is_empty() {
+            true => format!("{} {{ {} }}", kind, field),
+            false => format!(
+                "{}({}) {{ {}({}) }}",
+                kind,
+                args.iter()
+                    .map(|(name, ty)| format!("${}: {}", name, ty))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                field,
+                args.iter()
+                    .map(|(name, _)| format!("{}: ${}", name, name))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            ),
+        };
+        let body = json!({"query": document, "variables": variables});
+        let res = self.send("POST", &self.paths.graphql, Some(body)).await?;
+        match res["errors"].as_array() {
+            Some(errors) if !errors.is_empty() => {
+                Err(anyhow::anyhow!("GraphQL error: {}", errors[0]["message"]))
+            }
+            _ => Ok(res["data"][field].clone()),
+        }
+    }
+
+    // Posts a query from `--hql-file` instead of calling the route it replaces
+    async fn hql_request(&self, query: &HqlQuery, body: Option<Value>) -> Result<Value> {
+        let parameters = query.bind(&body.unwrap_or_else(|| json!({})))?;
+        let body = json!({"query": query.source, "parameters": parameters});
+        self.send("POST", &self.paths.hql, Some(body)).await
+    }
+
+    // Sends a query through the gRPC service instead, with the same JSON body and response as
+    // its REST route, timing the same phases
+    async fn grpc_request(&self, path: &str, body: Option<Value>) -> Result<Value> {
+        let channel = self
+            .grpc
+            .clone()
+            .ok_or_else(|| anyhow::anyhow!("No gRPC connection to HelixDB"))?;
+        let start = Instant::now();
+        trace!(%path, ?body, "gRPC request");
+        let request = QueryRequest {
+            query: path.trim_start_matches('/').to_string(),
+            body: body.map(|body| serde_json::to_vec(&body)).transpose()?.unwrap_or_default(),
+        };
+        let serialize = start.elapsed();
+        let start = Instant::now();
+        let response = grpc_query(channel, request).await?;
+        let network = start.elapsed();
+        let start = Instant::now();
+        let res: Value = serde_json::from_slice(&response.body)?;
+        self.record_phases(path, serialize, network, start.elapsed());
+        trace!(%path, response = %res, "gRPC response");
+        Ok(res)
+    }
+
+    // Only vector bodies are large enough for gzip to pay for itself
+    fn compresses(&self, path: &str) -> bool {
+        self.compress && (path == self.paths.create_vector || path == self.paths.search_vector)
+    }
+
+    fn is_query(&self, path: &str) -> bool {
+        let paths = &self.paths;
+        [
+            &paths.read_record,
+            &paths.read_record_id,
+            &paths.scan_records,
+            &paths.scan_record_ids,
+            &paths.read_records_by_data,
+            &paths.filter_records_by_data,
+            &paths.read_records_batch,
+            &paths.count_records,
+            &paths.search_vector,
+        ]
+        .iter()
+        .any(|query| query.as_str() == path)
+    }
+}
+
+async fn grpc_query(channel: Channel, request: QueryRequest) -> Result<QueryResponse> {
+    let mut grpc = tonic::client::Grpc::new(channel);
+    grpc.ready().await?;
+    let response = grpc
+        .unary(
+            tonic::Request::new(request),
+            PathAndQuery::from_static(GRPC_QUERY),
+            tonic::codec::ProstCodec::default(),
+        )
+        .await
+        .map_err(|status| anyhow::anyhow!("Request failed: {}", status))?;
+    Ok(response.into_inner())
+}
+
+// Connects to the gRPC service and checks it answers, with a count since that reads nothing
+// back but a number. Connecting alone succeeds against any server that accepts TCP.
+async fn grpc_channel(endpoint: &str, paths: &EndpointMap) -> Result<Channel> {
+    let channel = Channel::from_shared(endpoint.to_string())?.connect().await?;
+    let probe = QueryRequest {
+        query: paths.count_records.trim_start_matches('/').to_string(),
+        body: Vec::new(),
+    };
+    grpc_query(channel.clone(), probe).await?;
+    Ok(channel)
+}
+
+// GraphQL type of a query argument, from its name for ids and otherwise from its value
+fn graphql_type(name: &str, value: &Value) -> &'static str {
+    match (name, value) {
+        ("id", _) => "ID!",
+        ("ids", _) => "[ID!]!",
+        (_, Value::Array(items)) if items.first().is_some_and(Value::is_number) => "[Float!]!",
+        (_, Value::Array(_)) => "[String!]!",
+        (_, Value::Number(n)) if n.is_f64() => "Float!",
+        (_, Value::Number(_)) => "Int!",
+        (_, Value::Bool(_)) => "Boolean!",
+        _ => "String!",
+    }
 }
 
 #[async_trait]
 impl BenchmarkClient for HelixDBClient {
+    // Nothing needs creating, but the server has to be up. HelixDB has no health route, so any
+    // answer at all will do, whatever its status.
     async fn startup(&self) -> Result<()> {
-        Ok(()) // no specific startup needed; assume server is running
+        self.client.client().get(&self.endpoint).send().await?;
+        Ok(())
     }
 
     async fn create_records(&mut self, count: usize) -> Result<()> {
-        let pb = ProgressBar::new(count as u64);
-        pb.set_style(
-            ProgressStyle::default_bar()
-                .template("[{elapsed_precise}] {bar:40.cyan/blue} {pos}/{len} ({eta}) Create")
-                .unwrap()
-                .progress_chars("##-"),
-        );
-        for _ in 0..count {
-            let res = self
-                .make_request("POST", "/create_record", Some(json!({"data": "test_value"})))
-                .await?;
-            self.ids.push(
-                res["record"][0]["id"]
-                .as_str()
-                .expect("ID is not a string")
-                .parse::<Uuid>()
-                .expect("Failed to parse UUID")
-            );
-            pb.inc(1);
-        }
-        pb.finish_with_message("Create complete");
+        let this = &*self;
+        let ids = self
+            .runner
+            .run("Create", count, |i| async move {
+                let body = match &this.workload.payload {
+                    Some(payload) => payload.render(i),
+                    None => this.record_body(None, this.workload.value(i)),
+                };
+                let res = this
+                    .make_request("POST", &this.paths.create_record, Some(body))
+                    .await?;
+                record_id(&res)
+            })
+            .await?;
+        self.ids.extend(ids);
         Ok(())
     }
 
     async fn read_records(&self) -> Result<()> {
-        let pb = ProgressBar::new(self.ids.len() as u64);
-        pb.set_style(
-            ProgressStyle::default_bar()
-                .template("[{elapsed_precise}] {bar:40.cyan/blue} {pos}/{len} ({eta}) Read")
-                .unwrap()
-                .progress_chars("##-"),
-        );
-        for k in self.ids.clone().into_iter() {
-            let body = json!({"id": k.to_string()});
-            let res = self.make_request("POST", "/read_record", Some(body))
-                .await?;
-            assert!(res["record"][0]["data"] == "test_value", "data is correct");
-            pb.inc(1);
-        }
-        pb.finish_with_message("Read complete");
+        let keys = self.workload.read_keys(self.ids.len())?;
+        let keys = &keys;
+        self.runner
+            .run("Read", keys.len(), |i| async move {
+                let key = keys[i % keys.len()];
+                let body = json!({"id": self.ids[key]});
+                if let Projection::Id = self.workload.read_projection {
+                    self.make_request("POST", &self.paths.read_record_id, Some(body))
+                        .await?;
+                    return Ok(());
+                }
+                let res = self.make_request("POST", &self.paths.read_record, Some(body))
+                    .await?;
+                if let Some(expected) = self.workload.checked_value(i, key) {
+                    if res["record"][0]["data"] != expected {
+                        let id = &self.ids[key];
+                        return Err(anyhow::anyhow!("Read of {} returned the wrong data", id));
+                    }
+                }
+                if let Some(schema) = self.workload.checked_schema(i) {
+                    let record = res["record"][0].as_object().cloned().unwrap_or_default();
+                    schema.validate(&self.ids[key], &record)?;
+                }
+                Ok(())
+            })
+            .await?;
         Ok(())
     }
 
     async fn update_records(&self) -> Result<()> {
-        let pb = ProgressBar::new(self.ids.len() as u64);
-        pb.set_style(
-            ProgressStyle::default_bar()
-                .template("[{elapsed_precise}] {bar:40.cyan/blue} {pos}/{len} ({eta}) Update")
-                .unwrap()
-                .progress_chars("##-"),
-        );
-        for k in self.ids.clone().into_iter() {
-            let body = json!({"id": k.to_string(), "data": "updated_value"});
-            self.make_request("POST", "/update_record", Some(body))
-                .await?;
-            pb.inc(1);
-        }
-        pb.finish_with_message("Update complete");
+        let order = &self.workload.working_set(self.ids.len());
+        self.runner
+            .run("Update", self.ids.len(), |i| async move {
+                let id = &self.ids[order[i % order.len()]];
+                let body = self.update_body(id, i);
+                self.make_request("POST", &self.paths.update_record, Some(body))
+                    .await?;
+                Ok(())
+            })
+            .await?;
+        Ok(())
+    }
+
+    // HelixDB assigns node ids itself, so a new key comes back with a server id to track
+    async fn upsert_records(&mut self, count: usize) -> Result<()> {
+        let new_ids = self.workload.new_ids();
+        let this = &*self;
+        let ids = this
+            .runner
+            .run("Upsert", count, |i| async move {
+                let existing = upsert_key(&this.ids, i);
+                let is_new = existing.is_none();
+                let id = existing.unwrap_or_else(|| new_ids.id(i).to_string());
+                let body = this.record_body(Some(&id), this.workload.value(i));
+                let res = this
+                    .make_request("POST", &this.paths.upsert_record, Some(body))
+                    .await?;
+                match is_new {
+                    true => record_id(&res).map(Some),
+                    false => Ok(None),
+                }
+            })
+            .await?;
+        self.ids.extend(ids.into_iter().flatten());
         Ok(())
     }
 
     async fn delete_records(&self) -> Result<()> {
-        let pb = ProgressBar::new(self.ids.len() as u64);
-        pb.set_style(
-            ProgressStyle::default_bar()
-                .template("[{elapsed_precise}] {bar:40.cyan/blue} {pos}/{len} ({eta}) Delete")
-                .unwrap()
-                .progress_chars("##-"),
-        );
-        for k in self.ids.clone().into_iter() {
-            let body = json!({"id": k.to_string()});
-            self.make_request("POST", "/delete_record", Some(body))
+        let order = &self.workload.order(self.ids.len());
+        self.runner
+            .run("Delete", self.ids.len(), |i| async move {
+                let body = json!({"id": self.ids[order[i % order.len()]]});
+                self.make_request("POST", &self.paths.delete_record, Some(body))
+                    .await?;
+                Ok(())
+            })
+            .await?;
+        Ok(())
+    }
+
+    async fn cleanup(&mut self) -> Result<()> {
+        for ids in self.ids.chunks(CLEANUP_BATCH) {
+            self.make_request("POST", &self.paths.delete_records, Some(json!({"ids": ids})))
                 .await?;
-            pb.inc(1);
         }
-        pb.finish_with_message("Delete complete");
+        self.ids.clear();
         Ok(())
     }
 
     async fn scan_records(&self) -> Result<()> {
-        let count = self.ids.len();
-        let pb = ProgressBar::new_spinner();
-        pb.set_style(
-            ProgressStyle::default_spinner()
-                .template("{spinner:.green} [{elapsed_precise}] Running scan...")
-                .unwrap(),
-        );
-        pb.enable_steady_tick(Duration::from_millis(100));
-        let scan = Scan::new(Some(count), None, Projection::Full);
-        let _ = self.scan(&scan).await;
-        pb.finish_with_message("Scan complete");
+        let scan = self.workload.scan(&self.ids);
+        self.runner.scan(scan, |scan| async move { self.scan(&scan).await }).await?;
+        Ok(())
+    }
+
+    // HelixDB indexes are declared in the schema, so rather than create or drop one this picks
+    // between the indexed lookup and a filter over every record
+    async fn read_records_by_data(&self) -> Result<()> {
+        let path = match self.workload.data_index {
+            Some(false) => &self.paths.filter_records_by_data,
+            _ => &self.paths.read_records_by_data,
+        };
+        let keys = self.workload.read_keys(self.ids.len())?;
+        let keys = &keys;
+        self.runner
+            .run("Read by data", keys.len(), |i| async move {
+                let body = json!({"data": self.workload.value(keys[i % keys.len()])});
+                self.make_request("POST", path, Some(body)).await?;
+                Ok(())
+            })
+            .await?;
+        Ok(())
+    }
+
+    // A HelixDB query runs as one transaction, so the `transaction` query creates, reads back
+    // and updates the whole batch in a single request. It doesn't return the ids the records
+    // were given, so they aren't tracked.
+    async fn transaction(&mut self, count: usize) -> Result<()> {
+        let ops = self.workload.ops_per_txn;
+        let this = &*self;
+        let ids = this
+            .runner
+            .run("Txn", count, |i| async move {
+                let records: Vec<Value> = (i * ops..(i + 1) * ops)
+                    .map(|n| {
+                        let mut record = this.record_body(None, this.workload.value(n));
+                        record["updated"] = json!(this.workload.updated_value(n));
+                        record
+                    })
+                    .collect();
+                let body = json!({"records": records});
+                let res = this.make_request("POST", &this.paths.transaction, Some(body)).await?;
+                created_ids(&res, ops)
+            })
+            .await?;
+        self.ids.extend(ids.into_iter().flatten());
+        Ok(())
+    }
+
+    async fn batch_read(&self) -> Result<()> {
+        let keys = self.workload.read_keys(self.ids.len())?;
+        let batches: Vec<&[usize]> = keys.chunks(self.workload.batch_size).collect();
+        let batches = &batches;
+        self.runner
+            .run("Batch read", batches.len(), |i| async move {
+                let batch = batches[i % batches.len()];
+                let ids: Vec<&str> = batch.iter().map(|&key| self.ids[key].as_str()).collect();
+                let body = json!({"ids": ids});
+                let res = self.make_request("POST", &self.paths.read_records_batch, Some(body))
+                    .await?;
+                let found = res["records"].as_array().map_or(0, Vec::len);
+                if found < batch.len() && self.workload.verifies(i) {
+                    return Err(anyhow::anyhow!(
+                        "Batch read returned {} of {} records",
+                        found,
+                        batch.len()
+                    ));
+                }
+                Ok(())
+            })
+            .await?;
         Ok(())
     }
 
     async fn count_records(&self) -> Result<usize> {
         let res = self
-            .make_request("POST", "/count_records", None)
+            .make_request("POST", &self.paths.count_records, None)
             .await?;
-        println!("res: {:?}", res);
-        Ok(0)
-        //let count = res.get("count").unwrap();
-        //Ok(count.as_u64().unwrap_or(0) as usize)
+        debug!(response = %res, "count records");
+        parse_count(&res)
     }
 
-    async fn create_vectors(&self, count: usize) -> Result<()> {
-        let pb = ProgressBar::new(count as u64);
-        pb.set_style(
-            ProgressStyle::default_bar()
-                .template("[{elapsed_precise}] {bar:40.cyan/blue} {pos}/{len} ({eta}) Create vectors")
-                .unwrap()
-                .progress_chars("##-"),
-        );
-        let rnd_vectors = generate_random_vectors(count, 768);
-        for vec in rnd_vectors {
-            let _ = self
-                .make_request("POST", "/create_vector", Some(json!({"vec": vec})))
+    fn tracked_ids(&self) -> usize {
+        self.ids.len()
+    }
+
+    fn load_ids(&mut self, ids: &[String]) -> Result<()> {
+        self.ids.extend(ids.iter().cloned());
+        Ok(())
+    }
+
+    async fn reset(&mut self, purge: bool) -> Result<()> {
+        if purge {
+            self.make_request("POST", &self.paths.delete_all_records, Some(json!({})))
                 .await?;
-            pb.inc(1);
         }
-        pb.finish_with_message("Create complete");
+        self.ids.clear();
+        Ok(())
+    }
+
+    async fn create_record(&self, data: &str) -> Result<String> {
+        let res = self
+            .make_request("POST", &self.paths.create_record, Some(self.record_body(None, data)))
+            .await?;
+        record_id(&res)
+    }
+
+    async fn read_record(&self, key: &str) -> Result<()> {
+        let path = match self.workload.read_projection {
+            Projection::Id => &self.paths.read_record_id,
+            _ => &self.paths.read_record,
+        };
+        self.make_request("POST", path, Some(json!({"id": key})))
+            .await?;
+        Ok(())
+    }
+
+    async fn update_record(&self, key: &str, data: &str) -> Result<()> {
+        let body = json!({"id": key, "data": data});
+        self.make_request("POST", &self.paths.update_record, Some(body))
+            .await?;
+        Ok(())
+    }
+
+    async fn delete_record(&self, key: &str) -> Result<()> {
+        self.make_request("POST", &self.paths.delete_record, Some(json!({"id": key})))
+            .await?;
+        Ok(())
+    }
+
+    async fn create_vectors(&self, count: usize) -> Result<()> {
+        self.runner
+            .run("Create vectors", count, |_| async move {
+                let vec = self.random_vector();
+                self.make_request("POST", &self.paths.create_vector, Some(json!({"vec": vec})))
+                    .await?;
+                self.workload.vector_created();
+                Ok(())
+            })
+            .await?;
         Ok(())
     }
 
     async fn search_vectors(&self, count: usize) -> Result<()> {
-        let pb = ProgressBar::new(count as u64);
-        pb.set_style(
-            ProgressStyle::default_bar()
-                .template("[{elapsed_precise}] {bar:40.cyan/blue} {pos}/{len} ({eta}) Search vectors")
-                .unwrap()
-                .progress_chars("##-"),
-        );
-        let rnd_vectors = generate_random_vectors(count, 768);
-        for vec in rnd_vectors {
-            let _ = self
-                .make_request("POST", "/search_vector", Some(json!({"query": vec, "k": 7})))
-                .await?;
-            pb.inc(1);
-        }
-        pb.finish_with_message("Create complete");
+        self.runner
+            .run("Search vectors", count, |_| async move {
+                let vec = self.random_vector();
+                let body = json!({"query": vec, "k": self.workload.k});
+                let res = self.make_request("POST", &self.paths.search_vector, Some(body)).await?;
+                self.workload.check_search_results(res["vec"].as_array().map_or(0, Vec::len))
+            })
+            .await?;
         Ok(())
     }
 
+    // The version route may answer with JSON holding a `version` field or with the bare string
+    async fn server_version(&self) -> Option<String> {
+        let url = format!("{}{}", self.endpoint, self.paths.version);
+        let response = match self.client.client().get(&url).send().await {
+            Ok(response) if response.status().is_success() => response,
+            Ok(response) => {
+                debug!(status = %response.status(), "no server version");
+                return None;
+            }
+            Err(e) => {
+                debug!(error = %e, "no server version");
+                return None;
+            }
+        };
+        let body = response.text().await.ok()?;
+        let version = match serde_json::from_str::<Value>(&body) {
+            Ok(Value::Object(res)) => res.get("version")?.as_str()?.to_string(),
+            Ok(Value::String(version)) => version,
+            _ => body.trim().to_string(),
+        };
+        (!version.is_empty()).then_some(version)
+    }
+
+    // Search results carry the vectors but not ids this client could match against, since
+    // `create_vector` doesn't return the id it assigned
+    async fn vector_recall(&self, _queries: usize) -> Result<Option<f64>> {
+        Ok(None)
+    }
+
+    async fn dry_run(&mut self) -> Vec<(String, Result<()>)> {
+        let mut checks = Vec::new();
+
+        let created = self
+            .make_request("POST", &self.paths.create_record, Some(json!({"data": "test_value"})))
+            .await
+            .and_then(|res| record_id(&res));
+        let id = created.as_ref().ok().cloned();
+        checks.push((self.paths.create_record.clone(), created.map(|_| ())));
+
+        let read = match &id {
+            Some(id) => self
+                .make_request("POST", &self.paths.read_record, Some(json!({"id": id})))
+                .await
+                .and_then(|res| {
+                    if res["record"][0]["data"] == "test_value" {
+                        Ok(())
+                    } else {
+                        Err(anyhow::anyhow!("unexpected read response: {}", res))
+                    }
+                }),
+            None => Err(anyhow::anyhow!("skipped, create failed")),
+        };
+        checks.push((self.paths.read_record.clone(), read));
+
+        let deleted = match id {
+            Some(id) => self
+                .make_request("POST", &self.paths.delete_record, Some(json!({"id": id})))
+                .await
+                .map(|_| ()),
+            None => Err(anyhow::anyhow!("skipped, create failed")),
+        };
+        checks.push((self.paths.delete_record.clone(), deleted));
+
+        if self.protocol == Protocol::Grpc {
+            return checks;
+        }
+        let version = match self.http_version.get() {
+            Some(&version) if self.http2 && version != Version::HTTP_2 => Err(anyhow::anyhow!(
+                "--http2 is set but the server answered over {:?}",
+                version
+            )),
+            Some(_) => Ok(()),
+            None => Err(anyhow::anyhow!("no response received")),
+        };
+        let name = match self.http_version.get() {
+            Some(version) => format!("HTTP version {:?}", version),
+            None => "HTTP version".to_string(),
+        };
+        checks.push((name, version));
+
+        checks
+    }
+
     /*
     async fn bulk_create(&self, count: usize) -> Result<()> {
         let body = json!({"count": count, "data": "test_value"});
         let res = self
             .make_request("POST", "/insert", Some(body))
             .await?;
-        println!("Bulk create result: {:?}", res);
+        debug!(response = %res, "bulk create");
         Ok(())
     }
 
@@ -214,7 +899,7 @@ impl BenchmarkClient for HelixDBClient {
         let res = self
             .make_request("POST", "/huge_traversal", Some(body))
             .await?;
-        println!("Huge traversal result: {:?}", res);
+        debug!(response = %res, "huge traversal");
         Ok(())
     }
     */
@@ -225,25 +910,52 @@ impl HelixDBClient {
         let limit = scan.limit.unwrap_or(100) as i64;
         let offset = scan.start.unwrap_or(0) as i64;
         match scan.projection()? {
-            Projection::Id | Projection::Full => {
+            projection @ (Projection::Id | Projection::Full) => {
+                // `scan_record_ids` maps the range to ids, so only those go over the wire
+                let path = match projection {
+                    Projection::Id => &self.paths.scan_record_ids,
+                    _ => &self.paths.scan_records,
+                };
                 let body = json!({"limit": limit, "offset": offset});
-                let response = self
-                    .make_request("POST", "/scan_records", Some(body))
-                    .await?;
-                let count = response.as_array().map(|arr| arr.len()).unwrap_or(0);
-                Ok(count)
+                let response = self.make_request("POST", path, Some(body)).await?;
+                let records = response["records"].as_array().map(Vec::as_slice).unwrap_or_default();
+                // `scan_record_ids` returns the ids bare rather than as records
+                let ids = records.iter().filter_map(|record| {
+                    record["id"].as_str().or(record.as_str()).map(str::to_string)
+                });
+                scan.saw(ids);
+                Ok(records.len())
             }
             Projection::Count => {
-                let response = self.make_request("POST", "/count_records", None).await?;
-                let count = response.as_i64().unwrap_or(0) as usize;
-                Ok(count)
+                let response = self.make_request("POST", &self.paths.count_records, None).await?;
+                parse_count(&response)
             }
         }
     }
 }
 
+// `count_records` returns the count under the name of the query variable
+fn parse_count(response: &Value) -> Result<usize> {
+    response["count"]
+        .as_u64()
+        .map(|count| count as usize)
+        .ok_or_else(|| anyhow::anyhow!("no count in response: {}", response))
+}
+
 pub struct HelixDBEngine {
     endpoint: String,
+    protocol: Protocol,
+    paths: EndpointMap,
+    client: ReconnectingClient,
+    runner: Runner,
+    workload: Workload,
+    precision: Precision,
+    http2: bool,
+    compress: bool,
+    encoding: BodyEncoding,
+    phases: Option<PhaseTimer>,
+    hql: Arc<HqlQueries>,
+    grpc: Option<Channel>,
 }
 
 #[async_trait]
@@ -254,12 +966,471 @@ impl BenchmarkEngine for HelixDBEngine {
             .as_deref()
             .unwrap_or("http://localhost:6969")
             .to_string();
-        Ok(Self { endpoint })
+        let hql = match &options.hql_file {
+            Some(_) if options.protocol != Protocol::Rest => {
+                return Err(anyhow::anyhow!("--hql-file only works with --protocol rest"));
+            }
+            Some(path) => HqlQueries::load(path, &options.paths)?,
+            None => HqlQueries::default(),
+        };
+        let http2 = options.http2;
+        let client = ReconnectingClient::new(options, move |builder| match http2 {
+            true => builder.http2_prior_knowledge(),
+            false => builder,
+        })?;
+        // gRPC goes to the same endpoint, and a server that doesn't answer it is still
+        // benchmarked over REST rather than not at all
+        let (protocol, grpc) = match options.protocol {
+            Protocol::Grpc => match grpc_channel(&endpoint, &options.paths).await {
+                Ok(channel) => (Protocol::Grpc, Some(channel)),
+                Err(e) => {
+                    warn!("gRPC isn't available at {} ({}), falling back to REST", endpoint, e);
+                    (Protocol::Rest, None)
+                }
+            },
+            protocol => (protocol, None),
+        };
+        // GraphQL and gRPC carry JSON whatever the REST bodies are encoded as
+        let encoding = match protocol {
+            Protocol::Rest => options.encoding,
+            _ if options.encoding != BodyEncoding::Json => {
+                warn!("--encoding only applies to REST requests, sending JSON");
+                BodyEncoding::Json
+            }
+            _ => BodyEncoding::Json,
+        };
+        Ok(Self {
+            endpoint,
+            protocol,
+            paths: options.paths.clone(),
+            client,
+            runner: options.runner.clone(),
+            workload: options.workload.clone(),
+            precision: options.precision,
+            http2: options.http2,
+            compress: options.compress,
+            encoding,
+            phases: options.phases.clone(),
+            hql: Arc::new(hql),
+            grpc,
+        })
     }
 
     async fn create_client(&self) -> Result<Box<dyn BenchmarkClient>> {
-        let client = HelixDBClient::new(self.endpoint.clone());
+        let client = HelixDBClient::new(self);
         client.startup().await?;
         Ok(Box::new(client))
     }
+
+    fn stats_url(&self) -> Option<String> {
+        Some(format!("{}{}", self.endpoint, self.paths.stats))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{BenchConfig, Database};
+    use wiremock::matchers::{body_json, body_partial_json, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    // A client for a mock server standing in for HelixDB, with the default routes and workload
+    async fn client(server: &MockServer) -> HelixDBClient {
+        let options = BenchConfig {
+            endpoint: Some(server.uri()),
+            ..Default::default()
+        };
+        let runner = Runner::new(1, None).with_quiet(true);
+        let benchmark = crate::resolve_benchmark(Database::HelixDB, &options, runner).unwrap();
+        HelixDBClient::new(&HelixDBEngine::setup(&benchmark).await.unwrap())
+    }
+
+    fn record(id: &str, data: &str) -> ResponseTemplate {
+        ResponseTemplate::new(200).set_body_json(json!({"record": [{"id": id, "data": data}]}))
+    }
+
+    #[tokio::test]
+    async fn create_sends_data_and_tracks_the_returned_ids() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/create_record"))
+            .and(body_json(json!({"data": "test_value"})))
+            .respond_with(record("a", "test_value"))
+            .expect(3)
+            .mount(&server)
+            .await;
+        let mut client = client(&server).await;
+        client.create_records(3).await.unwrap();
+        assert_eq!(client.ids, ["a", "a", "a"]);
+    }
+
+    #[tokio::test]
+    async fn create_record_returns_integer_ids_as_strings() {
+        let server = MockServer::start().await;
+        let record = json!({"record": [{"id": 7, "data": "x"}]});
+        Mock::given(path("/create_record"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(record))
+            .mount(&server)
+            .await;
+        let client = client(&server).await;
+        assert_eq!(client.create_record("x").await.unwrap(), "7");
+    }
+
+    #[tokio::test]
+    async fn upsert_keys_new_records_from_the_id_seed() {
+        let id = crate::utils::IdSource::new(Some(7), 0).id(0).to_string();
+        let server = MockServer::start().await;
+        Mock::given(path("/upsert_record"))
+            .and(body_partial_json(json!({"id": id})))
+            .respond_with(record(&id, "x"))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let mut client = client(&server).await;
+        client.workload.id_seed = Some(7);
+        client.upsert_records(1).await.unwrap();
+        assert_eq!(client.ids, [id]);
+    }
+
+    #[tokio::test]
+    async fn create_fails_without_an_id_in_the_response() {
+        let server = MockServer::start().await;
+        Mock::given(path("/create_record"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"record": []})))
+            .mount(&server)
+            .await;
+        let mut client = client(&server).await;
+        assert!(client.create_records(1).await.is_err());
+        assert!(client.ids.is_empty());
+    }
+
+    #[tokio::test]
+    async fn read_checks_the_data_returned() {
+        let server = MockServer::start().await;
+        Mock::given(path("/read_record"))
+            .and(body_json(json!({"id": "a"})))
+            .respond_with(record("a", "test_value"))
+            .mount(&server)
+            .await;
+        Mock::given(path("/read_record"))
+            .and(body_json(json!({"id": "b"})))
+            .respond_with(record("b", "something_else"))
+            .mount(&server)
+            .await;
+        let mut client = client(&server).await;
+        client.ids = vec!["a".to_string()];
+        client.read_records().await.unwrap();
+        client.ids = vec!["b".to_string()];
+        let error = client.read_records().await.unwrap_err();
+        assert!(format!("{:#}", error).contains("wrong data"), "{:#}", error);
+    }
+
+    #[tokio::test]
+    async fn update_sends_the_id_and_new_data() {
+        let server = MockServer::start().await;
+        Mock::given(path("/update_record"))
+            .and(body_json(json!({"id": "a", "data": "updated_value"})))
+            .respond_with(record("a", "updated_value"))
+            .expect(2)
+            .mount(&server)
+            .await;
+        let mut client = client(&server).await;
+        client.ids = vec!["a".to_string(), "a".to_string()];
+        client.update_records().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn update_mode_chooses_between_the_data_and_the_whole_record() {
+        let server = MockServer::start().await;
+        Mock::given(path("/update_record"))
+            .and(body_json(json!({"id": "a", "data": "updated_value"})))
+            .respond_with(record("a", "updated_value"))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let full = json!({"id": "a", "data": "updated_value", "n": 0, "tag": "x"});
+        Mock::given(path("/update_record"))
+            .and(body_json(full))
+            .respond_with(record("a", "updated_value"))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let mut client = client(&server).await;
+        let payload = PayloadTemplate::parse(r#"{"n": "{{seq}}", "tag": "x"}"#).unwrap();
+        client.workload.payload = Some(payload);
+        client.ids = vec!["a".to_string()];
+        client.update_records().await.unwrap();
+        client.workload.update_mode = UpdateMode::Full;
+        client.update_records().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn keyspace_size_confines_updates_to_the_first_records() {
+        let server = MockServer::start().await;
+        for (id, times) in [("a", 3), ("b", 2)] {
+            Mock::given(path("/update_record"))
+                .and(body_json(json!({"id": id, "data": "updated_value"})))
+                .respond_with(record(id, "updated_value"))
+                .expect(times)
+                .mount(&server)
+                .await;
+        }
+        Mock::given(path("/update_record"))
+            .respond_with(record("c", "updated_value"))
+            .expect(0)
+            .mount(&server)
+            .await;
+        let mut client = client(&server).await;
+        client.workload.keyspace_size = Some(2);
+        client.ids = ["a", "b", "c", "d", "e"].map(String::from).to_vec();
+        client.update_records().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn transaction_sends_each_batch_in_one_request() {
+        let server = MockServer::start().await;
+        let record = json!({"data": "test_value", "updated": "updated_value"});
+        Mock::given(path("/transaction"))
+            .and(body_json(json!({"records": [record, record]})))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"created": ["a", 7]})))
+            .expect(3)
+            .mount(&server)
+            .await;
+        let mut client = client(&server).await;
+        client.workload.ops_per_txn = 2;
+        client.transaction(3).await.unwrap();
+        assert_eq!(client.ids, ["a", "7", "a", "7", "a", "7"]);
+    }
+
+    #[tokio::test]
+    async fn transaction_fails_without_every_created_id() {
+        let server = MockServer::start().await;
+        Mock::given(path("/transaction"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"created": ["a"]})))
+            .mount(&server)
+            .await;
+        let mut client = client(&server).await;
+        client.workload.ops_per_txn = 2;
+        assert!(client.transaction(1).await.is_err());
+        assert!(client.ids.is_empty());
+    }
+
+    #[tokio::test]
+    async fn batch_read_asks_for_batch_size_ids_and_checks_they_all_came_back() {
+        let server = MockServer::start().await;
+        let records = |ids: &[&str]| {
+            let records: Vec<Value> = ids.iter().map(|id| json!({"id": id})).collect();
+            ResponseTemplate::new(200).set_body_json(json!({"records": records}))
+        };
+        Mock::given(path("/read_records_batch"))
+            .and(body_json(json!({"ids": ["a", "b"]})))
+            .respond_with(records(&["a", "b"]))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(path("/read_records_batch"))
+            .and(body_json(json!({"ids": ["c"]})))
+            .respond_with(records(&[]))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let mut client = client(&server).await;
+        client.workload.batch_size = 2;
+        client.ids = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+        let error = client.batch_read().await.unwrap_err();
+        assert!(format!("{:#}", error).contains("returned 0 of 1"), "{:#}", error);
+    }
+
+    #[tokio::test]
+    async fn verify_scan_fails_the_scan_when_a_page_repeats_an_id() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/scan_records"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "records": [{"id": "a", "data": "x"}, {"id": "a", "data": "x"}]
+            })))
+            .mount(&server)
+            .await;
+        let mut client = client(&server).await;
+        client.workload.verify_scan = true;
+        client.ids = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+        let error = client.scan_records().await.unwrap_err();
+        assert!(format!("{:#}", error).contains("returned 1 more than once"), "{:#}", error);
+    }
+
+    #[tokio::test]
+    async fn search_vectors_checks_the_number_of_results() {
+        let server = MockServer::start().await;
+        let vectors = |count: usize| {
+            let vectors = vec![json!({"data": [0.5, 0.5]}); count];
+            ResponseTemplate::new(200).set_body_json(json!({"vec": vectors}))
+        };
+        Mock::given(path("/search_vector"))
+            .respond_with(vectors(2))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        Mock::given(path("/search_vector")).respond_with(vectors(1)).mount(&server).await;
+        let mut client = client(&server).await;
+        client.workload.k = 2;
+        client.search_vectors(1).await.unwrap();
+        let error = client.search_vectors(1).await.unwrap_err();
+        assert!(format!("{:#}", error).contains("returned 1 results, expected 2"), "{:#}", error);
+    }
+
+    const HQL: &str = "// the application's own lookup
+QUERY read_record(id: ID) =>
+    record <- N<Record>(id)
+    RETURN record
+
+QUERY update_record(id: ID, data: String, version: I64) =>
+    record <- N<Record>(id)::UPDATE({data: data, version: version})
+    RETURN record
+";
+
+    #[test]
+    fn hql_queries_take_their_names_and_parameters_from_the_signature() {
+        let queries = HqlQueries::parse(HQL).unwrap();
+        let read = queries.get("/read_record").unwrap();
+        assert_eq!(read.params, ["id"]);
+        assert!(read.source.starts_with("QUERY read_record(id: ID) =>"));
+        assert!(read.source.ends_with("RETURN record"));
+        let update = queries.get("/update_record").unwrap();
+        assert_eq!(update.params, ["id", "data", "version"]);
+        let error = update.bind(&json!({"id": "a", "data": "b"})).unwrap_err();
+        assert!(error.to_string().contains("takes version"), "{}", error);
+        assert!(HqlQueries::parse("QUERY read_record =>").is_err());
+    }
+
+    #[tokio::test]
+    async fn hql_queries_replace_the_route_they_are_named_after() {
+        let server = MockServer::start().await;
+        let queries = HqlQueries::parse(HQL).unwrap();
+        let source = queries.get("/read_record").unwrap().source.clone();
+        Mock::given(path("/hql"))
+            .and(body_json(json!({"query": source, "parameters": {"id": "a"}})))
+            .respond_with(record("a", "test_value"))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let mut client = client(&server).await;
+        client.hql = Arc::new(queries);
+        client.ids = vec!["a".to_string()];
+        client.read_records().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn delete_sends_each_tracked_id() {
+        let server = MockServer::start().await;
+        for id in ["a", "b"] {
+            Mock::given(path("/delete_record"))
+                .and(body_json(json!({"id": id})))
+                .respond_with(ResponseTemplate::new(200).set_body_json(json!("NONE")))
+                .expect(1)
+                .mount(&server)
+                .await;
+        }
+        let mut client = client(&server).await;
+        client.ids = vec!["a".to_string(), "b".to_string()];
+        client.delete_records().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn shuffle_deletes_every_id_once_in_a_seeded_order() {
+        let server = MockServer::start().await;
+        Mock::given(path("/delete_record"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!("NONE")))
+            .mount(&server)
+            .await;
+        let mut client = client(&server).await;
+        client.workload.shuffle = true;
+        client.workload.seed = Some(7);
+        client.ids = (0..20).map(|i| i.to_string()).collect();
+        client.delete_records().await.unwrap();
+        let sent: Vec<String> = server
+            .received_requests()
+            .await
+            .unwrap()
+            .iter()
+            .map(|request| request.body_json::<Value>().unwrap()["id"].to_string())
+            .collect();
+        let expected: Vec<String> =
+            client.workload.order(20).iter().map(|i| json!(i.to_string()).to_string()).collect();
+        assert_eq!(sent, expected);
+        assert_ne!(client.workload.order(20), (0..20).collect::<Vec<_>>());
+    }
+
+    #[tokio::test]
+    async fn scan_pages_by_limit_and_offset() {
+        let server = MockServer::start().await;
+        Mock::given(path("/scan_records"))
+            .and(body_json(json!({"limit": 2, "offset": 4})))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"records": [
+                {"id": "a", "data": "test_value"},
+                {"id": "b", "data": "test_value"},
+            ]})))
+            .mount(&server)
+            .await;
+        Mock::given(path("/scan_record_ids"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"records": ["a"]})))
+            .mount(&server)
+            .await;
+        let client = client(&server).await;
+        let full = Scan::new(Some(2), Some(4), Projection::Full);
+        assert_eq!(client.scan(&full).await.unwrap(), 2);
+        let ids = Scan::new(Some(2), Some(4), Projection::Id);
+        assert_eq!(client.scan(&ids).await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn count_reads_the_count_field() {
+        let server = MockServer::start().await;
+        Mock::given(path("/count_records"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"count": 42})))
+            .mount(&server)
+            .await;
+        let client = client(&server).await;
+        assert_eq!(client.count_records().await.unwrap(), 42);
+    }
+
+    #[tokio::test]
+    async fn count_fails_without_a_count() {
+        let server = MockServer::start().await;
+        Mock::given(path("/count_records"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"total": 42})))
+            .mount(&server)
+            .await;
+        let client = client(&server).await;
+        assert!(client.count_records().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn failure_status_is_kept_on_the_error() {
+        let server = MockServer::start().await;
+        Mock::given(path("/read_record"))
+            .respond_with(ResponseTemplate::new(429))
+            .mount(&server)
+            .await;
+        let client = client(&server).await;
+        let error = client.read_record("a").await.unwrap_err();
+        let status = error.downcast_ref::<StatusError>().map(|e| e.status.as_u16());
+        assert_eq!(status, Some(429));
+    }
+
+    #[tokio::test]
+    async fn connection_errors_before_any_response_are_errors_but_not_reconnects() {
+        // Nothing listens on the port once the listener is dropped
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let options = BenchConfig {
+            endpoint: Some(format!("http://{}", listener.local_addr().unwrap())),
+            ..Default::default()
+        };
+        drop(listener);
+        let runner = Runner::new(1, None).with_quiet(true);
+        let benchmark = crate::resolve_benchmark(Database::HelixDB, &options, runner).unwrap();
+        let client = HelixDBClient::new(&HelixDBEngine::setup(&benchmark).await.unwrap());
+        let error = client.read_record("a").await.unwrap_err();
+        assert!(error.downcast_ref::<reqwest::Error>().is_some_and(|e| e.is_connect()));
+        assert_eq!(benchmark.reconnects.load(std::sync::atomic::Ordering::SeqCst), 0);
+    }
 }
@@ -0,0 +1,360 @@
+use crate::runner::Runner;
+use crate::types::{
+    Benchmark, BenchmarkClient, BenchmarkEngine, Projection, Scan, Workload, CLEANUP_BATCH,
+};
+use crate::utils::{parse_uuids, upsert_key};
+use ::libsql::{params, Builder, Connection, Database};
+use anyhow::Result;
+use async_trait::async_trait;
+use uuid::Uuid;
+
+// Token remote databases are opened with, as Turso issues them
+const AUTH_TOKEN_VAR: &str = "LIBSQL_AUTH_TOKEN";
+
+pub struct LibSqlClient {
+    conn: Connection,
+    runner: Runner,
+    workload: Workload,
+    ids: Vec<Uuid>,
+}
+
+impl LibSqlClient {
+    pub fn new(conn: Connection, runner: Runner, workload: Workload) -> Self {
+        Self {
+            conn,
+            runner,
+            workload,
+            ids: Vec::new(),
+        }
+    }
+
+    fn table(&self) -> &str {
+        self.workload.table()
+    }
+
+    async fn insert(&self, id: Uuid, data: &str) -> Result<()> {
+        let statement = format!("INSERT INTO {} (id, data) VALUES (?1, ?2)", self.table());
+        self.conn.execute(&statement, params![id.to_string(), data]).await?;
+        Ok(())
+    }
+
+    // The record's data, or its id under an id-only read projection; `None` if it doesn't exist
+    async fn select(&self, id: Uuid, projection: Projection) -> Result<Option<String>> {
+        let column = match projection {
+            Projection::Id => "id",
+            _ => "data",
+        };
+        let statement = format!("SELECT {} FROM {} WHERE id = ?1", column, self.table());
+        let mut rows = self.conn.query(&statement, params![id.to_string()]).await?;
+        match rows.next().await? {
+            Some(row) => Ok(Some(row.get::<String>(0)?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn update(&self, id: Uuid, data: &str) -> Result<()> {
+        let statement = format!("UPDATE {} SET data = ?1 WHERE id = ?2", self.table());
+        self.conn.execute(&statement, params![data, id.to_string()]).await?;
+        Ok(())
+    }
+
+    async fn remove(&self, id: Uuid) -> Result<()> {
+        let statement = format!("DELETE FROM {} WHERE id = ?1", self.table());
+        self.conn.execute(&statement, params![id.to_string()]).await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl BenchmarkClient for LibSqlClient {
+    async fn startup(&self) -> Result<()> {
+        let statement = format!(
+            "CREATE TABLE IF NOT EXISTS {} (id TEXT PRIMARY KEY, data TEXT)",
+            self.table()
+        );
+        self.conn.execute(&statement, ()).await?;
+        Ok(())
+    }
+
+    async fn create_records(&mut self, count: usize) -> Result<()> {
+        let new_ids = self.workload.new_ids();
+        let this = &*self;
+        let ids = this
+            .runner
+            .run("Create", count, |i| async move {
+                let id = new_ids.id(i);
+                this.insert(id, this.workload.value(i)).await?;
+                Ok(id)
+            })
+            .await?;
+        self.ids.extend(ids);
+        Ok(())
+    }
+
+    async fn read_records(&self) -> Result<()> {
+        let keys = self.workload.read_keys(self.ids.len())?;
+        let keys = &keys;
+        self.runner
+            .run("Read", keys.len(), |i| async move {
+                let key = keys[i % keys.len()];
+                let projection = self.workload.read_projection;
+                let data = self.select(self.ids[key], projection).await?;
+                if data.is_none() && self.workload.verifies(i) {
+                    return Err(anyhow::anyhow!("Record {} wasn't found", self.ids[key]));
+                }
+                if let Projection::Id = projection {
+                    return Ok(());
+                }
+                if let Some(expected) = self.workload.checked_value(i, key) {
+                    if data.as_deref() != Some(expected) {
+                        let id = &self.ids[key];
+                        return Err(anyhow::anyhow!("Read of {} returned the wrong data", id));
+                    }
+                }
+                Ok(())
+            })
+            .await?;
+        Ok(())
+    }
+
+    async fn update_records(&self) -> Result<()> {
+        let order = &self.workload.working_set(self.ids.len());
+        self.runner
+            .run("Update", self.ids.len(), |i| async move {
+                let id = self.ids[order[i % order.len()]];
+                self.update(id, self.workload.updated_value(i)).await
+            })
+            .await?;
+        Ok(())
+    }
+
+    async fn upsert_records(&mut self, count: usize) -> Result<()> {
+        let new_ids = self.workload.new_ids();
+        let this = &*self;
+        let statement = &format!(
+            "INSERT INTO {} (id, data) VALUES (?1, ?2) \
+            ON CONFLICT (id) DO UPDATE SET data = excluded.data",
+            this.table()
+        );
+        let ids = this
+            .runner
+            .run("Upsert", count, |i| async move {
+                let existing = upsert_key(&this.ids, i);
+                let id = existing.unwrap_or_else(|| new_ids.id(i));
+                let data = this.workload.value(i);
+                this.conn.execute(statement, params![id.to_string(), data]).await?;
+                Ok(existing.is_none().then_some(id))
+            })
+            .await?;
+        self.ids.extend(ids.into_iter().flatten());
+        Ok(())
+    }
+
+    async fn delete_records(&self) -> Result<()> {
+        let order = &self.workload.order(self.ids.len());
+        self.runner
+            .run("Delete", self.ids.len(), |i| {
+                self.remove(self.ids[order[i % order.len()]])
+            })
+            .await?;
+        Ok(())
+    }
+
+    // One transaction per batch, so each batch commits once
+    async fn cleanup(&mut self) -> Result<()> {
+        let statement = format!("DELETE FROM {} WHERE id = ?1", self.table());
+        for batch in self.ids.chunks(CLEANUP_BATCH) {
+            let tx = self.conn.transaction().await?;
+            for id in batch {
+                tx.execute(&statement, params![id.to_string()]).await?;
+            }
+            tx.commit().await?;
+        }
+        self.ids.clear();
+        Ok(())
+    }
+
+    async fn scan_records(&self) -> Result<()> {
+        let scan = self.workload.scan(&self.ids);
+        self.runner.scan(scan, |scan| async move { self.scan(&scan).await }).await?;
+        Ok(())
+    }
+
+    async fn read_records_by_data(&self) -> Result<()> {
+        let keys = self.workload.read_keys(self.ids.len())?;
+        let keys = &keys;
+        let statement = &format!("SELECT COUNT(*) FROM {} WHERE data = ?1", self.table());
+        self.runner
+            .run("Read by data", keys.len(), |i| async move {
+                let data = self.workload.value(keys[i % keys.len()]);
+                let mut rows = self.conn.query(statement, params![data]).await?;
+                let matches = match rows.next().await? {
+                    Some(row) => row.get::<i64>(0)?,
+                    None => 0,
+                };
+                if matches == 0 && self.workload.verifies(i) {
+                    return Err(anyhow::anyhow!("No record holds the data {:?}", data));
+                }
+                Ok(())
+            })
+            .await?;
+        Ok(())
+    }
+
+    async fn transaction(&mut self, _count: usize) -> Result<()> {
+        Err(anyhow::anyhow!("txn isn't supported for libSQL"))
+    }
+
+    async fn batch_read(&self) -> Result<()> {
+        Err(anyhow::anyhow!("batch_read isn't supported for libSQL"))
+    }
+
+    async fn count_records(&self) -> Result<usize> {
+        let statement = format!("SELECT COUNT(*) FROM {}", self.table());
+        let mut rows = self.conn.query(&statement, ()).await?;
+        match rows.next().await? {
+            Some(row) => Ok(row.get::<i64>(0)? as usize),
+            None => Ok(0),
+        }
+    }
+
+    fn tracked_ids(&self) -> usize {
+        self.ids.len()
+    }
+
+    fn load_ids(&mut self, ids: &[String]) -> Result<()> {
+        self.ids.extend(parse_uuids(ids)?);
+        Ok(())
+    }
+
+    async fn reset(&mut self, purge: bool) -> Result<()> {
+        if purge {
+            return Err(anyhow::anyhow!("--purge isn't supported for libSQL"));
+        }
+        self.ids.clear();
+        Ok(())
+    }
+
+    async fn create_record(&self, data: &str) -> Result<String> {
+        let id = self.workload.new_ids().id(0);
+        self.insert(id, data).await?;
+        Ok(id.to_string())
+    }
+
+    async fn read_record(&self, key: &str) -> Result<()> {
+        self.select(key.parse::<Uuid>()?, self.workload.read_projection).await?;
+        Ok(())
+    }
+
+    async fn update_record(&self, key: &str, data: &str) -> Result<()> {
+        self.update(key.parse::<Uuid>()?, data).await
+    }
+
+    async fn delete_record(&self, key: &str) -> Result<()> {
+        self.remove(key.parse::<Uuid>()?).await
+    }
+
+    async fn create_vectors(&self, _count: usize) -> Result<()> {
+        Err(anyhow::anyhow!("vectors aren't supported for libSQL"))
+    }
+
+    async fn search_vectors(&self, _count: usize) -> Result<()> {
+        Err(anyhow::anyhow!("vectors aren't supported for libSQL"))
+    }
+
+    async fn server_version(&self) -> Option<String> {
+        let mut rows = self.conn.query("SELECT sqlite_version()", ()).await.ok()?;
+        let row = rows.next().await.ok()??;
+        row.get::<String>(0).ok()
+    }
+
+    async fn dry_run(&mut self) -> Vec<(String, Result<()>)> {
+        let id = Uuid::new_v4();
+        let mut checks = Vec::new();
+
+        let created = self.insert(id, "test_value").await;
+        let create_ok = created.is_ok();
+        checks.push(("INSERT".to_string(), created));
+
+        let read = if create_ok {
+            match self.select(id, Projection::Full).await {
+                Ok(Some(data)) if data == "test_value" => Ok(()),
+                Ok(data) => Err(anyhow::anyhow!("unexpected row: {:?}", data)),
+                Err(e) => Err(e),
+            }
+        } else {
+            Err(anyhow::anyhow!("skipped, create failed"))
+        };
+        checks.push(("SELECT".to_string(), read));
+
+        let deleted = if create_ok {
+            self.remove(id).await
+        } else {
+            Err(anyhow::anyhow!("skipped, create failed"))
+        };
+        checks.push(("DELETE".to_string(), deleted));
+
+        checks
+    }
+}
+
+impl LibSqlClient {
+    async fn scan(&self, scan: &Scan) -> Result<usize> {
+        let limit = scan.limit.unwrap_or(100) as i64;
+        let offset = scan.start.unwrap_or(0) as i64;
+        let columns = match scan.projection()? {
+            Projection::Count => return self.count_records().await,
+            Projection::Id => "id",
+            Projection::Full => "id, data",
+        };
+        let query = format!("SELECT {} FROM {} LIMIT ?1 OFFSET ?2", columns, self.table());
+        let mut rows = self.conn.query(&query, params![limit, offset]).await?;
+        let (mut count, mut ids) = (0, Vec::new());
+        while let Some(row) = rows.next().await? {
+            count += 1;
+            if scan.checking() {
+                ids.push(row.get::<String>(0)?);
+            }
+        }
+        scan.saw(ids);
+        Ok(count)
+    }
+}
+
+// Engine for libSQL servers, sqld or Turso, over `libsql://`, `http://` or `https://` with the
+// token in `LIBSQL_AUTH_TOKEN` if the server needs one. Embedded libSQL would link a second
+// SQLite next to rusqlite's, so database files are left to the SQLite engine.
+pub struct LibSqlEngine {
+    db: Database,
+    runner: Runner,
+    workload: Workload,
+}
+
+#[async_trait]
+impl BenchmarkEngine for LibSqlEngine {
+    async fn setup(options: &Benchmark) -> Result<Self> {
+        let endpoint = options.endpoint.as_deref().unwrap_or("http://localhost:8080");
+        let remote = ["libsql://", "http://", "https://"];
+        if !remote.iter().any(|scheme| endpoint.starts_with(scheme)) {
+            return Err(anyhow::anyhow!(
+                "libSQL endpoint {:?} isn't a libsql://, http:// or https:// URL, \
+                use --database sqlite for database files",
+                endpoint
+            ));
+        }
+        let token = std::env::var(AUTH_TOKEN_VAR).unwrap_or_default();
+        let db = Builder::new_remote(endpoint.to_string(), token).build().await?;
+        Ok(Self {
+            db,
+            runner: options.runner.clone(),
+            workload: options.workload.clone(),
+        })
+    }
+
+    async fn create_client(&self) -> Result<Box<dyn BenchmarkClient>> {
+        let conn = self.db.connect()?;
+        let client = LibSqlClient::new(conn, self.runner.clone(), self.workload.clone());
+        client.startup().await?;
+        Ok(Box::new(client))
+    }
+}
@@ -1,17 +1,47 @@
 use anyhow::Result;
-use clap::{Parser, Subcommand};
+use clap::{ArgAction, Parser, Subcommand};
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
-use tokio;
+use tracing::{info, warn};
+use tracing_subscriber::filter::{LevelFilter, Targets};
+use tracing_subscriber::prelude::*;
 
+mod cassandra;
+mod clickhouse;
+mod cypher;
+mod dgraph;
+mod duckdb;
+mod engines;
 mod helixdb;
+mod libsql;
+mod memgraph;
+mod memory;
+mod milvus;
+mod mysql;
 mod neo4j;
+mod opensearch;
+mod runner;
+mod sqlite;
 mod types;
 mod utils;
+mod weaviate;
 
-use crate::helixdb::HelixDBEngine;
-use crate::neo4j::Neo4jEngine;
+use crate::memory::{MemoryReport, MemorySampler, MemorySummary};
+use crate::runner::{LatencyLog, Runner};
 use crate::types::BenchmarkEngine;
-use crate::types::{Benchmark, BenchmarkClient, Database};
+use crate::utils::{ConnectionCounter, PayloadTemplate, PhaseTimer, PhaseTotals};
+use crate::types::{
+    Average, BenchConfig, Benchmark, BenchmarkClient, BodyEncoding, Database, Histogram,
+    LatencyStats, OperationAggregate, Operation, OperationResult, OutputFormat, Precision,
+    Projection, RecordSchema, ReplayOp, RequestsFailed, ThresholdError, UpdateMode, VerifyError,
+    Workload,
+};
 
 #[derive(Parser)]
 #[command(name = "helix-bench")]
@@ -19,41 +49,140 @@ use crate::types::{Benchmark, BenchmarkClient, Database};
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+    /// Log more detail to stderr: -v for operation summaries, -vv for each request, -vvv for
+    /// full request and response bodies
+    #[arg(short, long, action = ArgAction::Count, global = true)]
+    verbose: u8,
 }
 
 #[derive(Subcommand)]
 enum Commands {
     /// Benchmark a specific operation
     Bench {
-        /// Operation to benchmark: create, read, update, delete, scan
-        #[arg(default_value = "all")]
-        operation: String,
-        /// Number of operations to perform
-        #[arg(short, long, default_value_t = 500_000)]
-        count: usize,
-        /// Database: helixdb, neo4j or others
-        #[arg(short, long, default_value = "helixdb")]
-        database: String,
-        /// Endpoint URL (optional)
-        #[arg(short, long)]
-        endpoint: Option<String>,
+        #[command(flatten)]
+        options: BenchConfig,
+        /// Load options from a TOML or YAML file; flags given on the command line take precedence
+        #[arg(long)]
+        config: Option<PathBuf>,
+    },
+    /// Run an operation at doubling concurrency levels and report throughput and p99 for each.
+    /// Read and update first create `--count` records [default count: 10000, operation: read]
+    Ramp {
+        #[command(flatten)]
+        options: BenchConfig,
+        /// Load options from a TOML or YAML file; flags given on the command line take precedence
+        #[arg(long)]
+        config: Option<PathBuf>,
+        /// Seconds to run at each concurrency level
+        #[arg(long, default_value_t = 10)]
+        step_duration: u64,
+        /// Highest concurrency level to try
+        #[arg(long, default_value_t = 64)]
+        max_concurrency: usize,
+    },
+    /// Run an operation at doubling concurrency levels until its error rate or p99 goes over
+    /// the limit, and report the highest throughput sustained within both. The p99 limit is
+    /// `--max-p99-ms` [default: 100]; failed requests are counted rather than fatal
+    Stress {
+        #[command(flatten)]
+        options: BenchConfig,
+        /// Load options from a TOML or YAML file; flags given on the command line take precedence
+        #[arg(long)]
+        config: Option<PathBuf>,
+        /// Seconds to run at each concurrency level
+        #[arg(long, default_value_t = 10)]
+        step_duration: u64,
+        /// Highest concurrency level to try
+        #[arg(long, default_value_t = 1024)]
+        max_concurrency: usize,
+        /// Percentage of failed requests above which a level isn't sustainable
+        #[arg(long, default_value_t = 1.0)]
+        max_error_rate: f64,
+    },
+    /// Create `--count` vectors, run as many searches against them and optionally check the
+    /// searches' recall, reporting the vector workload on its own
+    BenchVector {
+        #[command(flatten)]
+        options: BenchConfig,
+        /// Load options from a TOML or YAML file; flags given on the command line take precedence
+        #[arg(long)]
+        config: Option<PathBuf>,
+        /// Also measure recall@k over this many searches, against exact nearest neighbours
+        /// found by brute force over the created vectors, which are kept in memory for it
+        #[arg(long)]
+        recall: Option<usize>,
+    },
+    /// Replay a trace of operations in order, one at a time, and time the whole replay
+    Replay {
+        /// Trace file, one `create <id> <data>`, `read <id>`, `update <id> <data>` or
+        /// `delete <id>` per line
+        file: PathBuf,
+        #[command(flatten)]
+        options: BenchConfig,
+        /// Load options from a TOML or YAML file; flags given on the command line take precedence
+        #[arg(long)]
+        config: Option<PathBuf>,
+    },
+    /// Check the options of a `bench` run, from flags and `--config`, without connecting to the
+    /// database: print them with every default filled in and report any that conflict
+    ValidateConfig {
+        #[command(flatten)]
+        options: BenchConfig,
+        /// Load options from a TOML or YAML file; flags given on the command line take precedence
+        #[arg(long)]
+        config: Option<PathBuf>,
+    },
+    /// Compare two JSON reports from `--output json`, printing the change in throughput and p99
+    /// per operation and failing if any got worse by more than the threshold
+    Diff {
+        /// Report to compare against
+        baseline: PathBuf,
+        /// Report of the run being checked
+        current: PathBuf,
+        /// Percentage by which throughput may drop or p99 rise before it counts as a regression
+        #[arg(long, default_value_t = 5.0)]
+        threshold: f64,
+    },
+    /// Lay JSON reports from `--output json` of different databases side by side, printing
+    /// each one's throughput and p50, p95 and p99 per operation with the best marked
+    Compare {
+        /// Reports to compare, one per database
+        #[arg(required = true, num_args = 2..)]
+        reports: Vec<PathBuf>,
     },
 }
 
 async fn run_benchmark(
     client: &mut dyn BenchmarkClient,
+    runner: &Runner,
     operation: &str,
     count: usize,
-) -> Result<(Duration, f64, f64)> {
+) -> Result<OperationResult> {
+    // These work on the ids the client created, and with none would finish instantly and
+    // report a meaningless throughput
+    let needs_records = <Operation as clap::ValueEnum>::from_str(operation, true)
+        .is_ok_and(|op| op.needs_records());
+    if needs_records && client.tracked_ids() == 0 {
+        return Err(anyhow::anyhow!(
+            "No records to {}: only records created earlier in the same run are tracked. \
+            Use `bench all`, which runs `create` first, `--prefill` or `--ids-file`",
+            operation.to_lowercase()
+        ));
+    }
+    runner.reset();
     let start = Instant::now();
     match operation.to_lowercase().as_str() {
         "create" => client.create_records(count).await?,
         "read" => client.read_records().await?,
         "update" => client.update_records().await?,
+        "upsert" => client.upsert_records(count).await?,
         "delete" => client.delete_records().await?,
         "scan" => client.scan_records().await?,
+        "read_by_data" => client.read_records_by_data().await?,
         "create_vectors" => client.create_vectors(count).await?,
         "search_vectors" => client.search_vectors(count).await?,
+        "txn" => client.transaction(count).await?,
+        "batch_read" => client.batch_read().await?,
         _ => return Err(anyhow::anyhow!("Unsupported operation: {}", operation)),
 
         /*
@@ -89,140 +218,2388 @@ async fn run_benchmark(
     }
 
     let total_time = start.elapsed();
-    let avg_time_per_request = total_time.as_secs_f64() / count as f64;
-    let throughput = count as f64 / total_time.as_secs_f64();
+    info!(%operation, ?total_time, completed = runner.completed(), "finished");
+    // only the requests that completed get credit: an interrupted, time-bound or partly failed
+    // run stops short of --count, and reads, updates and deletes go over the tracked ids, which
+    // --read-count, --prefill or --ids-file can make more or fewer than --count
+    let (avg_time_per_request, throughput) =
+        OperationResult::rates(runner.completed(), total_time);
+    let latencies = runner.take_latencies();
+    let histogram = Histogram::from_latencies(&latencies);
 
-    Ok((total_time, avg_time_per_request, throughput))
+    Ok(OperationResult {
+        operation: operation.to_string(),
+        total_time,
+        avg_time: avg_time_per_request,
+        throughput,
+        mb_per_sec: None,
+        latency: LatencyStats::from_latencies(latencies),
+        completed: runner.completed(),
+        errors: runner.errors(),
+        error_breakdown: runner.take_error_breakdown(),
+        truncated: runner.truncated(),
+        timeline: runner.take_timeline(),
+        histogram,
+    })
+}
+
+// Bytes of payload a request of `operation` carries: the record data written or read back, or
+// one vector's components. `None` where that isn't known: deletes and scans, which move no data
+// or a varying amount, and records shaped by --payload-template or --schema.
+fn payload_bytes(benchmark: &Benchmark, operation: &str) -> Option<f64> {
+    let workload = &benchmark.workload;
+    let shaped = workload.payload.is_some() || workload.schema.is_some();
+    let data = |default: &str| match &workload.values {
+        Some(values) => {
+            values.iter().map(String::len).sum::<usize>() as f64 / values.len().max(1) as f64
+        }
+        None => default.len() as f64,
+    };
+    let component = match benchmark.precision {
+        Precision::F32 => 4,
+        Precision::F64 => 8,
+    };
+    match operation {
+        "create" | "upsert" | "read_by_data" if !shaped => Some(data("test_value")),
+        "read" if !shaped && !matches!(workload.read_projection, Projection::Id) => {
+            Some(data("test_value"))
+        }
+        "update" if !shaped => Some(data("updated_value")),
+        "batch_read" if !shaped => Some(data("test_value") * workload.batch_size as f64),
+        "create_vectors" | "search_vectors" => Some((workload.dims * component) as f64),
+        _ => None,
+    }
+}
+
+// Operations run, in order, by `bench all` unless `--ops` picks others
+const ALL_OPERATIONS: &[Operation] = &[
+    Operation::Create,
+    Operation::Read,
+    Operation::Update,
+    //Operation::Delete,
+    Operation::Scan,
+    Operation::CreateVectors,
+    Operation::SearchVectors,
+    //"bulk_create",
+    //"huge_traversal",
+];
+
+// The operations `bench all` runs without `--ops`, leaving the vector ones out on engines
+// that have no vectors
+fn default_ops(database: Database) -> Vec<Operation> {
+    let vectors = engines::supports_vectors(database);
+    ALL_OPERATIONS.iter().copied().filter(|op| vectors || !op.is_vector()).collect()
+}
+
+// Operations that work on existing records need a create or upsert ahead of them to make some,
+// unless the run was prefilled
+fn check_ops(ops: &[Operation], prefilled: bool, database: Database) -> Result<()> {
+    if ops.is_empty() {
+        return Err(anyhow::anyhow!("--ops needs at least one operation"));
+    }
+    for (i, op) in ops.iter().enumerate() {
+        if op.is_vector() && !engines::supports_vectors(database) {
+            return Err(anyhow::anyhow!(
+                "--ops: {} isn't supported for {}",
+                op.as_str(),
+                database_name(database)
+            ));
+        }
+        let seeded = ops[..i]
+            .iter()
+            .any(|op| matches!(op, Operation::Create | Operation::Upsert));
+        if op.needs_records() && !seeded && !prefilled {
+            return Err(anyhow::anyhow!(
+                "--ops: {} needs records, put create or upsert before it",
+                op.as_str()
+            ));
+        }
+    }
+    Ok(())
+}
+
+// The iterations left after dropping the `drop` slowest by total time, always keeping one
+fn without_slowest(runs: &[Vec<OperationResult>], drop: usize) -> Vec<Vec<OperationResult>> {
+    let total = |run: &Vec<OperationResult>| run.iter().map(|r| r.total_time).sum::<Duration>();
+    let mut runs = runs.to_vec();
+    runs.sort_by_key(total);
+    runs.truncate(runs.len().saturating_sub(drop).max(1));
+    runs
+}
+
+// Ids of existing records, one per line, skipping blank lines and `#` comments
+fn read_ids(path: &Path) -> Result<Vec<String>> {
+    let file = std::fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", path.display(), e))?;
+    let ids: Vec<String> = file
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect();
+    if ids.is_empty() {
+        return Err(anyhow::anyhow!("{} lists no ids", path.display()));
+    }
+    info!(ids = ids.len(), path = %path.display(), "loaded ids");
+    Ok(ids)
+}
+
+// Creates `count` records through the client so later operations have a dataset to work on,
+// outside any measured operation and ignoring --duration and --operation-timeout-budget
+async fn prefill(client: &mut dyn BenchmarkClient, runner: &Runner, count: usize) -> Result<()> {
+    info!(count, "prefilling");
+    let (duration, budget) = (runner.duration(), runner.time_budget());
+    runner.set_duration(None);
+    runner.set_time_budget(None);
+    runner.reset();
+    let res = client.create_records(count).await;
+    runner.set_duration(duration);
+    runner.set_time_budget(budget);
+    res
 }
 
 async fn run_all_benchmarks(
     client: &mut dyn BenchmarkClient,
+    benchmark: &Benchmark,
+    ops: &[Operation],
     count: usize,
-) -> Result<Vec<(String, Duration, f64, f64)>> {
+    mut verifier: Option<&mut CountVerifier>,
+) -> Result<Vec<OperationResult>> {
+    let runner = &benchmark.runner;
     let mut results = Vec::new();
 
-    let (create_duration, create_avg_time, create_throughput) =
-        run_benchmark(client, "create", count).await?;
-    results.push(("create".to_string(), create_duration, create_avg_time, create_throughput));
+    for operation in ops.iter().map(Operation::as_str) {
+        let mut result = run_benchmark(client, runner, operation, count)
+            .await?
+            .with_payload(payload_bytes(benchmark, operation));
+        if let Some(verifier) = verifier.as_deref_mut() {
+            verifier.check(client, operation).await?;
+        }
+        if runner.is_cancelled() {
+            result.operation = format!("{} (partial)", operation);
+            results.push(result);
+            break;
+        }
+        results.push(result);
+    }
+
+    Ok(results)
+}
+
+// Cross-checks the record count reported by the server against the ids the client tracked,
+// relative to whatever was already stored before the run
+struct CountVerifier {
+    baseline: usize,
+    mismatches: Vec<String>,
+}
+
+impl CountVerifier {
+    async fn new(client: &dyn BenchmarkClient) -> Result<Self> {
+        Ok(Self {
+            baseline: client.count_records().await?,
+            mismatches: Vec::new(),
+        })
+    }
+
+    /// Re-reads the baseline, for a new client starting over on the same database
+    async fn rebase(&mut self, client: &dyn BenchmarkClient) -> Result<()> {
+        self.baseline = client.count_records().await?;
+        Ok(())
+    }
+
+    async fn check(&mut self, client: &dyn BenchmarkClient, operation: &str) -> Result<()> {
+        let tracked = client.tracked_ids();
+        let (expected, explanation) = match operation {
+            "create" | "upsert" => (
+                self.baseline + tracked,
+                format!("{} already stored + {} tracked", self.baseline, tracked),
+            ),
+            "delete" => (
+                self.baseline,
+                format!("{} already stored, all {} tracked ids deleted", self.baseline, tracked),
+            ),
+            _ => return Ok(()),
+        };
+        let actual = client.count_records().await?;
+        if actual != expected {
+            let mismatch = format!(
+                "after {}: server reports {} records, expected {} ({}), off by {:+}",
+                operation,
+                actual,
+                expected,
+                explanation,
+                actual as i64 - expected as i64
+            );
+            warn!("count mismatch {}", mismatch);
+            self.mismatches.push(mismatch);
+        }
+        Ok(())
+    }
+}
+
+// Compares results against the configured thresholds, returning a message per violation
+fn check_thresholds(
+    results: &[OperationResult],
+    max_p99_ms: Option<f64>,
+    min_throughput: Option<f64>,
+) -> Vec<String> {
+    let mut violations = Vec::new();
+    for result in results {
+        if let (Some(max), Some(latency)) = (max_p99_ms, result.latency) {
+            let p99 = latency.p99.as_secs_f64() * 1000.0;
+            if p99 > max {
+                violations.push(format!(
+                    "{}: p99 latency {:.3} ms exceeds the {:.3} ms limit",
+                    result.operation, p99, max
+                ));
+            }
+        }
+        if let Some(min) = min_throughput {
+            if result.throughput < min {
+                violations.push(format!(
+                    "{}: throughput {:.2} ops/s is below the {:.2} ops/s minimum",
+                    result.operation, result.throughput, min
+                ));
+            }
+        }
+    }
+    violations
+}
+
+// One row per second of each operation: when the window started, the requests completed in
+// it and the running total for the operation
+fn write_timeseries(path: &Path, results: &[OperationResult]) -> Result<()> {
+    let mut csv = String::from("operation,timestamp,elapsed_s,ops,cumulative\n");
+    for result in results {
+        let timeline = &result.timeline;
+        let mut cumulative = 0;
+        for (second, &ops) in timeline.windows.iter().enumerate() {
+            cumulative += ops;
+            let timestamp = timeline.started + chrono::Duration::seconds(second as i64);
+            csv.push_str(&format!(
+                "{},{},{},{},{}\n",
+                result.operation,
+                timestamp.to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+                second,
+                ops,
+                cumulative
+            ));
+        }
+    }
+    std::fs::write(path, csv)
+        .map_err(|e| anyhow::anyhow!("Failed to write {}: {}", path.display(), e))
+}
 
-    let (read_duration, read_avg_time, read_throughput) =
-        run_benchmark(client, "read", count).await?;
-    results.push(("read".to_string(), read_duration, read_avg_time, read_throughput));
+// Successful requests per latency bucket of each operation, each bucket bounded above by
+// `le_us` microseconds, with the running total for the operation
+fn write_histogram(path: &Path, results: &[OperationResult]) -> Result<()> {
+    let mut csv = String::from("operation,le_us,count,cumulative\n");
+    for result in results {
+        let mut cumulative = 0;
+        for (bucket, &count) in result.histogram.buckets.iter().enumerate() {
+            cumulative += count;
+            csv.push_str(&format!(
+                "{},{},{},{}\n",
+                result.operation,
+                1u64 << bucket,
+                count,
+                cumulative
+            ));
+        }
+    }
+    std::fs::write(path, csv)
+        .map_err(|e| anyhow::anyhow!("Failed to write {}: {}", path.display(), e))
+}
 
-    let (update_duration, update_avg_time, update_throughput) =
-        run_benchmark(client, "update", count).await?;
-    results.push(("update".to_string(), update_duration, update_avg_time, update_throughput));
+// A new directory under `base` for a run's report bundle, named for when the run started and
+// the database, so repeated runs sort in order and never overwrite each other
+fn create_run_dir(base: &Path, database: Database) -> Result<PathBuf> {
+    let started = chrono::Utc::now().format("%Y%m%d-%H%M%S");
+    let dir = base.join(format!("{}-{}", started, database_name(database).to_lowercase()));
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| anyhow::anyhow!("Failed to create {}: {}", dir.display(), e))?;
+    Ok(dir)
+}
 
-    //let (delete_duration, delete_avg_time, delete_throughput) =
-    //    run_benchmark(client, "delete", count).await?;
-    //results.push(("delete".to_string(), delete_duration, delete_avg_time, delete_throughput));
+fn write_json(path: &Path, value: &impl Serialize) -> Result<()> {
+    std::fs::write(path, serde_json::to_string_pretty(value)?)
+        .map_err(|e| anyhow::anyhow!("Failed to write {}: {}", path.display(), e))
+}
 
-    let (scan_duration, scan_avg_time, scan_throughput) =
-        run_benchmark(client, "scan", count).await?;
-    results.push(("scan".to_string(), scan_duration, scan_avg_time, scan_throughput));
+// Context stored with JSON results so archived runs can be told apart later
+#[derive(Serialize)]
+struct RunMetadata {
+    run_id: String,
+    timestamp: String,
+    version: &'static str,
+    hostname: String,
+    os: &'static str,
+    arch: &'static str,
+    cpus: usize,
+    /// As the server reported it, when the engine can ask
+    #[serde(skip_serializing_if = "Option::is_none")]
+    server_version: Option<String>,
+}
 
-    let (create_vectors_duration, create_vectors_avg_time, create_vectors_throughput) =
-        run_benchmark(client, "create_vectors", count).await?;
-    results.push(("create_vectors".to_string(), create_vectors_duration, create_vectors_avg_time, create_vectors_throughput));
+impl RunMetadata {
+    fn collect() -> Self {
+        let hostname = std::env::var("HOSTNAME")
+            .or_else(|_| std::env::var("COMPUTERNAME"))
+            .ok()
+            .or_else(|| {
+                std::fs::read_to_string("/etc/hostname")
+                    .ok()
+                    .map(|name| name.trim().to_string())
+            })
+            .filter(|name| !name.is_empty())
+            .unwrap_or_else(|| "unknown".to_string());
+        Self {
+            run_id: uuid::Uuid::new_v4().simple().to_string()[..12].to_string(),
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            version: env!("CARGO_PKG_VERSION"),
+            hostname,
+            os: std::env::consts::OS,
+            arch: std::env::consts::ARCH,
+            cpus: num_cpus::get(),
+            server_version: None,
+        }
+    }
+}
 
-    let (search_vectors_duration, search_vectors_avg_time, search_vectors_throughput) =
-        run_benchmark(client, "search_vectors", count).await?;
-    results.push(("search_vector".to_string(), search_vectors_duration, search_vectors_avg_time, search_vectors_throughput));
+// Results of `bench-vector`
+#[derive(Serialize)]
+struct VectorReport<'a> {
+    metadata: RunMetadata,
+    database: &'static str,
+    config: &'a BenchConfig,
+    results: &'a [OperationResult],
+    /// Mean recall@k, with `--recall` on an engine that supports it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    recall: Option<f64>,
+}
 
-    //let (bulk_create_duration, bulk_create_avg_time, bulk_create_throughput) =
-    //    run_benchmark(client, "bulk_create", count).await?;
-    //results.push(("bulk_create".to_string(), bulk_create_duration, bulk_create_avg_time, bulk_create_throughput));
+#[derive(Serialize)]
+struct JsonReport<'a> {
+    metadata: RunMetadata,
+    database: &'static str,
+    config: &'a BenchConfig,
+    /// Every iteration's results, one after the other
+    results: &'a [OperationResult],
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    aggregates: &'a [OperationAggregate],
+    /// Not counted when the run was interrupted
+    existing_records: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    memory: Option<MemoryReport>,
+    /// TCP connections opened, with `--report-connections`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    connections: Option<usize>,
+    /// Times the HTTP client was rebuilt after its connections failed
+    reconnects: usize,
+    /// Average request phases per route, with `--report-phases`
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    phases: &'a [PhaseReport],
+}
 
-    /*
+// The average time a route's requests spent in each phase, in milliseconds
+#[derive(Serialize)]
+struct PhaseReport {
+    route: String,
+    requests: usize,
+    serialize_ms: f64,
+    network_ms: f64,
+    deserialize_ms: f64,
+}
 
-    let (huge_traversal_duration, huge_traversal_avg_time, huge_traversal_throughput) =
-        run_benchmark(client, "huge_traversal", count, KeyType::U32).await?;
-    results.push((
-        "huge_traversal".to_string(),
-        huge_traversal_duration,
-        huge_traversal_avg_time,
-        huge_traversal_throughput,
-    ));
-    */
+impl PhaseReport {
+    fn new(route: String, totals: PhaseTotals) -> Self {
+        let avg = |total: Duration| total.as_secs_f64() * 1000.0 / totals.requests.max(1) as f64;
+        Self {
+            route,
+            requests: totals.requests,
+            serialize_ms: avg(totals.serialize),
+            network_ms: avg(totals.network),
+            deserialize_ms: avg(totals.deserialize),
+        }
+    }
+}
 
-    Ok(results)
+// `detailed` adds latency spread columns, std dev and CV
+fn print_results(server: &str, scope: &str, results: &[OperationResult], detailed: bool) {
+    let width = if detailed { 145 } else { 111 };
+    println!("\nBenchmark Results for {} ({}):", server, scope);
+    println!("{:-<width$}", "");
+    print!(
+        "{:<10} | {:<15} | {:<15} | {:<15} | {:<15} | {:<10}",
+        "Operation", "Total Time", "Avg Time/Req (ms)", "p99 (ms)", "Throughput (ops/s)", "MB/s"
+    );
+    if detailed {
+        print!(" | {:<15} | {:<10}", "Std Dev (ms)", "CV");
+    }
+    println!();
+    println!("{:-<width$}", "");
+    for result in results {
+        print!(
+            "{:<10} | {:<15} | {:<15.6} | {:<15} | {:<15.2} | {:<10}",
+            result.operation,
+            format!("{:?}", result.total_time),
+            result.avg_time * 1000.0,
+            format_ms(result.latency.map(|l| l.p99)),
+            result.throughput,
+            format_mb_per_sec(result.mb_per_sec)
+        );
+        if detailed {
+            print!(
+                " | {:<15} | {:<10}",
+                format_ms(result.latency.map(|l| l.std_dev)),
+                format_cv(result.latency.map(|l| l.cv))
+            );
+        }
+        println!();
+    }
+    for result in results.iter().filter(|result| result.errors > 0) {
+        println!("{}: {}", result.operation, format_errors(result));
+    }
+    for result in results.iter().filter(|result| result.truncated) {
+        println!("{}: {}", result.operation, format_truncated(result));
+    }
+}
+
+fn print_result(server: &str, operation: &str, completed: &str, result: &OperationResult) {
+    println!(
+        "Benchmark: {} {} operations on {}:\n\
+        Total Time: {:?}\n\
+        Avg Time/Request: {:.6} ms\n\
+        Throughput: {:.2} ops/s",
+        operation,
+        completed,
+        server,
+        result.total_time,
+        result.avg_time * 1000.0,
+        result.throughput
+    );
+    if let Some(mb_per_sec) = result.mb_per_sec {
+        println!("Bandwidth: {:.2} MB/s of payload", mb_per_sec);
+    }
+    if let Some(latency) = result.latency {
+        println!(
+            "Latency (ms): mean {} | p50 {} | p95 {} | p99 {} | max {}",
+            format_ms(Some(latency.mean)),
+            format_ms(Some(latency.p50)),
+            format_ms(Some(latency.p95)),
+            format_ms(Some(latency.p99)),
+            format_ms(Some(latency.max)),
+        );
+        println!(
+            "Latency spread: std dev {} ms | CV {}",
+            format_ms(Some(latency.std_dev)),
+            format_cv(Some(latency.cv))
+        );
+    }
+    if result.errors > 0 {
+        println!("Errors: {}", format_errors(result));
+    }
+    if result.truncated {
+        println!("Truncated: {}", format_truncated(result));
+    }
+}
+
+fn format_mb_per_sec(mb_per_sec: Option<f64>) -> String {
+    match mb_per_sec {
+        Some(mb_per_sec) => format!("{:.2}", mb_per_sec),
+        None => "-".to_string(),
+    }
+}
+
+fn format_errors(result: &OperationResult) -> String {
+    let breakdown: Vec<String> = result
+        .error_breakdown
+        .iter()
+        .map(|(kind, count)| format!("{}: {}", kind, count))
+        .collect();
+    format!(
+        "{} of {} requests failed ({:.2}%){}",
+        result.errors,
+        result.completed + result.errors,
+        result.error_rate(),
+        match breakdown.is_empty() {
+            true => String::new(),
+            false => format!(" [{}]", breakdown.join(", ")),
+        }
+    )
+}
+
+fn format_truncated(result: &OperationResult) -> String {
+    format!(
+        "time budget ran out after {} requests in {:?}, throughput is over those",
+        result.completed, result.total_time
+    )
+}
+
+fn print_aggregates(
+    server: &str,
+    iterations: usize,
+    dropped: usize,
+    average: Average,
+    aggregates: &[OperationAggregate],
+) {
+    let name = match average {
+        Average::Mean => "mean",
+        Average::Median => "median",
+    };
+    let dropped = match dropped {
+        0 => String::new(),
+        dropped => format!(", slowest {} dropped", dropped),
+    };
+    println!(
+        "\nBenchmark Results for {} ({} ± std dev over {} iterations{}):",
+        server,
+        name,
+        iterations,
+        dropped
+    );
+    println!("{:-<118}", "");
+    println!(
+        "{:<10} | {:<24} | {:<12} | {:<12} | {:<24} | {:<15}",
+        "Operation", "Throughput (ops/s)", "Best", "Worst", "Avg Time/Req (ms)", "p99 (ms)"
+    );
+    println!("{:-<118}", "");
+    for aggregate in aggregates {
+        let (throughput, avg) = (aggregate.throughput, aggregate.avg_time);
+        println!(
+            "{:<10} | {:<24} | {:<12.2} | {:<12.2} | {:<24} | {:<15}",
+            aggregate.operation,
+            format!("{:.2} ± {:.2}", throughput.average(average), throughput.std_dev),
+            throughput.max,
+            throughput.min,
+            format!("{:.6} ± {:.6}", avg.average(average) * 1000.0, avg.std_dev * 1000.0),
+            aggregate
+                .p99
+                .map(|p99| format!("{:.6}", p99.average(average) * 1000.0))
+                .unwrap_or_else(|| "-".to_string()),
+        );
+    }
+}
+
+fn print_memory(memory: &MemoryReport) {
+    let format = |summary: Option<MemorySummary>| match summary {
+        Some(s) => format!(
+            "min {:.1} | max {:.1} | final {:.1}",
+            mb(s.min),
+            mb(s.max),
+            mb(s.last)
+        ),
+        None => "-".to_string(),
+    };
+    println!(
+        "\nServer memory (MB, {} samples):\n  RSS:  {}\n  Heap: {}",
+        memory.samples.len(),
+        format(memory.rss),
+        format(memory.heap)
+    );
+}
+
+// Many requests per connection means keep-alive is working; close to one per request means
+// connections are being torn down and reopened
+fn print_connections(opened: usize, results: &[OperationResult]) {
+    let requests: usize = results.iter().map(|r| r.completed + r.errors).sum();
+    match opened {
+        0 => println!("\nConnections opened: 0"),
+        _ => println!(
+            "\nConnections opened: {} ({:.1} requests per connection)",
+            opened,
+            requests as f64 / opened as f64
+        ),
+    }
+}
+
+// Shares are of the three phases together, which leaves out time in the runner and engine
+fn print_phases(phases: &[PhaseReport]) {
+    println!("\nRequest phases (avg ms):");
+    println!(
+        "{:<24} | {:<10} | {:<16} | {:<16} | {:<16}",
+        "Route", "Requests", "Serialize", "Network", "Deserialize"
+    );
+    println!("{:-<92}", "");
+    for phase in phases {
+        let total = phase.serialize_ms + phase.network_ms + phase.deserialize_ms;
+        let share = |ms: f64| match total > 0.0 {
+            true => format!("{:.4} ({:.0}%)", ms, ms / total * 100.0),
+            false => format!("{:.4}", ms),
+        };
+        println!(
+            "{:<24} | {:<10} | {:<16} | {:<16} | {:<16}",
+            phase.route,
+            phase.requests,
+            share(phase.serialize_ms),
+            share(phase.network_ms),
+            share(phase.deserialize_ms)
+        );
+    }
+}
+
+fn mb(bytes: u64) -> f64 {
+    bytes as f64 / (1024.0 * 1024.0)
+}
+
+fn format_cv(cv: Option<f64>) -> String {
+    cv.map(|cv| format!("{:.3}", cv))
+        .unwrap_or_else(|| "-".to_string())
+}
+
+fn format_ms(duration: Option<Duration>) -> String {
+    duration
+        .map(|d| format!("{:.6}", d.as_secs_f64() * 1000.0))
+        .unwrap_or_else(|| "-".to_string())
+}
+
+async fn run_dry_run(engine: &dyn BenchmarkEngine, database: Database) -> Result<()> {
+    println!("Dry run against {}:", database_name(database));
+    let mut client = match engine.create_client().await {
+        Ok(client) => {
+            println!("  {:<20} ok", "startup");
+            client
+        }
+        Err(e) => {
+            println!("  {:<20} FAILED ({})", "startup", e);
+            return Err(anyhow::anyhow!("Dry run failed: could not start a client"));
+        }
+    };
+
+    let checks = client.dry_run().await;
+    let failed = checks.iter().filter(|(_, result)| result.is_err()).count();
+    for (name, result) in &checks {
+        match result {
+            Ok(()) => println!("  {:<20} ok", name),
+            Err(e) => println!("  {:<20} FAILED ({})", name, e),
+        }
+    }
+
+    if failed > 0 {
+        return Err(anyhow::anyhow!(
+            "Dry run failed: {} of {} checks failed",
+            failed,
+            checks.len()
+        ));
+    }
+    println!("All endpoints responded correctly");
+    Ok(())
+}
+
+// Logging stays at warnings by default so it can't affect timings
+fn init_logging(verbose: u8) {
+    let level = match verbose {
+        0 => LevelFilter::WARN,
+        1 => LevelFilter::INFO,
+        2 => LevelFilter::DEBUG,
+        _ => LevelFilter::TRACE,
+    };
+    tracing_subscriber::registry()
+        .with(tracing_subscriber::fmt::layer().with_writer(std::io::stderr))
+        .with(Targets::new().with_target("helix_bench", level))
+        .init();
 }
 
 fn database_name(database: Database) -> &'static str {
-    match database {
-        Database::HelixDB => "HelixDB",
-        Database::Neo4j => "Neo4j",
+    engines::lookup(database).map_or("unknown database", |engine| engine.display_name)
+}
+
+// The database's name for result headers, with the server's version when it reported one
+fn server_name(database: Database, version: Option<&str>) -> String {
+    match version {
+        Some(version) => format!("{} {}", database_name(database), version),
+        None => database_name(database).to_string(),
     }
 }
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    let cli = Cli::parse();
+// Serves the runner's live request counters and latency histogram for Prometheus to scrape at
+// `/metrics` on the given port, for the length of the run
+fn serve_metrics(port: Option<u16>) -> Result<()> {
+    let Some(port) = port else {
+        return Ok(());
+    };
+    // 100µs to 10s, roughly three buckets per decade
+    let buckets = [
+        0.0001, 0.00025, 0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0,
+        2.5, 5.0, 10.0,
+    ];
+    PrometheusBuilder::new()
+        .with_http_listener(([0, 0, 0, 0], port))
+        .set_buckets_for_metric(Matcher::Full(runner::LATENCY_METRIC.to_string()), &buckets)?
+        .install()
+        .map_err(|e| anyhow::anyhow!("Failed to serve metrics on port {}: {}", port, e))?;
+    info!(port, "serving Prometheus metrics at /metrics");
+    Ok(())
+}
 
-    match cli.command {
-        Commands::Bench {
-            operation,
-            count,
-            database,
-            endpoint,
-        } => {
-            let database = match database.to_lowercase().as_str() {
-                "helixdb" => Database::HelixDB,
-                "neo4j" => Database::Neo4j,
-                _ => return Err(anyhow::anyhow!("Invalid database: {}", database)),
+fn load_options(options: BenchConfig, config: Option<PathBuf>) -> Result<BenchConfig> {
+    match config {
+        Some(path) => Ok(options.or(BenchConfig::load(&path)?)),
+        None => Ok(options),
+    }
+}
+
+// Requests per second the loops may start, `None` for no limit
+fn limit_rps(options: &BenchConfig) -> Result<Option<f64>> {
+    match options.limit_rps {
+        Some(rps) if !(rps > 0.0 && rps.is_finite()) => {
+            Err(anyhow::anyhow!("--limit-rps must be a positive number, got {}", rps))
+        }
+        rps => Ok(rps),
+    }
+}
+
+// Failed requests an operation tolerates, `None` to abort on the first
+fn max_errors(options: &BenchConfig) -> Option<usize> {
+    match options.fail_fast.unwrap_or(options.max_errors.is_none()) {
+        true => None,
+        false => Some(options.max_errors.unwrap_or(usize::MAX)),
+    }
+}
+
+// Applies defaults to the options the engines read, rejecting values they can't use before
+// any data is written
+fn resolve_benchmark(
+    database: Database,
+    options: &BenchConfig,
+    runner: Runner,
+) -> Result<Benchmark> {
+    let problems = conflicts(options, database);
+    if !problems.is_empty() {
+        return Err(anyhow::anyhow!("{}", problems.join(", ")));
+    }
+    let zipf_exponent = options.zipf_exponent.unwrap_or(0.99);
+    if !(zipf_exponent > 0.0 && zipf_exponent < 1.0) {
+        return Err(anyhow::anyhow!(
+            "--zipf-exponent must be between 0 and 1, got {}",
+            zipf_exponent
+        ));
+    }
+    let sizing = options.value_size.is_some() || options.value_size_dist.is_some();
+    let size = options.value_size.unwrap_or(16);
+    if size == 0 {
+        return Err(anyhow::anyhow!("--value-size must be at least 1"));
+    }
+    let sizes = utils::ValueSizes {
+        distribution: options.value_size_dist.unwrap_or_default(),
+        size,
+        spread: options.value_size_spread.unwrap_or(size / 4),
+    };
+    // sized values need a pool to come from, even if no pool size was asked for
+    let distinct_values = match (options.distinct_values, sizing) {
+        (None, true) => Some(1000),
+        (count, _) => count,
+    };
+    let update_mode = options.update_mode.unwrap_or_default();
+    // Only Cypher, whose `SET n = {...}` replaces the property map, and HelixDB, which sends
+    // the whole record, write anything different for a full update
+    let full_updates = matches!(database, Database::Neo4j | Database::Memgraph | Database::HelixDB);
+    if update_mode == UpdateMode::Full && !full_updates {
+        warn!(
+            "--update-mode full only changes the write on HelixDB, Neo4j and Memgraph, {} \
+            records have a single field",
+            database_name(database)
+        );
+    }
+    let payload = options.payload_template.as_deref().map(PayloadTemplate::parse).transpose()?;
+    if options.report_phases.unwrap_or_default() && database != Database::HelixDB {
+        warn!("--report-phases only times HelixDB requests, not {}", database_name(database));
+    }
+    if options.deterministic_ids.unwrap_or_default() && database == Database::Dgraph {
+        warn!("--deterministic-ids has no effect on Dgraph, which assigns record ids itself");
+    }
+    if options.deterministic_ids.unwrap_or_default() && database == Database::HelixDB {
+        warn!("--deterministic-ids only keys HelixDB upserts, HelixDB assigns created ids itself");
+    }
+    let binary = options.encoding.is_some_and(|encoding| encoding != BodyEncoding::Json);
+    if binary && database != Database::HelixDB {
+        warn!("--encoding only applies to HelixDB, not {}", database_name(database));
+    }
+    if options.compress.unwrap_or_default() && database != Database::HelixDB {
+        warn!("--compress only applies to HelixDB, not {}", database_name(database));
+    }
+    if options.hql_file.is_some() && database != Database::HelixDB {
+        warn!("--hql-file only applies to HelixDB, not {}", database_name(database));
+    }
+    if options.neo4j_routing.unwrap_or_default() && database != Database::Neo4j {
+        warn!("--neo4j-routing only applies to Neo4j, not {}", database_name(database));
+    }
+    let schema = options.schema.as_deref().map(RecordSchema::load).transpose()?;
+    if schema.is_some() && payload.is_some() {
+        return Err(anyhow::anyhow!("--schema and --payload-template both shape records, pick one"));
+    }
+    let schema_engines = [Database::HelixDB, Database::Sqlite, Database::MySql, Database::Neo4j];
+    if schema.is_some() && !schema_engines.contains(&database) {
+        warn!(
+            "--schema isn't supported for {}, records keep a single field",
+            database_name(database)
+        );
+    }
+    if let Some(name) = &options.dataset_name {
+        if !utils::is_identifier(name) {
+            return Err(anyhow::anyhow!(
+                "--dataset-name {:?} must be letters, digits and underscores",
+                name
+            ));
+        }
+        let named_engines = [
+            Database::Sqlite,
+            Database::DuckDB,
+            Database::LibSQL,
+            Database::MySql,
+            Database::ClickHouse,
+            Database::Cassandra,
+            Database::Neo4j,
+            Database::Memgraph,
+        ];
+        if !named_engines.contains(&database) {
+            warn!(
+                "--dataset-name isn't supported for {}, records stay where they are",
+                database_name(database)
+            );
+        }
+    }
+    if payload.is_some() && database != Database::HelixDB {
+        warn!(
+            "--payload-template only applies to HelixDB, {} records keep a single field",
+            database_name(database)
+        );
+    }
+    let data_index = match (options.create_index, options.no_index) {
+        (Some(true), _) => Some(true),
+        (_, Some(true)) => Some(false),
+        _ => None,
+    };
+    let indexed = matches!(
+        database,
+        Database::HelixDB | Database::Neo4j | Database::Memgraph | Database::Sqlite
+    );
+    if data_index.is_some() && !indexed {
+        warn!(
+            "--create-index and --no-index only apply to HelixDB, Neo4j, Memgraph and SQLite, \
+            not {}",
+            database_name(database)
+        );
+    }
+    if options.keyspace_size == Some(0) {
+        return Err(anyhow::anyhow!("--keyspace-size must be at least 1"));
+    }
+    let (dims, k) = (options.dims.unwrap_or(768), options.k.unwrap_or(7));
+    if dims == 0 || k == 0 {
+        return Err(anyhow::anyhow!("--dims and --k must be at least 1"));
+    }
+    let count_scans = matches!(options.projection, Some(Projection::Count));
+    if options.verify_scan.unwrap_or_default() && count_scans {
+        warn!("--verify-scan needs scans to return records, --projection count only counts them");
+    }
+    let read_projection = options.read_projection.unwrap_or_default();
+    if let Projection::Count = read_projection {
+        return Err(anyhow::anyhow!("--read-projection must be id or full"));
+    }
+    Ok(Benchmark {
+        database,
+        endpoint: options.endpoint.clone(),
+        wait_for_ready: options.wait_for_ready.map(Duration::from_secs),
+        precision: options.precision.unwrap_or_default(),
+        http2: options.http2.unwrap_or_default(),
+        compress: options.compress.unwrap_or_default(),
+        encoding: options.encoding.unwrap_or_default(),
+        ca_cert: options.ca_cert.clone(),
+        accept_invalid_certs: options.danger_accept_invalid_certs.unwrap_or_default(),
+        connections: options
+            .report_connections
+            .unwrap_or_default()
+            .then(ConnectionCounter::default),
+        phases: options.report_phases.unwrap_or_default().then(PhaseTimer::default),
+        reconnects: Arc::default(),
+        // no more connections are busy at once than requests are allowed in flight
+        pool_max_idle_per_host: options.pool_max_idle_per_host.unwrap_or(
+            options
+                .concurrency
+                .unwrap_or(1)
+                .min(options.max_in_flight.unwrap_or(usize::MAX))
+                .max(1),
+        ),
+        pool_idle_timeout: options.pool_idle_timeout.map(Duration::from_secs),
+        protocol: options.protocol.unwrap_or_default(),
+        paths: options.paths.clone().unwrap_or_default(),
+        hql_file: options.hql_file.clone(),
+        neo4j_routing: options.neo4j_routing.unwrap_or_default(),
+        workload: Workload {
+            projection: options.projection.unwrap_or_default(),
+            read_projection,
+            scan_page_size: options.scan_page_size,
+            verify_scan: options.verify_scan.unwrap_or_default(),
+            values: distinct_values
+                .filter(|&count| count > 0)
+                .map(|count| Arc::new(utils::generate_values(count, sizes, options.seed))),
+            read_distribution: options.read_distribution.unwrap_or_default(),
+            zipf_exponent,
+            read_count: options.read_count,
+            shuffle: options.shuffle.unwrap_or_default(),
+            keyspace_size: options.keyspace_size,
+            seed: options.seed,
+            id_seed: options
+                .deterministic_ids
+                .unwrap_or_default()
+                .then(|| options.seed.unwrap_or(0)),
+            id_sources: Arc::default(),
+            update_mode,
+            payload,
+            schema: schema.map(Arc::new),
+            dataset_name: options.dataset_name.clone(),
+            external_ids: options.ids_file.is_some(),
+            read_verify: options.read_verify.unwrap_or_default(),
+            read_verify_every: options.read_verify_every.unwrap_or(100).max(1),
+            data_index,
+            dims,
+            k,
+            metric: options.metric.unwrap_or_default(),
+            track_vectors: false,
+            search_results: options.search_results.unwrap_or_default(),
+            vectors_created: Arc::default(),
+            ops_per_txn: options.ops_per_txn.unwrap_or(1).max(1),
+            batch_size: options.batch_size.unwrap_or(100).max(1),
+        },
+        runner,
+    })
+}
+
+fn phase_reports(options: &Benchmark) -> Vec<PhaseReport> {
+    let totals = options.phases.as_ref().map(PhaseTimer::totals).unwrap_or_default();
+    totals.into_iter().map(|(route, totals)| PhaseReport::new(route, totals)).collect()
+}
+
+// Connections are counted as the HTTP clients resolve hostnames, which misses Bolt and
+// embedded engines and endpoints given as IP addresses
+fn warn_uncounted_connections(options: &Benchmark) {
+    if matches!(
+        options.database,
+        Database::Sqlite
+            | Database::DuckDB
+            | Database::LibSQL
+            | Database::Memgraph
+            | Database::MySql
+            | Database::Cassandra
+    ) {
+        warn!(
+            "--report-connections only counts HTTP connections, {} has none",
+            database_name(options.database)
+        );
+        return;
+    }
+    let is_ip = options
+        .endpoint
+        .as_deref()
+        .and_then(|endpoint| reqwest::Url::parse(endpoint).ok())
+        .and_then(|url| url.host_str().map(|host| host.trim_matches(['[', ']']).to_string()))
+        .is_some_and(|host| host.parse::<std::net::IpAddr>().is_ok());
+    if is_ip {
+        warn!("--report-connections can't count connections to an IP address, use a hostname");
+    }
+}
+
+// The first Ctrl-C stops the run at the next request and prints what completed so far, a
+// second one aborts immediately
+fn handle_interrupts(runner: Runner) {
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            runner.cancel();
+            eprintln!("\nInterrupted, waiting for in-flight requests (Ctrl-C again to abort)");
+            if tokio::signal::ctrl_c().await.is_ok() {
+                std::process::exit(130);
+            }
+        }
+    });
+}
+
+// `--quiet` prints the JSON report alone, whatever `--output` says
+fn output_format(options: &BenchConfig) -> OutputFormat {
+    match options.quiet.unwrap_or_default() {
+        true => OutputFormat::Json,
+        false => options.output.unwrap_or_default(),
+    }
+}
+
+// Prints a JSON report to stdout, on one line with `--quiet` so scripts can read it as such
+fn print_json<T: Serialize>(report: &T, quiet: bool) -> Result<()> {
+    let json = match quiet {
+        true => serde_json::to_string(report)?,
+        false => serde_json::to_string_pretty(report)?,
+    };
+    println!("{}", json);
+    Ok(())
+}
+
+// Checks of the options `bench` makes before connecting, returning the ids of `--ids-file`
+fn check_bench(options: &BenchConfig) -> Result<Option<Vec<String>>> {
+    let loaded_ids = options.ids_file.as_deref().map(read_ids).transpose()?;
+    let counts_everything = options.verify.unwrap_or_default() || options.purge.unwrap_or_default();
+    if loaded_ids.is_some() && counts_everything {
+        return Err(anyhow::anyhow!(
+            "--ids-file can't be combined with --verify or --purge, which expect every record \
+            to come from the run"
+        ));
+    }
+    let seeded = options.prefill.is_some_and(|n| n > 0) || loaded_ids.is_some();
+    let operation = options.operation.as_deref().unwrap_or("all");
+    let database = options.database.unwrap_or_default();
+    let vector =
+        <Operation as clap::ValueEnum>::from_str(operation, true).is_ok_and(|op| op.is_vector());
+    if vector && !engines::supports_vectors(database) {
+        return Err(anyhow::anyhow!(
+            "{} isn't supported for {}",
+            operation.to_lowercase(),
+            database_name(database)
+        ));
+    }
+    if operation.eq_ignore_ascii_case("all") {
+        let ops = options.ops.clone().unwrap_or_else(|| default_ops(database));
+        check_ops(&ops, seeded, database)?;
+    } else if options.ops.is_some() {
+        warn!("--ops only applies to `all`, running {} alone", operation);
+    }
+    let iterations = options.iterations.unwrap_or(1).max(1);
+    let drop_slowest = options.drop_slowest.unwrap_or(0);
+    if drop_slowest >= iterations {
+        return Err(anyhow::anyhow!(
+            "--drop-slowest {} would leave none of the {} iterations",
+            drop_slowest,
+            iterations
+        ));
+    }
+    Ok(loaded_ids)
+}
+
+// The options with every default filled in, as recorded in the JSON report
+fn effective_config(options: BenchConfig, benchmark: &Benchmark) -> BenchConfig {
+    BenchConfig {
+        operation: Some(options.operation.clone().unwrap_or_else(|| "all".to_string())),
+        count: Some(options.count.unwrap_or(500_000)),
+        database: Some(benchmark.database),
+        concurrency: Some(options.concurrency.unwrap_or(1)),
+        precision: Some(benchmark.precision),
+        projection: Some(benchmark.workload.projection),
+        read_projection: Some(benchmark.workload.read_projection),
+        dims: Some(benchmark.workload.dims),
+        k: Some(benchmark.workload.k),
+        metric: Some(benchmark.workload.metric),
+        output: Some(output_format(&options)),
+        iterations: Some(options.iterations.unwrap_or(1).max(1)),
+        ..options
+    }
+}
+
+// Options that are each valid but don't make sense together, or do nothing on the database.
+// Every run rejects them, and `validate-config` lists them.
+fn conflicts(options: &BenchConfig, database: Database) -> Vec<String> {
+    let mut conflicts = Vec::new();
+    if options.concurrency == Some(0) {
+        conflicts.push("--concurrency must be at least 1".to_string());
+    }
+    if let (Some(duration), Some(count)) = (options.duration, options.count) {
+        conflicts.push(format!(
+            "--duration {} and --count {} are both set, operations run for the duration \
+            whatever the count",
+            duration, count
+        ));
+    }
+    let metric_engines = [Database::Weaviate, Database::Milvus, Database::OpenSearch];
+    if let Some(metric) = options.metric.filter(|_| !metric_engines.contains(&database)) {
+        conflicts.push(format!(
+            "--metric {} only applies to Weaviate, Milvus and OpenSearch, not {}",
+            metric.as_str(),
+            database_name(database)
+        ));
+    }
+    conflicts
+}
+
+// Runs every check `bench` would before connecting, prints the resolved options and fails if
+// any check did
+fn validate_config(options: BenchConfig) -> Result<()> {
+    let database = options.database.unwrap_or_default();
+    let checks = check_bench(&options).and_then(|_| limit_rps(&options));
+    let mut problems: Vec<String> = checks.err().map(|e| format!("{:#}", e)).into_iter().collect();
+    let runner = Runner::new(options.concurrency.unwrap_or(1), None).with_quiet(true);
+    match resolve_benchmark(database, &options, runner) {
+        Ok(benchmark) => {
+            let config = effective_config(options, &benchmark);
+            print!("{}", toml::to_string(&config)?);
+        }
+        Err(e) => problems.push(format!("{:#}", e)),
+    }
+    if problems.is_empty() {
+        eprintln!("The configuration is valid");
+        return Ok(());
+    }
+    for problem in &problems {
+        eprintln!("error: {}", problem);
+    }
+    Err(anyhow::anyhow!("{} problem(s) in the configuration", problems.len()))
+}
+
+async fn bench(options: BenchConfig) -> Result<()> {
+    let mut metadata = RunMetadata::collect();
+    let operation = options.operation.clone().unwrap_or_else(|| "all".to_string());
+    let count = options.count.unwrap_or(500_000);
+    let database = options.database.unwrap_or_default();
+    let concurrency = options.concurrency.unwrap_or(1);
+    let output = output_format(&options);
+    let iterations = options.iterations.unwrap_or(1).max(1);
+    let ops = options.ops.clone().unwrap_or_else(|| default_ops(database));
+    let loaded_ids = check_bench(&options)?;
+
+    let dry_run = options.dry_run.unwrap_or_default();
+    let verify = options.verify.unwrap_or_default();
+    let cleanup = options.cleanup.unwrap_or_default();
+    let purge = options.purge.unwrap_or_default();
+    let sample_memory = options.sample_memory.unwrap_or_default();
+    let prefill_count = options.prefill;
+    let average = options.average.unwrap_or_default();
+    let drop_slowest = options.drop_slowest.unwrap_or(0);
+    let (max_p99_ms, min_throughput) = (options.max_p99_ms, options.min_throughput);
+    let duration = options.duration.map(Duration::from_secs);
+    let run_dir = match &options.output_dir {
+        Some(base) => Some(create_run_dir(base, database)?),
+        None => None,
+    };
+    // the bundle's copy, unless the raw latencies were asked for somewhere else as well
+    let raw_latencies = options
+        .raw_latencies
+        .clone()
+        .or_else(|| run_dir.as_ref().map(|dir| dir.join("latencies.csv")));
+    let latency_log = raw_latencies.as_deref().map(LatencyLog::create).transpose()?;
+    let runner = Runner::new(concurrency, duration)
+        .with_max_errors(max_errors(&options))
+        .with_limit_rps(limit_rps(&options)?)
+        .with_start_jitter(options.start_jitter_ms.map(Duration::from_millis))
+        .with_think_time(options.think_time_ms.map(Duration::from_millis))
+        .with_max_in_flight(options.max_in_flight)
+        .with_sample_every(options.sample_every)
+        .with_quiet(options.quiet.unwrap_or_default())
+        .with_time_budget(options.operation_timeout_budget.map(Duration::from_secs))
+        .with_latency_log(latency_log.clone());
+    let benchmark = resolve_benchmark(database, &options, runner.clone())?;
+    let config = effective_config(options, &benchmark);
+    if benchmark.connections.is_some() {
+        warn_uncounted_connections(&benchmark);
+    }
+
+    if dry_run {
+        // the dry run reports a failing startup itself, once any wait for the server is over
+        let engine = match benchmark.wait_for_ready {
+            Some(_) => engines::connect(&benchmark).await?.0,
+            None => engines::setup(&benchmark).await?,
+        };
+        return run_dry_run(&*engine, database).await;
+    }
+
+    let (engine, mut client) = engines::connect(&benchmark).await?;
+    metadata.server_version = client.server_version().await;
+    let server = server_name(database, metadata.server_version.as_deref());
+    handle_interrupts(runner.clone());
+
+    let mut verifier = match verify {
+        true => Some(CountVerifier::new(&*client).await?),
+        false => None,
+    };
+
+    let sampler = match (sample_memory, engine.stats_url()) {
+        (false, _) => None,
+        (true, Some(url)) => {
+            // kept out of the connection count, which is about the benchmark's own requests
+            let options = Benchmark {
+                connections: None,
+                ..benchmark.clone()
             };
+            let client = utils::http_client_builder(&options)?.build()?;
+            Some(MemorySampler::start(client, url))
+        }
+        (true, None) => {
+            warn!("{} exposes no memory stats, not sampling memory", database_name(database));
+            None
+        }
+    };
+
+    // Every iteration gets a fresh client, so it starts without the ids of the one before
+    let mut runs = Vec::new();
+    for iteration in 0..iterations {
+        if iteration > 0 {
+            client = engine.create_client().await?;
+        }
+        if purge {
+            info!("purging records");
+            client.reset(true).await?;
+        }
+        if iteration > 0 || purge {
+            if let Some(verifier) = verifier.as_mut() {
+                verifier.rebase(&*client).await?;
+            }
+        }
+        if iterations > 1 && !config.quiet.unwrap_or_default() {
+            eprintln!("Iteration {}/{}", iteration + 1, iterations);
+        }
+        if let Some(ids) = &loaded_ids {
+            client.load_ids(ids)?;
+        }
+        if let Some(count) = prefill_count.filter(|&count| count > 0) {
+            prefill(&mut *client, &runner, count).await?;
+        }
+        let results = if operation.to_lowercase() == "all" {
+            run_all_benchmarks(&mut *client, &benchmark, &ops, count, verifier.as_mut()).await?
+        } else {
+            let result = run_benchmark(&mut *client, &runner, &operation, count)
+                .await?
+                .with_payload(payload_bytes(&benchmark, &operation.to_lowercase()));
+            if let Some(verifier) = verifier.as_mut() {
+                verifier.check(&*client, &operation.to_lowercase()).await?;
+            }
+            vec![result]
+        };
+        runs.push(results);
+        if cleanup {
+            info!(records = client.tracked_ids(), "cleaning up");
+            client.cleanup().await?;
+        }
+        if runner.is_cancelled() {
+            break;
+        }
+    }
+    if let Some(log) = &latency_log {
+        log.flush()?;
+    }
+    let connections = benchmark.connections.as_ref().map(ConnectionCounter::count);
+    let reconnects = benchmark.reconnects.load(Ordering::SeqCst);
+    let phases = phase_reports(&benchmark);
+    let memory = match sampler {
+        Some(sampler) => sampler.finish().await,
+        None => None,
+    };
+    let aggregates = match iterations {
+        1 => Vec::new(),
+        _ => OperationAggregate::from_runs(&without_slowest(&runs, drop_slowest)),
+    };
+    let runs_completed = runs.len();
+    let results: Vec<OperationResult> = runs.into_iter().flatten().collect();
 
-            let options = Benchmark { database, endpoint };
-            let engine: Box<dyn BenchmarkEngine> = match database {
-                Database::HelixDB => Box::new(HelixDBEngine::setup(&options).await?),
-                Database::Neo4j => Box::new(Neo4jEngine::setup(&options).await?),
+    if output == OutputFormat::Text {
+        if !aggregates.is_empty() {
+            let dropped = drop_slowest.min(runs_completed.saturating_sub(1));
+            print_aggregates(&server, runs_completed, dropped, average, &aggregates);
+        } else if operation.to_lowercase() == "all" {
+            let scope = match duration {
+                Some(duration) => format!("{:?} per operation", duration),
+                None => format!("{} operations", count),
+            };
+            print_results(&server, &scope, &results, config.detailed.unwrap_or_default());
+        } else {
+            let completed = if runner.is_cancelled() || runner.truncated() {
+                format!("{} of {}", runner.completed(), count)
+            } else {
+                runner.completed().to_string()
             };
+            print_result(&server, &operation, &completed, &results[0]);
+        }
+        if let Some(memory) = &memory {
+            print_memory(memory);
+        }
+        if let Some(opened) = connections {
+            print_connections(opened, &results);
+        }
+        if reconnects > 0 {
+            println!("\nReconnections: {}", reconnects);
+        }
+        if !phases.is_empty() {
+            print_phases(&phases);
+        }
+    }
+
+    if let Some(path) = &config.timeseries {
+        write_timeseries(path, &results)?;
+    }
+
+    // count exisiting records, unless the run was cut short
+    let existing_records = match runner.is_cancelled() {
+        true => None,
+        false => Some(client.count_records().await?),
+    };
+    // With JSON output stdout carries only the report, so status lines go to stderr
+    let status = |line: String| match output {
+        _ if config.quiet.unwrap_or_default() => {}
+        OutputFormat::Text => println!("{}", line),
+        OutputFormat::Json => eprintln!("{}", line),
+    };
+    let report = JsonReport {
+        metadata,
+        database: database_name(database),
+        config: &config,
+        results: &results,
+        aggregates: &aggregates,
+        existing_records,
+        memory,
+        connections,
+        reconnects,
+        phases: &phases,
+    };
+    if output == OutputFormat::Json {
+        print_json(&report, config.quiet.unwrap_or_default())?;
+    }
+    if let Some(dir) = &run_dir {
+        write_json(&dir.join("results.json"), &report)?;
+        write_json(&dir.join("config.json"), &config)?;
+        write_histogram(&dir.join("histogram.csv"), &results)?;
+        write_timeseries(&dir.join("timeseries.csv"), &results)?;
+        if let Some(path) = &config.raw_latencies {
+            status(format!("Raw latencies written to {} instead", path.display()));
+        }
+        status(format!("Report written to {}", dir.display()));
+    }
+    match existing_records {
+        Some(count) => status(format!("Existing records: {}", count)),
+        None => {
+            status("Run interrupted, results are partial".to_string());
+            std::process::exit(130);
+        }
+    }
+
+    if let Some(verifier) = &verifier {
+        if !verifier.mismatches.is_empty() {
+            return Err(VerifyError(format!(
+                "Record count verification failed: {}",
+                verifier.mismatches.join("; ")
+            ))
+            .into());
+        }
+        status("Record counts verified".to_string());
+    }
+
+    let violations = check_thresholds(&results, max_p99_ms, min_throughput);
+    if !violations.is_empty() {
+        for violation in &violations {
+            status(format!("Threshold violated: {}", violation));
+        }
+        return Err(ThresholdError(format!(
+            "{} performance threshold(s) violated",
+            violations.len()
+        ))
+        .into());
+    }
+
+    Ok(())
+}
+
+// The operation that creates the records or vectors `operation` works on, for the commands
+// that time a single operation over and over
+fn seed_operation(command: &str, operation: &str) -> Result<Option<&'static str>> {
+    match operation {
+        "create" | "create_vectors" => Ok(None),
+        "read" | "update" | "read_by_data" => Ok(Some("create")),
+        "search_vectors" => Ok(Some("create_vectors")),
+        _ => Err(anyhow::anyhow!(
+            "Unsupported {} operation: {} (expected create, read, update, read_by_data, \
+            create_vectors or search_vectors)",
+            command,
+            operation
+        )),
+    }
+}
+
+// Runs one operation at doubling concurrency levels for a fixed time each, to find where
+// throughput stops scaling and latency takes off
+async fn ramp(options: BenchConfig, step: Duration, max_concurrency: usize) -> Result<()> {
+    let operation = options
+        .operation
+        .clone()
+        .unwrap_or_else(|| "read".to_string())
+        .to_lowercase();
+    let count = options.count.unwrap_or(10_000);
+    let database = options.database.unwrap_or_default();
+    let seed = seed_operation("ramp", &operation)?;
+
+    let start_concurrency = options.concurrency.unwrap_or(1).max(1);
+    let latency_log = options.raw_latencies.as_deref().map(LatencyLog::create).transpose()?;
+    let runner = Runner::new(start_concurrency, None)
+        .with_max_errors(max_errors(&options))
+        .with_limit_rps(limit_rps(&options)?)
+        .with_start_jitter(options.start_jitter_ms.map(Duration::from_millis))
+        .with_think_time(options.think_time_ms.map(Duration::from_millis))
+        .with_max_in_flight(options.max_in_flight)
+        .with_sample_every(options.sample_every)
+        .with_quiet(options.quiet.unwrap_or_default())
+        .with_latency_log(latency_log.clone());
+    // keep enough idle connections for the highest level rather than the first
+    let options = BenchConfig {
+        pool_max_idle_per_host: options.pool_max_idle_per_host.or(Some(max_concurrency)),
+        ..options
+    };
+    let options = resolve_benchmark(database, &options, runner.clone())?;
+    let (_engine, mut client) = engines::connect(&options).await?;
+    handle_interrupts(runner.clone());
+
+    if let Some(seed) = seed {
+        run_benchmark(&mut *client, &runner, seed, count).await?;
+    }
+
+    let mut steps = Vec::new();
+    runner.set_duration(Some(step));
+    let mut concurrency = start_concurrency;
+    while concurrency <= max_concurrency && !runner.is_cancelled() {
+        runner.set_concurrency(concurrency);
+        let result = run_benchmark(&mut *client, &runner, &operation, count).await?;
+        steps.push((concurrency, result));
+        concurrency *= 2;
+    }
+    if let Some(log) = &latency_log {
+        log.flush()?;
+    }
+
+    println!(
+        "\nRamp Results for {} ({}, {:?} per step):",
+        database_name(database),
+        operation,
+        step
+    );
+    println!("{:-<74}", "");
+    println!(
+        "{:<11} | {:<18} | {:<15} | {:<15}",
+        "Concurrency", "Throughput (ops/s)", "p50 (ms)", "p99 (ms)"
+    );
+    println!("{:-<74}", "");
+    for (concurrency, result) in &steps {
+        println!(
+            "{:<11} | {:<18.2} | {:<15} | {:<15}",
+            concurrency,
+            result.throughput,
+            format_ms(result.latency.map(|l| l.p50)),
+            format_ms(result.latency.map(|l| l.p99)),
+        );
+    }
+    if runner.is_cancelled() {
+        println!("Run interrupted, results are partial");
+        std::process::exit(130);
+    }
+
+    Ok(())
+}
 
-            let mut client = engine.create_client().await?;
-
-            if operation.to_lowercase() == "all" {
-                let results = run_all_benchmarks(&mut *client, count).await?;
-                println!(
-                    "\nBenchmark Results for {} ({} operations):",
-                    database_name(database),
-                    count,
-                );
-                println!("{:-<80}", "");
-                println!(
-                    "{:<10} | {:<15} | {:<15} | {:<15}",
-                    "Operation", "Total Time", "Avg Time/Req (ms)", "Throughput (ops/s)"
-                );
-                println!("{:-<80}", "");
-                for (op, duration, avg_time, throughput) in results {
-                    println!(
-                        "{:<10} | {:<15} | {:<15.6} | {:<15.2}",
-                        op,
-                        format!("{:?}", duration),
-                        avg_time * 1000.0,
-                        throughput
-                    );
+// Like `ramp`, doubles the concurrency of one operation each step, but stops at the first level
+// whose error rate or p99 is over its limit and reports the last level within both as the
+// highest sustainable load
+async fn stress(
+    options: BenchConfig,
+    step: Duration,
+    max_concurrency: usize,
+    max_error_rate: f64,
+) -> Result<()> {
+    let operation = options
+        .operation
+        .clone()
+        .unwrap_or_else(|| "read".to_string())
+        .to_lowercase();
+    let count = options.count.unwrap_or(10_000);
+    let database = options.database.unwrap_or_default();
+    let max_p99_ms = options.max_p99_ms.unwrap_or(100.0);
+    let seed = seed_operation("stress", &operation)?;
+
+    let start_concurrency = options.concurrency.unwrap_or(1).max(1);
+    let latency_log = options.raw_latencies.as_deref().map(LatencyLog::create).transpose()?;
+    // failures are what is being looked for, so they are counted rather than fatal
+    let runner = Runner::new(start_concurrency, None)
+        .with_max_errors(Some(options.max_errors.unwrap_or(usize::MAX)))
+        .with_limit_rps(limit_rps(&options)?)
+        .with_start_jitter(options.start_jitter_ms.map(Duration::from_millis))
+        .with_think_time(options.think_time_ms.map(Duration::from_millis))
+        .with_max_in_flight(options.max_in_flight)
+        .with_sample_every(options.sample_every)
+        .with_quiet(options.quiet.unwrap_or_default())
+        .with_latency_log(latency_log.clone());
+    let options = BenchConfig {
+        pool_max_idle_per_host: options.pool_max_idle_per_host.or(Some(max_concurrency)),
+        ..options
+    };
+    let options = resolve_benchmark(database, &options, runner.clone())?;
+    let (_engine, mut client) = engines::connect(&options).await?;
+    handle_interrupts(runner.clone());
+
+    if let Some(seed) = seed {
+        run_benchmark(&mut *client, &runner, seed, count).await?;
+    }
+
+    println!(
+        "\nStress Results for {} ({}, {:?} per step, p99 under {} ms, errors under {}%):",
+        database_name(database),
+        operation,
+        step,
+        max_p99_ms,
+        max_error_rate
+    );
+    println!("{:-<74}", "");
+    println!(
+        "{:<11} | {:<18} | {:<15} | {:<15}",
+        "Concurrency", "Throughput (ops/s)", "p99 (ms)", "Errors (%)"
+    );
+    println!("{:-<74}", "");
+    let mut sustained: Option<(usize, OperationResult)> = None;
+    let mut breach = None;
+    runner.set_duration(Some(step));
+    let mut concurrency = start_concurrency;
+    while concurrency <= max_concurrency && !runner.is_cancelled() {
+        runner.set_concurrency(concurrency);
+        let result = match run_benchmark(&mut *client, &runner, &operation, count).await {
+            Ok(result) => result,
+            Err(e) => {
+                breach = Some(format!("concurrency {} failed: {}", concurrency, e));
+                break;
+            }
+        };
+        println!(
+            "{:<11} | {:<18.2} | {:<15} | {:<15.2}",
+            concurrency,
+            result.throughput,
+            format_ms(result.latency.map(|l| l.p99)),
+            result.error_rate()
+        );
+        let p99_ms = result.latency.map_or(0.0, |l| l.p99.as_secs_f64() * 1000.0);
+        if result.error_rate() > max_error_rate {
+            breach = Some(format!(
+                "concurrency {} had {:.2}% errors",
+                concurrency,
+                result.error_rate()
+            ));
+            break;
+        }
+        if p99_ms > max_p99_ms {
+            breach = Some(format!("concurrency {} had a p99 of {:.3} ms", concurrency, p99_ms));
+            break;
+        }
+        sustained = Some((concurrency, result));
+        concurrency *= 2;
+    }
+    if let Some(log) = &latency_log {
+        log.flush()?;
+    }
+    if runner.is_cancelled() {
+        println!("Run interrupted, results are partial");
+        std::process::exit(130);
+    }
+
+    let limit = match &breach {
+        Some(breach) => format!("{}, over the limit", breach),
+        None => format!("still within the limits at --max-concurrency {}", max_concurrency),
+    };
+    match sustained {
+        Some((concurrency, result)) => {
+            println!(
+                "\nMax sustainable load: {:.0} ops/s at p99 < {} ms (concurrency {})",
+                result.throughput, max_p99_ms, concurrency
+            );
+            println!("Stopped: {}", limit);
+            Ok(())
+        }
+        None => Err(ThresholdError(format!("No sustainable load: {}", limit)).into()),
+    }
+}
+
+fn parse_replay(path: &Path) -> Result<Vec<ReplayOp>> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("Failed to read trace {}: {}", path.display(), e))?;
+    let mut ops = Vec::new();
+    for (number, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let invalid = |reason: &str| {
+            anyhow::anyhow!("{}:{}: {}: {}", path.display(), number + 1, reason, line)
+        };
+        let mut parts = line.splitn(3, char::is_whitespace);
+        let (operation, id) = match (parts.next(), parts.next()) {
+            (Some(operation), Some(id)) => (operation, id.to_string()),
+            _ => return Err(invalid("expected an operation and an id")),
+        };
+        let data = parts.next().map(|data| data.trim().to_string());
+        ops.push(match (operation.to_lowercase().as_str(), data) {
+            ("create", Some(data)) => ReplayOp::Create { id, data },
+            ("update", Some(data)) => ReplayOp::Update { id, data },
+            ("create" | "update", None) => return Err(invalid("missing data")),
+            ("read", None) => ReplayOp::Read { id },
+            ("delete", None) => ReplayOp::Delete { id },
+            ("read" | "delete", Some(_)) => return Err(invalid("unexpected data")),
+            _ => return Err(invalid("unknown operation")),
+        });
+    }
+    Ok(ops)
+}
+
+// Runs a trace one operation at a time, so each sees the effects of those before it
+async fn replay(options: BenchConfig, path: PathBuf) -> Result<()> {
+    let mut metadata = RunMetadata::collect();
+    let ops = parse_replay(&path)?;
+    let database = options.database.unwrap_or_default();
+    let output = output_format(&options);
+    let latency_log = options.raw_latencies.as_deref().map(LatencyLog::create).transpose()?;
+    let runner = Runner::new(1, None)
+        .with_max_errors(max_errors(&options))
+        .with_limit_rps(limit_rps(&options)?)
+        .with_start_jitter(options.start_jitter_ms.map(Duration::from_millis))
+        .with_think_time(options.think_time_ms.map(Duration::from_millis))
+        .with_max_in_flight(options.max_in_flight)
+        .with_sample_every(options.sample_every)
+        .with_quiet(options.quiet.unwrap_or_default())
+        .with_latency_log(latency_log.clone());
+    let benchmark = resolve_benchmark(database, &options, runner.clone())?;
+    let (_engine, client) = engines::connect(&benchmark).await?;
+    metadata.server_version = client.server_version().await;
+    let server = server_name(database, metadata.server_version.as_deref());
+    handle_interrupts(runner.clone());
+
+    // Trace ids of the records the trace created, to the keys the database gave them
+    let keys: Mutex<HashMap<String, String>> = Mutex::new(HashMap::new());
+    let key = |id: &str| {
+        let keys = keys.lock().unwrap();
+        keys.get(id).cloned().unwrap_or_else(|| id.to_string())
+    };
+    let (client, ops_ref, keys, key) = (&*client, &ops, &keys, &key);
+    runner.reset();
+    let start = Instant::now();
+    runner
+        .run("Replay", ops.len(), |i| async move {
+            match &ops_ref[i] {
+                ReplayOp::Create { id, data } => {
+                    let created = client.create_record(data).await?;
+                    keys.lock().unwrap().insert(id.clone(), created);
                 }
-            } else {
-                let (duration, avg_time, throughput) =
-                    run_benchmark(&mut *client, &operation, count).await?;
-                println!(
-                    "Benchmark: {} {} operations on {}:\n\
-                    Total Time: {:?}\n\
-                    Avg Time/Request: {:.6} ms\n\
-                    Throughput: {:.2} ops/s",
-                    operation,
-                    count,
-                    database_name(database),
-                    duration,
-                    avg_time * 1000.0,
-                    throughput
-                );
+                ReplayOp::Read { id } => client.read_record(&key(id)).await?,
+                ReplayOp::Update { id, data } => client.update_record(&key(id), data).await?,
+                ReplayOp::Delete { id } => client.delete_record(&key(id)).await?,
+            }
+            Ok(())
+        })
+        .await?;
+    let total_time = start.elapsed();
+    if let Some(log) = &latency_log {
+        log.flush()?;
+    }
+
+    let count = match runner.is_cancelled() || runner.errors() > 0 {
+        true => runner.completed(),
+        false => ops.len(),
+    };
+    let (avg_time, throughput) = OperationResult::rates(count, total_time);
+    let latencies = runner.take_latencies();
+    let histogram = Histogram::from_latencies(&latencies);
+    let result = OperationResult {
+        operation: "replay".to_string(),
+        total_time,
+        avg_time,
+        throughput,
+        mb_per_sec: None,
+        latency: LatencyStats::from_latencies(latencies),
+        completed: runner.completed(),
+        errors: runner.errors(),
+        error_breakdown: runner.take_error_breakdown(),
+        truncated: runner.truncated(),
+        timeline: runner.take_timeline(),
+        histogram,
+    };
+
+    match output {
+        OutputFormat::Text => {
+            let completed = match runner.is_cancelled() {
+                true => format!("{} of {}", runner.completed(), ops.len()),
+                false => count.to_string(),
+            };
+            print_result(&server, "replay", &completed, &result);
+            let mut counts: Vec<(&str, usize)> = Vec::new();
+            for op in &ops {
+                match counts.iter_mut().find(|(name, _)| *name == op.name()) {
+                    Some((_, n)) => *n += 1,
+                    None => counts.push((op.name(), 1)),
+                }
+            }
+            let counts: Vec<String> =
+                counts.iter().map(|(name, n)| format!("{} {}", n, name)).collect();
+            println!("Trace: {} ({})", path.display(), counts.join(", "));
+        }
+        OutputFormat::Json => {
+            let config = BenchConfig {
+                operation: Some("replay".to_string()),
+                database: Some(database),
+                ..options
+            };
+            let report = JsonReport {
+                metadata,
+                database: database_name(database),
+                config: &config,
+                results: std::slice::from_ref(&result),
+                aggregates: &[],
+                existing_records: None,
+                memory: None,
+                connections: None,
+                reconnects: benchmark.reconnects.load(Ordering::SeqCst),
+                phases: &[],
+            };
+            print_json(&report, options.quiet.unwrap_or_default())?;
+        }
+    }
+    if runner.is_cancelled() {
+        eprintln!("Run interrupted, results are partial");
+        std::process::exit(130);
+    }
+
+    Ok(())
+}
+
+// Creates vectors then searches them, as a unit with its own report
+async fn bench_vector(options: BenchConfig, recall: Option<usize>) -> Result<()> {
+    let mut metadata = RunMetadata::collect();
+    let count = options.count.unwrap_or(10_000);
+    let database = options.database.unwrap_or_default();
+    if !engines::supports_vectors(database) {
+        return Err(anyhow::anyhow!("vectors aren't supported for {}", database_name(database)));
+    }
+    let concurrency = options.concurrency.unwrap_or(1);
+    let output = output_format(&options);
+    let latency_log = options.raw_latencies.as_deref().map(LatencyLog::create).transpose()?;
+    let runner = Runner::new(concurrency, options.duration.map(Duration::from_secs))
+        .with_max_errors(max_errors(&options))
+        .with_limit_rps(limit_rps(&options)?)
+        .with_start_jitter(options.start_jitter_ms.map(Duration::from_millis))
+        .with_think_time(options.think_time_ms.map(Duration::from_millis))
+        .with_max_in_flight(options.max_in_flight)
+        .with_sample_every(options.sample_every)
+        .with_quiet(options.quiet.unwrap_or_default())
+        .with_time_budget(options.operation_timeout_budget.map(Duration::from_secs))
+        .with_latency_log(latency_log.clone());
+    let mut benchmark = resolve_benchmark(database, &options, runner.clone())?;
+    benchmark.workload.track_vectors = recall.is_some();
+    let workload = benchmark.workload.clone();
+    let (_engine, mut client) = engines::connect(&benchmark).await?;
+    metadata.server_version = client.server_version().await;
+    let server = server_name(database, metadata.server_version.as_deref());
+    handle_interrupts(runner.clone());
+
+    let mut results = Vec::new();
+    for operation in [Operation::CreateVectors, Operation::SearchVectors] {
+        let result = run_benchmark(&mut *client, &runner, operation.as_str(), count).await?;
+        results.push(result.with_payload(payload_bytes(&benchmark, operation.as_str())));
+        if runner.is_cancelled() {
+            break;
+        }
+    }
+    if let Some(log) = &latency_log {
+        log.flush()?;
+    }
+    let recall = match recall {
+        Some(queries) if !runner.is_cancelled() => {
+            info!(queries, "checking recall");
+            let recall = client.vector_recall(queries).await?;
+            if recall.is_none() {
+                let name = database_name(database);
+                warn!("{} searches don't return vector ids, recall not measured", name);
+            }
+            recall
+        }
+        _ => None,
+    };
+
+    match output {
+        OutputFormat::Text => {
+            let scope = format!("{} vectors", count);
+            print_results(&server, &scope, &results, options.detailed.unwrap_or_default());
+            println!(
+                "Vectors: {} dims, k = {}, {} distance",
+                workload.dims,
+                workload.k,
+                workload.metric.as_str()
+            );
+            if let Some(recall) = recall {
+                println!("Recall@{}: {:.4}", workload.k, recall);
+            }
+        }
+        OutputFormat::Json => {
+            let config = BenchConfig {
+                operation: Some("bench-vector".to_string()),
+                count: Some(count),
+                database: Some(database),
+                concurrency: Some(concurrency),
+                precision: Some(benchmark.precision),
+                dims: Some(workload.dims),
+                k: Some(workload.k),
+                metric: Some(workload.metric),
+                ..options
+            };
+            let report = VectorReport {
+                metadata,
+                database: database_name(database),
+                config: &config,
+                results: &results,
+                recall,
+            };
+            print_json(&report, options.quiet.unwrap_or_default())?;
+        }
+    }
+    if runner.is_cancelled() {
+        eprintln!("Run interrupted, results are partial");
+        std::process::exit(130);
+    }
+
+    Ok(())
+}
+
+// The parts of a JSON report that `diff` and `compare` read
+#[derive(Deserialize)]
+struct SavedReport {
+    database: String,
+    results: Vec<OperationResult>,
+}
+
+impl SavedReport {
+    fn load(path: &Path) -> Result<Self> {
+        let file = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", path.display(), e))?;
+        serde_json::from_str(&file)
+            .map_err(|e| anyhow::anyhow!("{} is not a JSON report: {}", path.display(), e))
+    }
+
+    // Mean throughput and latency percentiles per operation in order of first appearance, over
+    // every iteration the report holds
+    fn by_operation(&self) -> Vec<OperationSummary> {
+        let mut operations: Vec<(String, Vec<&OperationResult>)> = Vec::new();
+        for result in &self.results {
+            match operations.iter_mut().find(|(name, _)| *name == result.operation) {
+                Some((_, runs)) => runs.push(result),
+                None => operations.push((result.operation.clone(), vec![result])),
             }
-            // count exisiting records
-            let count = client.count_records().await?;
-            println!("Existing records: {}", count);
         }
+        operations
+            .into_iter()
+            .map(|(name, runs)| {
+                let throughput = runs.iter().map(|r| r.throughput).sum::<f64>() / runs.len() as f64;
+                // mean of a percentile in milliseconds over the runs that have latencies
+                let ms = |percentile: fn(&LatencyStats) -> Duration| {
+                    let values: Vec<f64> = runs
+                        .iter()
+                        .filter_map(|r| r.latency.as_ref().map(percentile))
+                        .map(|d| d.as_secs_f64() * 1000.0)
+                        .collect();
+                    (!values.is_empty()).then(|| values.iter().sum::<f64>() / values.len() as f64)
+                };
+                OperationSummary {
+                    name,
+                    throughput,
+                    p50: ms(|l| l.p50),
+                    p95: ms(|l| l.p95),
+                    p99: ms(|l| l.p99),
+                }
+            })
+            .collect()
     }
+}
+
+struct OperationSummary {
+    name: String,
+    throughput: f64,
+    /// Latency percentiles in milliseconds
+    p50: Option<f64>,
+    p95: Option<f64>,
+    p99: Option<f64>,
+}
 
+// Percent change from `before` to `after`
+fn percent_change(before: f64, after: f64) -> Option<f64> {
+    (before != 0.0).then(|| (after - before) / before * 100.0)
+}
+
+fn format_change(change: Option<f64>, regressed: bool, color: bool) -> String {
+    let text = match change {
+        Some(change) => format!("{:+.1}%", change),
+        None => "-".to_string(),
+    };
+    match regressed && color {
+        true => format!("\x1b[31m{:<10}\x1b[0m", text),
+        false => format!("{:<10}", text),
+    }
+}
+
+// Compares the operations both reports ran. Throughput falling or p99 rising by more than
+// `threshold` percent is a regression, which fails the command so CI can gate on it.
+fn diff(baseline: &Path, current: &Path, threshold: f64) -> Result<()> {
+    let (before, after) = (SavedReport::load(baseline)?, SavedReport::load(current)?);
+    if before.database != after.database {
+        warn!("comparing {} against {}", after.database, before.database);
+    }
+    let color = std::io::stdout().is_terminal();
+    let after_ops = after.by_operation();
+    println!(
+        "\n{:<15} | {:<14} | {:<14} | {:<10} | {:<10} | {:<10} | {:<10}",
+        "Operation", "Throughput", "(baseline)", "Change", "p99 (ms)", "(baseline)", "Change"
+    );
+    println!("{:-<101}", "");
+    let mut regressions = Vec::new();
+    for op in before.by_operation() {
+        let (name, throughput, p99) = (op.name, op.throughput, op.p99);
+        let Some(new) = after_ops.iter().find(|new| new.name == name) else {
+            println!("{:<15} | missing from {}", name, current.display());
+            continue;
+        };
+        let (new_throughput, new_p99) = (&new.throughput, &new.p99);
+        let throughput_change = percent_change(throughput, *new_throughput);
+        let p99_change = p99.zip(*new_p99).and_then(|(p99, new)| percent_change(p99, new));
+        let slower = throughput_change.is_some_and(|change| change < -threshold);
+        let later = p99_change.is_some_and(|change| change > threshold);
+        if slower {
+            regressions.push(format!("{} throughput {:+.1}%", name, throughput_change.unwrap()));
+        }
+        if later {
+            regressions.push(format!("{} p99 {:+.1}%", name, p99_change.unwrap()));
+        }
+        let ms = |p99: Option<f64>| p99.map_or("-".to_string(), |p99| format!("{:.3}", p99));
+        println!(
+            "{:<15} | {:<14.2} | {:<14.2} | {} | {:<10} | {:<10} | {}",
+            name,
+            new_throughput,
+            throughput,
+            format_change(throughput_change, slower, color),
+            ms(*new_p99),
+            ms(p99),
+            format_change(p99_change, later, color),
+        );
+    }
+    if !regressions.is_empty() {
+        for regression in &regressions {
+            println!("Regression: {}", regression);
+        }
+        return Err(ThresholdError(format!(
+            "{} regression(s) beyond {}%",
+            regressions.len(),
+            threshold
+        ))
+        .into());
+    }
+    println!("No regressions beyond {}%", threshold);
+    Ok(())
+}
+
+// Formats a `compare` cell, marking the best value in its column in green, or with a `*` when
+// stdout isn't a terminal
+fn format_best(text: String, width: usize, best: bool, color: bool) -> String {
+    match (best, color) {
+        (true, true) => format!("\x1b[1;32m{:<width$}\x1b[0m", text),
+        (true, false) => format!("{:<width$}", format!("{}*", text)),
+        (false, _) => format!("{:<width$}", text),
+    }
+}
+
+// Lays the reports of several databases side by side, an operation at a time: throughput and
+// the p50, p95 and p99 latencies of each, with the best of each column marked
+fn compare(paths: &[PathBuf]) -> Result<()> {
+    let reports = paths
+        .iter()
+        .map(|path| SavedReport::load(path))
+        .collect::<Result<Vec<_>>>()?;
+    // the database names the rows, or the file when two reports are of the same database
+    let labels: Vec<String> = reports
+        .iter()
+        .zip(paths)
+        .map(|(report, path)| {
+            match reports.iter().filter(|r| r.database == report.database).count() {
+                1 => report.database.clone(),
+                _ => path.file_name().unwrap_or_default().to_string_lossy().into_owned(),
+            }
+        })
+        .collect();
+    let summaries: Vec<Vec<OperationSummary>> =
+        reports.iter().map(SavedReport::by_operation).collect();
+    let mut operations: Vec<&str> = Vec::new();
+    for summary in summaries.iter().flatten() {
+        if !operations.contains(&summary.name.as_str()) {
+            operations.push(&summary.name);
+        }
+    }
+    let color = std::io::stdout().is_terminal();
+    for operation in operations {
+        let rows: Vec<(&String, &OperationSummary)> = labels
+            .iter()
+            .zip(&summaries)
+            .filter_map(|(label, ops)| Some((label, ops.iter().find(|s| s.name == operation)?)))
+            .collect();
+        let best_throughput = rows.iter().map(|(_, s)| s.throughput).fold(f64::MIN, f64::max);
+        let lowest = |percentile: fn(&OperationSummary) -> Option<f64>| {
+            rows.iter().filter_map(|(_, s)| percentile(s)).fold(f64::INFINITY, f64::min)
+        };
+        let percentiles: [fn(&OperationSummary) -> Option<f64>; 3] =
+            [|s| s.p50, |s| s.p95, |s| s.p99];
+        let best = percentiles.map(lowest);
+        println!("\n{}", operation);
+        println!(
+            "{:<20} | {:<14} | {:<10} | {:<10} | {:<10}",
+            "Database", "Throughput", "p50 (ms)", "p95 (ms)", "p99 (ms)"
+        );
+        println!("{:-<76}", "");
+        for (label, summary) in rows {
+            let throughput = format_best(
+                format!("{:.2}", summary.throughput),
+                14,
+                summary.throughput == best_throughput,
+                color,
+            );
+            let latencies: Vec<String> = percentiles
+                .iter()
+                .zip(best)
+                .map(|(percentile, best)| match percentile(summary) {
+                    Some(ms) => format_best(format!("{:.3}", ms), 10, ms == best, color),
+                    None => format!("{:<10}", "-"),
+                })
+                .collect();
+            println!("{:<20} | {} | {}", label, throughput, latencies.join(" | "));
+        }
+    }
+    if !color {
+        println!("\n* best in its column");
+    }
     Ok(())
 }
+
+// Builds the runtime the run executes on: the multi-thread scheduler by default, or with
+// `--single-thread` everything on the main thread, to measure the client's own overhead
+fn runtime(options: &BenchConfig) -> Result<tokio::runtime::Runtime> {
+    let mut builder = match options.single_thread.unwrap_or_default() {
+        true => tokio::runtime::Builder::new_current_thread(),
+        false => tokio::runtime::Builder::new_multi_thread(),
+    };
+    if let Some(cores) = options.cpu_affinity.as_deref().map(core_ids).transpose()?.flatten() {
+        if options.single_thread.unwrap_or_default() {
+            pin_thread(cores[0]);
+        } else {
+            builder.worker_threads(cores.len());
+        }
+        // threads take the cores in turn, blocking-pool threads included
+        let next = AtomicUsize::new(0);
+        builder.on_thread_start(move || {
+            pin_thread(cores[next.fetch_add(1, Ordering::Relaxed) % cores.len()]);
+        });
+    }
+    Ok(builder.enable_all().build()?)
+}
+
+// The cores `--cpu-affinity` lists, checked against the ones this machine has. `None` where
+// the platform can't say which cores there are, and so can't pin threads to them either.
+fn core_ids(cores: &[usize]) -> Result<Option<Vec<core_affinity::CoreId>>> {
+    if cores.is_empty() {
+        return Err(anyhow::anyhow!("--cpu-affinity needs at least one core"));
+    }
+    let Some(available) = core_affinity::get_core_ids() else {
+        warn!("CPU affinity isn't supported on this platform, threads run unpinned");
+        return Ok(None);
+    };
+    cores
+        .iter()
+        .map(|&id| {
+            let core = available.iter().find(|core| core.id == id).copied();
+            core.ok_or_else(|| {
+                let count = available.len();
+                anyhow::anyhow!("--cpu-affinity: core {} doesn't exist, there are {}", id, count)
+            })
+        })
+        .collect::<Result<_>>()
+        .map(Some)
+}
+
+// Pins the calling thread to `core`, warning once if the OS won't allow it
+fn pin_thread(core: core_affinity::CoreId) {
+    static WARNED: AtomicBool = AtomicBool::new(false);
+    if !core_affinity::set_for_current(core) && !WARNED.swap(true, Ordering::Relaxed) {
+        warn!("couldn't pin threads to CPU core {}, they run unpinned", core.id);
+    }
+}
+
+// Exit codes CI can tell apart, with what each means
+fn exit_status(error: &anyhow::Error) -> (i32, &'static str) {
+    if error.downcast_ref::<ThresholdError>().is_some() {
+        (2, "the run finished but missed a threshold")
+    } else if error.downcast_ref::<RequestsFailed>().is_some() {
+        (3, "more requests failed than the run tolerates")
+    } else if error.downcast_ref::<VerifyError>().is_some() {
+        (4, "the record counts didn't match what the run wrote")
+    } else {
+        (1, "the run couldn't connect, set up or finish")
+    }
+}
+
+fn main() {
+    let cli = Cli::parse();
+    init_logging(cli.verbose);
+    if let Err(error) = run(cli) {
+        let (code, summary) = exit_status(&error);
+        eprintln!("Error: {:?}", error);
+        eprintln!("Exit code {}: {}", code, summary);
+        std::process::exit(code);
+    }
+}
+
+fn run(cli: Cli) -> Result<()> {
+    match cli.command {
+        Commands::Bench { options, config } => {
+            let options = load_options(options, config)?;
+            runtime(&options)?.block_on(async {
+                serve_metrics(options.metrics_port)?;
+                bench(options).await
+            })
+        }
+        Commands::Ramp {
+            options,
+            config,
+            step_duration,
+            max_concurrency,
+        } => {
+            let options = load_options(options, config)?;
+            let step = Duration::from_secs(step_duration);
+            runtime(&options)?.block_on(async {
+                serve_metrics(options.metrics_port)?;
+                ramp(options, step, max_concurrency).await
+            })
+        }
+        Commands::Stress {
+            options,
+            config,
+            step_duration,
+            max_concurrency,
+            max_error_rate,
+        } => {
+            let options = load_options(options, config)?;
+            let step = Duration::from_secs(step_duration);
+            runtime(&options)?.block_on(async {
+                serve_metrics(options.metrics_port)?;
+                stress(options, step, max_concurrency, max_error_rate).await
+            })
+        }
+        Commands::BenchVector {
+            options,
+            config,
+            recall,
+        } => {
+            let options = load_options(options, config)?;
+            runtime(&options)?.block_on(async {
+                serve_metrics(options.metrics_port)?;
+                bench_vector(options, recall).await
+            })
+        }
+        Commands::Replay {
+            file,
+            options,
+            config,
+        } => {
+            let options = load_options(options, config)?;
+            runtime(&options)?.block_on(async {
+                serve_metrics(options.metrics_port)?;
+                replay(options, file).await
+            })
+        }
+        Commands::ValidateConfig { options, config } => {
+            validate_config(load_options(options, config)?)
+        }
+        Commands::Diff {
+            baseline,
+            current,
+            threshold,
+        } => diff(&baseline, &current, threshold),
+        Commands::Compare { reports } => compare(&reports),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::VectorMetric;
+
+    // Writes `contents` to a file of its own under the temp dir
+    fn temp_file(name: &str, contents: &str) -> PathBuf {
+        let name = format!("helix-bench-{}-{}", std::process::id(), name);
+        let path = std::env::temp_dir().join(name);
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn parse_replay_reads_each_operation_and_skips_comments() {
+        let path = temp_file(
+            "trace",
+            "# a trace\ncreate a {\"x\": 1}\n\nREAD a\n  update a  some data \ndelete b\n",
+        );
+        let ops = parse_replay(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let names: Vec<_> = ops.iter().map(ReplayOp::name).collect();
+        assert_eq!(names, ["create", "read", "update", "delete"]);
+        let created = r#"{"x": 1}"#;
+        assert!(matches!(&ops[0], ReplayOp::Create { id, data } if id == "a" && data == created));
+        assert!(matches!(&ops[2], ReplayOp::Update { data, .. } if data == "some data"));
+        assert!(matches!(&ops[3], ReplayOp::Delete { id } if id == "b"));
+    }
+
+    #[test]
+    fn parse_replay_names_the_line_it_rejects() {
+        for (trace, reason) in [
+            ("read a\ncreate a", "2: missing data"),
+            ("delete a extra", "1: unexpected data"),
+            ("insert a data", "1: unknown operation"),
+            ("read", "1: expected an operation and an id"),
+        ] {
+            let path = temp_file("bad-trace", trace);
+            let error = parse_replay(&path).err().unwrap().to_string();
+            std::fs::remove_file(&path).unwrap();
+            assert!(error.contains(reason), "{}", error);
+        }
+        assert!(parse_replay(Path::new("/nonexistent/trace")).is_err());
+    }
+
+    // A JSON report of `database` running each operation at the throughput and p99 given
+    fn report(name: &str, database: &str, results: &[(&str, f64, Option<f64>)]) -> PathBuf {
+        let results: Vec<serde_json::Value> = results
+            .iter()
+            .map(|&(operation, throughput, p99)| {
+                let latency = p99.map(|p99| {
+                    serde_json::json!({
+                        "mean": p99 / 2.0, "p50": p99 / 4.0, "p95": p99 / 2.0, "p99": p99,
+                        "max": p99, "std_dev": 0.0, "cv": 0.0
+                    })
+                });
+                serde_json::json!({
+                    "operation": operation, "total_time": 1.0, "avg_time": 1.0 / throughput,
+                    "throughput": throughput, "latency": latency, "completed": 1, "errors": 0
+                })
+            })
+            .collect();
+        let report = serde_json::json!({"database": database, "results": results});
+        temp_file(name, &report.to_string())
+    }
+
+    #[test]
+    fn percent_change_is_relative_to_before() {
+        assert_eq!(percent_change(200.0, 250.0), Some(25.0));
+        assert_eq!(percent_change(200.0, 100.0), Some(-50.0));
+        assert_eq!(percent_change(0.0, 100.0), None);
+    }
+
+    #[test]
+    fn diff_fails_only_on_changes_beyond_the_threshold() {
+        let baseline = report("diff-baseline", "helixdb", &[("Create", 1000.0, Some(0.002))]);
+        let close = report("diff-close", "helixdb", &[("Create", 950.0, Some(0.0021))]);
+        let slower = report("diff-slower", "helixdb", &[("Create", 800.0, Some(0.002))]);
+        let later = report("diff-later", "helixdb", &[("Create", 1000.0, Some(0.003))]);
+        let results = [&close, &slower, &later].map(|current| diff(&baseline, current, 10.0));
+        for path in [&baseline, &close, &slower, &later] {
+            std::fs::remove_file(path).unwrap();
+        }
+        let [close, slower, later] = results;
+        assert!(close.is_ok());
+        assert!(slower.unwrap_err().is::<ThresholdError>());
+        assert!(later.unwrap_err().is::<ThresholdError>());
+    }
+
+    #[test]
+    fn by_operation_averages_iterations_in_order_of_first_appearance() {
+        let path = report(
+            "compare-iterations",
+            "sqlite",
+            &[("Read", 100.0, Some(0.004)), ("Create", 50.0, None), ("Read", 300.0, Some(0.008))],
+        );
+        let summaries = SavedReport::load(&path).unwrap().by_operation();
+        std::fs::remove_file(&path).unwrap();
+        let names: Vec<&str> = summaries.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, ["Read", "Create"]);
+        let read = &summaries[0];
+        assert_eq!(read.throughput, 200.0);
+        assert_eq!((read.p50, read.p95, read.p99), (Some(1.5), Some(3.0), Some(6.0)));
+        assert_eq!((summaries[1].throughput, summaries[1].p99), (50.0, None));
+    }
+
+    #[test]
+    fn format_best_marks_the_best_cell_without_color() {
+        assert_eq!(format_best("1.50".to_string(), 6, true, false), "1.50* ");
+        assert_eq!(format_best("1.50".to_string(), 6, false, false), "1.50  ");
+        assert_eq!(format_best("1.50".to_string(), 6, true, true), "\x1b[1;32m1.50  \x1b[0m");
+    }
+
+    #[test]
+    fn compare_needs_every_report_to_load() {
+        let helixdb = report("compare-helixdb", "helixdb", &[("Create", 1000.0, Some(0.002))]);
+        let sqlite = report("compare-sqlite", "sqlite", &[("Read", 500.0, None)]);
+        let missing = std::env::temp_dir().join("helix-bench-compare-missing.json");
+        let loaded = compare(&[helixdb.clone(), sqlite.clone()]);
+        let partial = compare(&[helixdb.clone(), missing]);
+        for path in [&helixdb, &sqlite] {
+            std::fs::remove_file(path).unwrap();
+        }
+        assert!(loaded.is_ok());
+        assert!(partial.unwrap_err().to_string().contains("compare-missing"));
+    }
+
+    #[test]
+    fn a_report_with_no_completed_requests_loads_back() {
+        let results: Vec<OperationResult> = [Duration::from_secs(1), Duration::ZERO]
+            .into_iter()
+            .map(|total_time| {
+                let (avg_time, throughput) = OperationResult::rates(0, total_time);
+                OperationResult {
+                    operation: "Read".to_string(),
+                    total_time,
+                    avg_time,
+                    throughput,
+                    mb_per_sec: None,
+                    latency: None,
+                    completed: 0,
+                    errors: 5,
+                    error_breakdown: Default::default(),
+                    truncated: false,
+                    timeline: Default::default(),
+                    histogram: Default::default(),
+                }
+            })
+            .collect();
+        let report = serde_json::json!({"database": "helixdb", "results": results});
+        let path = temp_file("no-completions", &report.to_string());
+        let loaded = SavedReport::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        let summaries = loaded.unwrap().by_operation();
+        assert_eq!((summaries[0].throughput, summaries[0].p99), (0.0, None));
+    }
+
+    #[test]
+    fn runs_reject_the_conflicts_validate_config_lists() {
+        let runner = || Runner::new(1, None).with_quiet(true);
+        let zero = BenchConfig { concurrency: Some(0), ..Default::default() };
+        let metric = BenchConfig { metric: Some(VectorMetric::Euclidean), ..Default::default() };
+        for options in [&zero, &metric] {
+            assert!(!conflicts(options, Database::HelixDB).is_empty());
+            assert!(resolve_benchmark(Database::HelixDB, options, runner()).is_err());
+        }
+        assert!(resolve_benchmark(Database::Weaviate, &metric, runner()).is_ok());
+    }
+
+    #[test]
+    fn exit_status_tells_each_kind_of_failure_apart() {
+        let codes = [
+            anyhow::anyhow!("connection refused"),
+            ThresholdError("missed".to_string()).into(),
+            anyhow::Error::new(std::io::Error::other("refused")).context(RequestsFailed::FailFast),
+            VerifyError("counts differ".to_string()).into(),
+        ]
+        .map(|error| exit_status(&error).0);
+        assert_eq!(codes, [1, 2, 3, 4]);
+    }
+
+    #[tokio::test]
+    async fn operations_on_tracked_records_need_some_first() {
+        let server = wiremock::MockServer::start().await;
+        let options = BenchConfig { endpoint: Some(server.uri()), ..Default::default() };
+        let runner = Runner::new(1, None).with_quiet(true);
+        let benchmark = resolve_benchmark(Database::HelixDB, &options, runner.clone()).unwrap();
+        let (_engine, mut client) = engines::connect(&benchmark).await.unwrap();
+        for operation in ["read", "Delete", "batch_read", "read_by_data"] {
+            let error = run_benchmark(&mut *client, &runner, operation, 1).await.err().unwrap();
+            assert!(error.to_string().starts_with("No records"), "{}", error);
+        }
+    }
+}
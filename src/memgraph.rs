@@ -0,0 +1,371 @@
+use crate::cypher;
+use crate::runner::Runner;
+use crate::types::{
+    Benchmark, BenchmarkClient, BenchmarkEngine, Projection, Scan, Workload, CLEANUP_BATCH,
+};
+use crate::utils::{parse_uuids, upsert_key};
+use anyhow::Result;
+use async_trait::async_trait;
+use neo4rs::{query, ConfigBuilder, Graph, Node, Query};
+use uuid::Uuid;
+
+const CREATE_DATA_INDEX: &str = "CREATE INDEX ON :Record(data)";
+const DROP_DATA_INDEX: &str = "DROP INDEX ON :Record(data)";
+
+pub struct MemgraphClient {
+    graph: Graph,
+    runner: Runner,
+    workload: Workload,
+    ids: Vec<Uuid>,
+}
+
+impl MemgraphClient {
+    pub fn new(graph: Graph, runner: Runner, workload: Workload) -> Self {
+        Self {
+            graph,
+            runner,
+            workload,
+            ids: Vec::new(),
+        }
+    }
+
+    // `statement` against the `--dataset-name` label
+    fn query(&self, statement: &str) -> Query {
+        query(&cypher::with_label(statement, self.workload.dataset_name.as_deref()))
+    }
+
+    // Runs a query and drains its rows, returning how many there were
+    async fn fetch(&self, q: Query) -> Result<usize> {
+        let mut stream = self.graph.execute(q).await?;
+        let mut rows = 0;
+        while stream.next().await?.is_some() {
+            rows += 1;
+        }
+        Ok(rows)
+    }
+
+    async fn fetch_count(&self, statement: &str) -> Result<usize> {
+        let mut stream = self.graph.execute(self.query(statement)).await?;
+        let count = match stream.next().await? {
+            Some(row) => row.get::<i64>("count")? as usize,
+            None => 0,
+        };
+        Ok(count)
+    }
+}
+
+#[async_trait]
+impl BenchmarkClient for MemgraphClient {
+    async fn startup(&self) -> Result<()> {
+        self.graph.run(self.query(cypher::PING)).await?;
+        match self.workload.data_index {
+            Some(true) => self.graph.run(self.query(CREATE_DATA_INDEX)).await?,
+            Some(false) => self.graph.run(self.query(DROP_DATA_INDEX)).await?,
+            None => {}
+        }
+        Ok(())
+    }
+
+    async fn create_records(&mut self, count: usize) -> Result<()> {
+        let new_ids = self.workload.new_ids();
+        let this = &*self;
+        let ids = this
+            .runner
+            .run("Create", count, |i| async move {
+                let id = new_ids.id(i);
+                let q = this.query(cypher::CREATE_RECORD)
+                    .param("id", id.to_string())
+                    .param("data", this.workload.value(i));
+                this.graph.run(q).await?;
+                Ok(id)
+            })
+            .await?;
+        self.ids.extend(ids);
+        Ok(())
+    }
+
+    async fn read_records(&self) -> Result<()> {
+        let statement = match self.workload.read_projection {
+            Projection::Id => cypher::READ_RECORD_ID,
+            _ => cypher::READ_RECORD,
+        };
+        let keys = self.workload.read_keys(self.ids.len())?;
+        let keys = &keys;
+        self.runner
+            .run("Read", keys.len(), |i| async move {
+                let id = self.ids[keys[i % keys.len()]];
+                self.fetch(self.query(statement).param("id", id.to_string()))
+                    .await?;
+                Ok(())
+            })
+            .await?;
+        Ok(())
+    }
+
+    async fn update_records(&self) -> Result<()> {
+        let order = &self.workload.working_set(self.ids.len());
+        self.runner
+            .run("Update", self.ids.len(), |i| async move {
+                let id = self.ids[order[i % order.len()]];
+                let q = self.query(cypher::update_record(self.workload.update_mode))
+                    .param("id", id.to_string())
+                    .param("data", self.workload.updated_value(i));
+                self.graph.run(q).await?;
+                Ok(())
+            })
+            .await?;
+        Ok(())
+    }
+
+    async fn upsert_records(&mut self, count: usize) -> Result<()> {
+        let new_ids = self.workload.new_ids();
+        let this = &*self;
+        let ids = this
+            .runner
+            .run("Upsert", count, |i| async move {
+                let existing = upsert_key(&this.ids, i);
+                let id = existing.unwrap_or_else(|| new_ids.id(i));
+                let q = this.query(cypher::UPSERT_RECORD)
+                    .param("id", id.to_string())
+                    .param("data", this.workload.value(i));
+                this.graph.run(q).await?;
+                Ok(existing.is_none().then_some(id))
+            })
+            .await?;
+        self.ids.extend(ids.into_iter().flatten());
+        Ok(())
+    }
+
+    async fn delete_records(&self) -> Result<()> {
+        let order = &self.workload.order(self.ids.len());
+        self.runner
+            .run("Delete", self.ids.len(), |i| async move {
+                let id = self.ids[order[i % order.len()]];
+                self.graph
+                    .run(self.query(cypher::DELETE_RECORD).param("id", id.to_string()))
+                    .await?;
+                Ok(())
+            })
+            .await?;
+        Ok(())
+    }
+
+    async fn cleanup(&mut self) -> Result<()> {
+        for batch in self.ids.chunks(CLEANUP_BATCH) {
+            let ids: Vec<String> = batch.iter().map(Uuid::to_string).collect();
+            self.graph
+                .run(self.query(cypher::DELETE_RECORDS).param("ids", ids))
+                .await?;
+        }
+        self.ids.clear();
+        Ok(())
+    }
+
+    async fn scan_records(&self) -> Result<()> {
+        let scan = self.workload.scan(&self.ids);
+        self.runner.scan(scan, |scan| async move { self.scan(&scan).await }).await?;
+        Ok(())
+    }
+
+    async fn read_records_by_data(&self) -> Result<()> {
+        let keys = self.workload.read_keys(self.ids.len())?;
+        let keys = &keys;
+        self.runner
+            .run("Read by data", keys.len(), |i| async move {
+                let data = self.workload.value(keys[i % keys.len()]);
+                let q = self.query(cypher::READ_RECORDS_BY_DATA).param("data", data);
+                let mut stream = self.graph.execute(q).await?;
+                let matches = match stream.next().await? {
+                    Some(row) => row.get::<i64>("count")?,
+                    None => 0,
+                };
+                if matches == 0 && self.workload.verifies(i) {
+                    return Err(anyhow::anyhow!("No record holds the data {:?}", data));
+                }
+                Ok(())
+            })
+            .await?;
+        Ok(())
+    }
+
+    async fn transaction(&mut self, _count: usize) -> Result<()> {
+        Err(anyhow::anyhow!("txn isn't supported for Memgraph"))
+    }
+
+    async fn batch_read(&self) -> Result<()> {
+        Err(anyhow::anyhow!("batch_read isn't supported for Memgraph"))
+    }
+
+    async fn count_records(&self) -> Result<usize> {
+        self.fetch_count(cypher::COUNT_RECORDS).await
+    }
+
+    fn tracked_ids(&self) -> usize {
+        self.ids.len()
+    }
+
+    fn load_ids(&mut self, ids: &[String]) -> Result<()> {
+        self.ids.extend(parse_uuids(ids)?);
+        Ok(())
+    }
+
+    async fn reset(&mut self, purge: bool) -> Result<()> {
+        if purge {
+            self.graph.run(self.query(cypher::DELETE_ALL_RECORDS)).await?;
+        }
+        self.ids.clear();
+        Ok(())
+    }
+
+    async fn create_record(&self, data: &str) -> Result<String> {
+        let id = self.workload.new_ids().id(0).to_string();
+        let q = self.query(cypher::CREATE_RECORD)
+            .param("id", id.clone())
+            .param("data", data);
+        self.graph.run(q).await?;
+        Ok(id)
+    }
+
+    async fn read_record(&self, key: &str) -> Result<()> {
+        let statement = match self.workload.read_projection {
+            Projection::Id => cypher::READ_RECORD_ID,
+            _ => cypher::READ_RECORD,
+        };
+        self.fetch(self.query(statement).param("id", key)).await?;
+        Ok(())
+    }
+
+    async fn update_record(&self, key: &str, data: &str) -> Result<()> {
+        let q = self.query(cypher::update_record(self.workload.update_mode))
+            .param("id", key)
+            .param("data", data);
+        self.graph.run(q).await?;
+        Ok(())
+    }
+
+    async fn delete_record(&self, key: &str) -> Result<()> {
+        self.graph
+            .run(self.query(cypher::DELETE_RECORD).param("id", key))
+            .await?;
+        Ok(())
+    }
+
+    async fn create_vectors(&self, _count: usize) -> Result<()> {
+        Err(anyhow::anyhow!("vectors aren't supported for Memgraph"))
+    }
+
+    async fn search_vectors(&self, _count: usize) -> Result<()> {
+        Err(anyhow::anyhow!("vectors aren't supported for Memgraph"))
+    }
+
+    async fn dry_run(&mut self) -> Vec<(String, Result<()>)> {
+        let id = Uuid::new_v4().to_string();
+        let mut checks = Vec::new();
+
+        let q = self.query(cypher::CREATE_RECORD)
+            .param("id", id.clone())
+            .param("data", "test_value");
+        let created = self.graph.run(q).await.map_err(Into::into);
+        let create_ok = created.is_ok();
+        checks.push(("create".to_string(), created));
+
+        let read = if create_ok {
+            let q = self.query(cypher::READ_RECORD_DATA).param("id", id.clone());
+            let data = match self.graph.execute(q).await {
+                Ok(mut stream) => stream
+                    .next()
+                    .await
+                    .map(|row| row.and_then(|row| row.get::<String>("data").ok())),
+                Err(e) => Err(e),
+            };
+            match data {
+                Ok(Some(data)) if data == "test_value" => Ok(()),
+                Ok(data) => Err(anyhow::anyhow!("unexpected read response: {:?}", data)),
+                Err(e) => Err(e.into()),
+            }
+        } else {
+            Err(anyhow::anyhow!("skipped, create failed"))
+        };
+        checks.push(("read".to_string(), read));
+
+        let deleted = if create_ok {
+            let q = self.query(cypher::DELETE_RECORD).param("id", id);
+            self.graph.run(q).await.map_err(Into::into)
+        } else {
+            Err(anyhow::anyhow!("skipped, create failed"))
+        };
+        checks.push(("delete".to_string(), deleted));
+
+        checks
+    }
+}
+
+impl MemgraphClient {
+    async fn scan(&self, scan: &Scan) -> Result<usize> {
+        let limit = scan.limit.unwrap_or(100) as i64;
+        let offset = scan.start.unwrap_or(0) as i64;
+        match scan.projection()? {
+            Projection::Count => self.fetch_count(cypher::COUNT_RECORDS).await,
+            projection => {
+                let statement = match projection {
+                    Projection::Id => cypher::SCAN_RECORD_IDS,
+                    _ => cypher::SCAN_RECORDS,
+                };
+                let q = self.query(statement)
+                    .param("limit", limit)
+                    .param("offset", offset);
+                if !scan.checking() {
+                    return self.fetch(q).await;
+                }
+                let mut stream = self.graph.execute(q).await?;
+                let mut rows = 0;
+                while let Some(row) = stream.next().await? {
+                    rows += 1;
+                    let id = match projection {
+                        Projection::Id => row.get::<String>("n.id")?,
+                        _ => row.get::<Node>("n")?.get::<String>("id")?,
+                    };
+                    scan.saw([id]);
+                }
+                Ok(rows)
+            }
+        }
+    }
+}
+
+// Engine for Memgraph, which speaks the same Cypher as Neo4j but over Bolt. Its default
+// install has authentication disabled, so the credentials are left blank.
+pub struct MemgraphEngine {
+    endpoint: String,
+    runner: Runner,
+    workload: Workload,
+}
+
+#[async_trait]
+impl BenchmarkEngine for MemgraphEngine {
+    async fn setup(options: &Benchmark) -> Result<Self> {
+        let endpoint = options
+            .endpoint
+            .as_deref()
+            .unwrap_or("bolt://localhost:7687")
+            .to_string();
+        Ok(Self {
+            endpoint,
+            runner: options.runner.clone(),
+            workload: options.workload.clone(),
+        })
+    }
+
+    async fn create_client(&self) -> Result<Box<dyn BenchmarkClient>> {
+        let config = ConfigBuilder::default()
+            .uri(self.endpoint.as_str())
+            .user("")
+            .password("")
+            .db("memgraph")
+            .build()?;
+        let graph = Graph::connect(config).await?;
+        let client = MemgraphClient::new(graph, self.runner.clone(), self.workload.clone());
+        client.startup().await?;
+        Ok(Box::new(client))
+    }
+}
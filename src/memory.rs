@@ -0,0 +1,147 @@
+use anyhow::Result;
+use reqwest::Client;
+use serde::Serialize;
+use serde_json::Value;
+use std::time::{Duration, Instant};
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+// Prometheus metric names read from a text exposition
+const RSS_METRIC: &str = "process_resident_memory_bytes";
+const HEAP_METRIC: &str = "process_heap_bytes";
+
+// Server memory at one point of the run, in bytes
+#[derive(Clone, Copy, Serialize)]
+pub struct MemorySample {
+    /// Seconds since sampling started
+    pub elapsed: f64,
+    pub rss: Option<u64>,
+    pub heap: Option<u64>,
+}
+
+// Range of one memory figure over the run, in bytes
+#[derive(Clone, Copy, Serialize)]
+pub struct MemorySummary {
+    pub min: u64,
+    pub max: u64,
+    pub last: u64,
+}
+
+impl MemorySummary {
+    /// Returns `None` when no sample had the figure
+    fn from_values(values: impl Iterator<Item = u64>) -> Option<Self> {
+        values.fold(None, |summary, value| {
+            Some(match summary {
+                None => Self {
+                    min: value,
+                    max: value,
+                    last: value,
+                },
+                Some(summary) => Self {
+                    min: summary.min.min(value),
+                    max: summary.max.max(value),
+                    last: value,
+                },
+            })
+        })
+    }
+}
+
+#[derive(Clone, Serialize)]
+pub struct MemoryReport {
+    pub rss: Option<MemorySummary>,
+    pub heap: Option<MemorySummary>,
+    pub samples: Vec<MemorySample>,
+}
+
+impl MemoryReport {
+    fn from_samples(samples: Vec<MemorySample>) -> Self {
+        Self {
+            rss: MemorySummary::from_values(samples.iter().filter_map(|s| s.rss)),
+            heap: MemorySummary::from_values(samples.iter().filter_map(|s| s.heap)),
+            samples,
+        }
+    }
+}
+
+// Polls a server's stats endpoint once a second in the background for as long as the run
+// lasts. If the first poll fails or has no memory figures in it, sampling stops there with
+// a warning and the run carries on without it.
+pub struct MemorySampler {
+    stop: oneshot::Sender<()>,
+    task: JoinHandle<Vec<MemorySample>>,
+}
+
+impl MemorySampler {
+    pub fn start(client: Client, url: String) -> Self {
+        let (stop, mut stopped) = oneshot::channel();
+        let task = tokio::spawn(async move {
+            let start = Instant::now();
+            let mut samples = Vec::new();
+            let mut interval = tokio::time::interval(SAMPLE_INTERVAL);
+            loop {
+                tokio::select! {
+                    _ = &mut stopped => break,
+                    _ = interval.tick() => {}
+                }
+                match sample(&client, &url).await {
+                    Ok((rss, heap)) => samples.push(MemorySample {
+                        elapsed: start.elapsed().as_secs_f64(),
+                        rss,
+                        heap,
+                    }),
+                    Err(e) if samples.is_empty() => {
+                        warn!(%url, "not sampling memory: {}", e);
+                        break;
+                    }
+                    Err(e) => debug!(%url, "memory sample failed: {}", e),
+                }
+            }
+            samples
+        });
+        Self { stop, task }
+    }
+
+    /// Stops sampling, returning `None` when nothing was sampled
+    pub async fn finish(self) -> Option<MemoryReport> {
+        let _ = self.stop.send(());
+        let samples = self.task.await.ok()?;
+        match samples.is_empty() {
+            true => None,
+            false => Some(MemoryReport::from_samples(samples)),
+        }
+    }
+}
+
+// Reads RSS and heap from either a JSON stats object or a Prometheus text exposition
+async fn sample(client: &Client, url: &str) -> Result<(Option<u64>, Option<u64>)> {
+    let response = client.get(url).send().await?;
+    if !response.status().is_success() {
+        return Err(anyhow::anyhow!("Request failed: {}", response.status()));
+    }
+    let body = response.text().await?;
+    let (rss, heap) = match serde_json::from_str::<Value>(&body) {
+        Ok(stats) => {
+            let field = |names: &[&str]| names.iter().find_map(|name| stats[*name].as_u64());
+            (field(&["rss", "rss_bytes"]), field(&["heap", "heap_bytes"]))
+        }
+        Err(_) => (metric(&body, RSS_METRIC), metric(&body, HEAP_METRIC)),
+    };
+    if rss.is_none() && heap.is_none() {
+        return Err(anyhow::anyhow!("no rss or heap figures in the response"));
+    }
+    Ok((rss, heap))
+}
+
+fn metric(exposition: &str, name: &str) -> Option<u64> {
+    exposition.lines().find_map(|line| {
+        let mut parts = line.split_whitespace();
+        match parts.next() {
+            Some(metric) if metric == name => parts.next()?.parse::<f64>().ok().map(|v| v as u64),
+            _ => None,
+        }
+    })
+}
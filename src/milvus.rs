@@ -0,0 +1,445 @@
+use crate::runner::Runner;
+use crate::types::{
+    Benchmark, BenchmarkClient, BenchmarkEngine, Projection, Scan, StatusError, VectorMetric,
+    Workload, CLEANUP_BATCH,
+};
+use crate::utils::{
+    generate_random_vector_f32, http_client_builder, nearest_neighbours, parse_uuids, upsert_key,
+};
+use anyhow::Result;
+use async_trait::async_trait;
+use reqwest::Client;
+use serde_json::{json, Value};
+use std::sync::Mutex;
+use tracing::{debug, trace};
+use uuid::Uuid;
+
+// Every Milvus collection needs a vector field, so records carry a constant two-component one
+// next to their data, which the collection's dynamic field holds
+const RECORD_COLLECTION: &str = "helix_records";
+const RECORD_VECTOR: [f32; 2] = [1.0, 0.0];
+
+// A collection's metric and dimension are fixed when it's created, so each combination gets a
+// collection of its own
+fn vector_collection(metric: VectorMetric, dims: usize) -> String {
+    format!("helix_vectors_{}_{}", metric.as_str(), dims)
+}
+
+fn metric_type(metric: VectorMetric) -> &'static str {
+    match metric {
+        VectorMetric::Cosine => "COSINE",
+        VectorMetric::Euclidean => "L2",
+        VectorMetric::Dot => "IP",
+    }
+}
+
+// Ids in a boolean expression filter
+fn id_filter(ids: &[String]) -> String {
+    format!("id in {}", json!(ids))
+}
+
+pub struct MilvusClient {
+    endpoint: String,
+    client: Client,
+    runner: Runner,
+    workload: Workload,
+    ids: Vec<Uuid>,
+    // Vectors created, with their ids, when the workload tracks them for recall
+    vectors: Mutex<Vec<(Uuid, Vec<f32>)>>,
+}
+
+impl MilvusClient {
+    pub fn new(endpoint: String, client: Client, runner: Runner, workload: Workload) -> Self {
+        Self {
+            endpoint,
+            client,
+            runner,
+            workload,
+            ids: Vec::new(),
+            vectors: Mutex::new(Vec::new()),
+        }
+    }
+
+    // Milvus answers 200 to failed requests too, with the failure in the body's `code`
+    async fn send(&self, path: &str, body: Value) -> Result<Value> {
+        let url = format!("{}/v2/vectordb{}", self.endpoint, path);
+        trace!(%path, %body, "request");
+        let response = self.client.post(&url).json(&body).send().await?;
+        debug!(%path, status = %response.status(), "response");
+        if !response.status().is_success() {
+            return Err(StatusError::new(response.status(), "").into());
+        }
+        let res = response.json::<Value>().await?;
+        trace!(response = %res, "response body");
+        match res["code"].as_i64() {
+            Some(0) => Ok(res["data"].clone()),
+            _ => Err(anyhow::anyhow!("Milvus error: {}", res["message"])),
+        }
+    }
+
+    async fn ensure_collection(&self, name: &str, dims: usize, metric: &str) -> Result<()> {
+        let has = self.send("/collections/has", json!({"collectionName": name})).await?;
+        if has["has"].as_bool() == Some(true) {
+            return Ok(());
+        }
+        // Strong consistency so a read sees the write just before it, as with the other engines
+        let collection = json!({
+            "collectionName": name,
+            "dimension": dims,
+            "metricType": metric,
+            "idType": "VarChar",
+            "params": {"max_length": 36, "consistencyLevel": "Strong"}
+        });
+        self.send("/collections/create", collection).await?;
+        Ok(())
+    }
+
+    async fn insert(&self, id: Uuid, data: &str) -> Result<()> {
+        let entity = json!({"id": id.to_string(), "vector": RECORD_VECTOR, "data": data});
+        let body = json!({"collectionName": RECORD_COLLECTION, "data": [entity]});
+        self.send("/entities/insert", body).await?;
+        Ok(())
+    }
+
+    // Milvus has no partial update, so an update writes the whole entity again
+    async fn upsert(&self, id: &str, data: &str) -> Result<()> {
+        let entity = json!({"id": id, "vector": RECORD_VECTOR, "data": data});
+        let body = json!({"collectionName": RECORD_COLLECTION, "data": [entity]});
+        self.send("/entities/upsert", body).await?;
+        Ok(())
+    }
+
+    async fn select(&self, id: &str, projection: Projection) -> Result<Value> {
+        let fields = match projection {
+            Projection::Id => json!(["id"]),
+            _ => json!(["data"]),
+        };
+        let body = json!({"collectionName": RECORD_COLLECTION, "id": [id], "outputFields": fields});
+        self.send("/entities/get", body).await
+    }
+
+    async fn remove(&self, ids: &[String]) -> Result<()> {
+        let body = json!({"collectionName": RECORD_COLLECTION, "filter": id_filter(ids)});
+        self.send("/entities/delete", body).await?;
+        Ok(())
+    }
+
+    // Ids of the nearest `k` vectors to `vector`, nearest first
+    async fn search(&self, vector: &[f32]) -> Result<Vec<String>> {
+        let body = json!({
+            "collectionName": vector_collection(self.workload.metric, self.workload.dims),
+            "data": [vector],
+            "annsField": "vector",
+            "limit": self.workload.k,
+            "outputFields": ["id"]
+        });
+        let res = self.send("/entities/search", body).await?;
+        Ok(res
+            .as_array()
+            .map(|hits| {
+                hits.iter()
+                    .filter_map(|hit| hit["id"].as_str().map(str::to_string))
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+
+    // Milvus caps offset + limit at 16384 rows, so larger scans need `--scan-page-size`
+    async fn scan(&self, scan: &Scan) -> Result<usize> {
+        let fields = match scan.projection()? {
+            Projection::Count => return self.count_records().await,
+            Projection::Id => json!(["id"]),
+            Projection::Full => json!(["id", "data"]),
+        };
+        let body = json!({
+            "collectionName": RECORD_COLLECTION,
+            "filter": "",
+            "limit": scan.limit.unwrap_or(100),
+            "offset": scan.start.unwrap_or(0),
+            "outputFields": fields
+        });
+        let res = self.send("/entities/query", body).await?;
+        let rows = res.as_array().map(Vec::as_slice).unwrap_or_default();
+        scan.saw(rows.iter().filter_map(|row| row["id"].as_str().map(str::to_string)));
+        Ok(rows.len())
+    }
+}
+
+#[async_trait]
+impl BenchmarkClient for MilvusClient {
+    // Creating the collections when they're missing doubles as the connectivity check
+    async fn startup(&self) -> Result<()> {
+        self.ensure_collection(RECORD_COLLECTION, RECORD_VECTOR.len(), "L2").await?;
+        let (metric, dims) = (self.workload.metric, self.workload.dims);
+        self.ensure_collection(&vector_collection(metric, dims), dims, metric_type(metric)).await
+    }
+
+    async fn create_records(&mut self, count: usize) -> Result<()> {
+        let new_ids = self.workload.new_ids();
+        let this = &*self;
+        let ids = this
+            .runner
+            .run("Create", count, |i| async move {
+                let id = new_ids.id(i);
+                this.insert(id, this.workload.value(i)).await?;
+                Ok(id)
+            })
+            .await?;
+        self.ids.extend(ids);
+        Ok(())
+    }
+
+    async fn read_records(&self) -> Result<()> {
+        let keys = self.workload.read_keys(self.ids.len())?;
+        let keys = &keys;
+        self.runner
+            .run("Read", keys.len(), |i| async move {
+                let key = keys[i % keys.len()];
+                let projection = self.workload.read_projection;
+                let res = self.select(&self.ids[key].to_string(), projection).await?;
+                let expected = self.workload.checked_value(i, key);
+                if let (Projection::Full, Some(expected)) = (projection, expected) {
+                    if res[0]["data"] != expected {
+                        let id = &self.ids[key];
+                        return Err(anyhow::anyhow!("Read of {} returned the wrong data", id));
+                    }
+                }
+                Ok(())
+            })
+            .await?;
+        Ok(())
+    }
+
+    async fn update_records(&self) -> Result<()> {
+        let order = &self.workload.working_set(self.ids.len());
+        self.runner
+            .run("Update", self.ids.len(), |i| async move {
+                let id = self.ids[order[i % order.len()]];
+                self.upsert(&id.to_string(), self.workload.updated_value(i)).await
+            })
+            .await?;
+        Ok(())
+    }
+
+    async fn upsert_records(&mut self, count: usize) -> Result<()> {
+        let new_ids = self.workload.new_ids();
+        let this = &*self;
+        let ids = this
+            .runner
+            .run("Upsert", count, |i| async move {
+                let existing = upsert_key(&this.ids, i);
+                let id = existing.unwrap_or_else(|| new_ids.id(i));
+                this.upsert(&id.to_string(), this.workload.value(i)).await?;
+                Ok(existing.is_none().then_some(id))
+            })
+            .await?;
+        self.ids.extend(ids.into_iter().flatten());
+        Ok(())
+    }
+
+    async fn delete_records(&self) -> Result<()> {
+        let order = &self.workload.order(self.ids.len());
+        self.runner
+            .run("Delete", self.ids.len(), |i| async move {
+                self.remove(&[self.ids[order[i % order.len()]].to_string()]).await
+            })
+            .await?;
+        Ok(())
+    }
+
+    async fn cleanup(&mut self) -> Result<()> {
+        for batch in self.ids.chunks(CLEANUP_BATCH) {
+            let ids: Vec<String> = batch.iter().map(Uuid::to_string).collect();
+            self.remove(&ids).await?;
+        }
+        self.ids.clear();
+        Ok(())
+    }
+
+    async fn scan_records(&self) -> Result<()> {
+        let scan = self.workload.scan(&self.ids);
+        self.runner.scan(scan, |scan| async move { self.scan(&scan).await }).await?;
+        Ok(())
+    }
+
+    async fn read_records_by_data(&self) -> Result<()> {
+        Err(anyhow::anyhow!("read_by_data isn't supported for Milvus"))
+    }
+
+    async fn transaction(&mut self, _count: usize) -> Result<()> {
+        Err(anyhow::anyhow!("txn isn't supported for Milvus"))
+    }
+
+    async fn batch_read(&self) -> Result<()> {
+        Err(anyhow::anyhow!("batch_read isn't supported for Milvus"))
+    }
+
+    async fn count_records(&self) -> Result<usize> {
+        let body = json!({
+            "collectionName": RECORD_COLLECTION,
+            "filter": "",
+            "outputFields": ["count(*)"]
+        });
+        let res = self.send("/entities/query", body).await?;
+        res[0]["count(*)"]
+            .as_u64()
+            .map(|count| count as usize)
+            .ok_or_else(|| anyhow::anyhow!("no count in response: {}", res))
+    }
+
+    fn tracked_ids(&self) -> usize {
+        self.ids.len()
+    }
+
+    fn load_ids(&mut self, ids: &[String]) -> Result<()> {
+        self.ids.extend(parse_uuids(ids)?);
+        Ok(())
+    }
+
+    async fn reset(&mut self, purge: bool) -> Result<()> {
+        if purge {
+            return Err(anyhow::anyhow!("--purge isn't supported for Milvus"));
+        }
+        self.ids.clear();
+        self.vectors.lock().unwrap().clear();
+        Ok(())
+    }
+
+    async fn create_record(&self, data: &str) -> Result<String> {
+        let id = self.workload.new_ids().id(0);
+        self.insert(id, data).await?;
+        Ok(id.to_string())
+    }
+
+    async fn read_record(&self, key: &str) -> Result<()> {
+        self.select(key, self.workload.read_projection).await?;
+        Ok(())
+    }
+
+    async fn update_record(&self, key: &str, data: &str) -> Result<()> {
+        self.upsert(key, data).await
+    }
+
+    async fn delete_record(&self, key: &str) -> Result<()> {
+        self.remove(&[key.to_string()]).await
+    }
+
+    // Milvus float vectors are f32 whatever the precision they're sent with
+    async fn create_vectors(&self, count: usize) -> Result<()> {
+        let new_ids = self.workload.new_ids();
+        let collection = vector_collection(self.workload.metric, self.workload.dims);
+        let collection = &collection;
+        self.runner
+            .run("Create vectors", count, |i| async move {
+                let (id, vector) = (new_ids.id(i), generate_random_vector_f32(self.workload.dims));
+                let entity = json!({"id": id.to_string(), "vector": vector});
+                let body = json!({"collectionName": collection, "data": [entity]});
+                self.send("/entities/insert", body).await?;
+                self.workload.vector_created();
+                if self.workload.track_vectors {
+                    self.vectors.lock().unwrap().push((id, vector));
+                }
+                Ok(())
+            })
+            .await?;
+        Ok(())
+    }
+
+    async fn search_vectors(&self, count: usize) -> Result<()> {
+        self.runner
+            .run("Search vectors", count, |_| async move {
+                let found = self.search(&generate_random_vector_f32(self.workload.dims)).await?;
+                self.workload.check_search_results(found.len())
+            })
+            .await?;
+        Ok(())
+    }
+
+    async fn vector_recall(&self, queries: usize) -> Result<Option<f64>> {
+        let vectors = self.vectors.lock().unwrap().clone();
+        if vectors.is_empty() {
+            return Ok(None);
+        }
+        let (ids, vectors): (Vec<Uuid>, Vec<Vec<f32>>) = vectors.into_iter().unzip();
+        let k = self.workload.k.min(vectors.len());
+        let mut total = 0.0;
+        for _ in 0..queries {
+            let query = generate_random_vector_f32(self.workload.dims);
+            let exact = nearest_neighbours(self.workload.metric, &vectors, &query, k);
+            let found = self.search(&query).await?;
+            let hits = exact
+                .iter()
+                .filter(|&&i| found.contains(&ids[i].to_string()))
+                .count();
+            total += hits as f64 / k as f64;
+        }
+        Ok(Some(total / queries.max(1) as f64))
+    }
+
+    async fn dry_run(&mut self) -> Vec<(String, Result<()>)> {
+        let id = Uuid::new_v4();
+        let mut checks = Vec::new();
+
+        let created = self.insert(id, "test_value").await;
+        let create_ok = created.is_ok();
+        checks.push(("/entities/insert".to_string(), created));
+
+        let read = if create_ok {
+            self.select(&id.to_string(), Projection::Full).await.and_then(|res| {
+                if res[0]["data"] == "test_value" {
+                    Ok(())
+                } else {
+                    Err(anyhow::anyhow!("unexpected read response: {}", res))
+                }
+            })
+        } else {
+            Err(anyhow::anyhow!("skipped, create failed"))
+        };
+        checks.push(("/entities/get".to_string(), read));
+
+        let deleted = if create_ok {
+            self.remove(&[id.to_string()]).await
+        } else {
+            Err(anyhow::anyhow!("skipped, create failed"))
+        };
+        checks.push(("/entities/delete".to_string(), deleted));
+
+        checks
+    }
+}
+
+// Engine for Milvus over its v2 REST API, without authentication
+pub struct MilvusEngine {
+    endpoint: String,
+    client: Client,
+    runner: Runner,
+    workload: Workload,
+}
+
+#[async_trait]
+impl BenchmarkEngine for MilvusEngine {
+    async fn setup(options: &Benchmark) -> Result<Self> {
+        let endpoint = options
+            .endpoint
+            .as_deref()
+            .unwrap_or("http://localhost:19530")
+            .to_string();
+        Ok(Self {
+            endpoint,
+            client: http_client_builder(options)?.build()?,
+            runner: options.runner.clone(),
+            workload: options.workload.clone(),
+        })
+    }
+
+    async fn create_client(&self) -> Result<Box<dyn BenchmarkClient>> {
+        let client = MilvusClient::new(
+            self.endpoint.clone(),
+            self.client.clone(),
+            self.runner.clone(),
+            self.workload.clone(),
+        );
+        client.startup().await?;
+        Ok(Box::new(client))
+    }
+}
@@ -1,154 +1,478 @@
-use crate::types::{Benchmark, BenchmarkClient, BenchmarkEngine, Projection, Scan};
+use crate::cypher;
+use crate::runner::Runner;
+use crate::types::{
+    Benchmark, BenchmarkClient, BenchmarkEngine, Projection, Scan, StatusError, Workload,
+    CLEANUP_BATCH,
+};
+use crate::utils::{parse_uuids, upsert_key, ReconnectingClient};
 use anyhow::Result;
 use async_trait::async_trait;
-use reqwest::Client;
+use reqwest::Url;
 use serde_json::{Value, json};
+use std::borrow::Cow;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 use uuid::Uuid;
-use indicatif::{ProgressBar, ProgressStyle};
-use std::time::Duration;
+use tracing::{debug, info, trace};
+
+const CREATE_DATA_INDEX: &str = "CREATE INDEX record_data IF NOT EXISTS FOR (n:Record) ON (n.data)";
+const DROP_DATA_INDEX: &str = "DROP INDEX record_data IF EXISTS";
+const SERVER_VERSION: &str =
+    "CALL dbms.components() YIELD versions, edition RETURN versions[0] + ' ' + edition";
+const ROUTING_TABLE: &str =
+    "CALL dbms.routing.getRoutingTable({}, 'neo4j') YIELD servers RETURN servers";
+
+// Cluster members that `--neo4j-routing` sends requests to, taken from the routing table once
+// at startup. Writes go to the leader and reads round-robin over the followers, or over the
+// leader too when there are none.
+pub struct Routing {
+    writers: Vec<String>,
+    readers: Vec<String>,
+    next: AtomicUsize,
+}
+
+impl Routing {
+    // The routing table lists Bolt addresses, so each member is reached on the host it gives
+    // with the scheme and HTTP port of `endpoint`
+    fn from_table(endpoint: &str, response: &Value) -> Result<Self> {
+        let endpoint = Url::parse(endpoint)?;
+        let port = endpoint.port_or_known_default().unwrap_or(7474);
+        let servers = response["results"][0]["data"][0]["row"][0].as_array();
+        let members = |role: &str| -> Vec<String> {
+            servers
+                .into_iter()
+                .flatten()
+                .filter(|server| server["role"] == role)
+                .flat_map(|server| server["addresses"].as_array().into_iter().flatten())
+                .filter_map(Value::as_str)
+                .map(|address| {
+                    let host = address.rsplit_once(':').map_or(address, |(host, _)| host);
+                    format!("{}://{}:{}", endpoint.scheme(), host, port)
+                })
+                .collect()
+        };
+        let writers = members("WRITE");
+        if writers.is_empty() {
+            return Err(anyhow::anyhow!("The routing table has no leader: {}", response));
+        }
+        let readers = match members("READ") {
+            readers if readers.is_empty() => writers.clone(),
+            readers => readers,
+        };
+        Ok(Self {
+            writers,
+            readers,
+            next: AtomicUsize::new(0),
+        })
+    }
+
+    fn writer(&self) -> &str {
+        &self.writers[self.next.fetch_add(1, Ordering::Relaxed) % self.writers.len()]
+    }
+
+    fn reader(&self) -> &str {
+        &self.readers[self.next.fetch_add(1, Ordering::Relaxed) % self.readers.len()]
+    }
+}
 
 pub struct Neo4jClient {
     endpoint: String,
-    client: Client,
+    client: ReconnectingClient,
+    runner: Runner,
+    workload: Workload,
+    routing: Option<Arc<Routing>>,
     ids: Vec<Uuid>,
 }
 
 impl Neo4jClient {
-    pub fn new(endpoint: String) -> Self {
+    pub fn new(
+        endpoint: String,
+        client: ReconnectingClient,
+        runner: Runner,
+        workload: Workload,
+        routing: Option<Arc<Routing>>,
+    ) -> Self {
         Self {
             endpoint,
-            client: Client::new(),
+            client,
+            runner,
+            workload,
+            routing,
             ids: Vec::new(),
         }
     }
 
     async fn execute_cypher(&self, query: &str, params: Value) -> Result<Value> {
-        let url = format!("{}/db/neo4j/tx/commit", self.endpoint);
-        let body = json!({
-            "statements": [{"statement": query, "parameters": params}]
-        });
+        self.execute_statements(vec![(query, params)]).await
+    }
+
+    // Like `execute_cypher`, for a statement that only reads, so routing can send it to a
+    // follower
+    async fn read_cypher(&self, query: &str, params: Value) -> Result<Value> {
+        let server = self.routing.as_ref().map_or(self.endpoint.as_str(), |r| r.reader());
+        self.post_statements(server, vec![(query, params)]).await
+    }
+
+    async fn execute_statements(&self, statements: Vec<(&str, Value)>) -> Result<Value> {
+        let server = self.routing.as_ref().map_or(self.endpoint.as_str(), |r| r.writer());
+        self.post_statements(server, statements).await
+    }
+
+    // Runs `statements` in order in one request to the transactional endpoint of `server`,
+    // which commits them together once they have all run
+    async fn post_statements(&self, server: &str, statements: Vec<(&str, Value)>) -> Result<Value> {
+        let label = self.workload.dataset_name.as_deref();
+        let queries: Vec<Cow<str>> =
+            statements.iter().map(|(query, _)| cypher::with_label(query, label)).collect();
+        let query = queries.join("; ");
+        let url = format!("{}/db/neo4j/tx/commit", server);
+        let statements: Vec<Value> = queries
+            .iter()
+            .zip(statements)
+            .map(|(query, (_, params))| json!({"statement": query, "parameters": params}))
+            .collect();
+        let body = json!({"statements": statements});
+        trace!(%body, "request");
         let response = self
             .client
-            .post(&url)
-            .json(&body)
-            .basic_auth("neo4j", Some("neo4jtest"))
-            .send()
+            .send(|client| client.post(&url).json(&body).basic_auth("neo4j", Some("neo4jtest")))
             .await?;
+        debug!(%query, status = %response.status(), "response");
         if response.status().is_success() {
-            response.json::<Value>().await.map_err(Into::into)
+            let res = response.json::<Value>().await?;
+            trace!(response = %res, "response body");
+            Ok(res)
         } else {
-            Err(anyhow::anyhow!("Request failed: {}", response.status()))
+            Err(StatusError::new(response.status(), "").into())
         }
     }
+
+    // Parameters writing a record, with fresh values for any schema fields under `fields`
+    fn record_params(&self, id: &str, data: &str) -> Value {
+        let mut params = json!({"id": id, "data": data});
+        if let Some(fields) = self.workload.fields() {
+            params["fields"] = Value::Object(fields);
+        }
+        params
+    }
+
+    // `query` with the schema fields set as well, when there is a schema
+    fn with_fields(&self, query: &'static str) -> Cow<'static, str> {
+        cypher::with_fields(query, self.workload.schema.is_some())
+    }
 }
 
 #[async_trait]
 impl BenchmarkClient for Neo4jClient {
     async fn startup(&self) -> Result<()> {
-        self.execute_cypher("RETURN 1", json!({})).await?;
+        self.execute_cypher(cypher::PING, json!({})).await?;
+        let index = match self.workload.data_index {
+            Some(true) => CREATE_DATA_INDEX,
+            Some(false) => DROP_DATA_INDEX,
+            None => return Ok(()),
+        };
+        // the index is named after the label, so each dataset gets its own
+        let index = match &self.workload.dataset_name {
+            Some(label) => Cow::Owned(index.replace("record_data", &format!("{}_data", label))),
+            None => Cow::Borrowed(index),
+        };
+        self.execute_cypher(&index, json!({})).await.and_then(check_cypher_errors)?;
         Ok(())
     }
 
     async fn create_records(&mut self, count: usize) -> Result<()> {
-        let pb = ProgressBar::new(count as u64);
-        pb.set_style(
-            ProgressStyle::default_bar()
-                .template("[{elapsed_precise}] {bar:40.cyan/blue} {pos}/{len} ({eta}) Create")
-                .unwrap()
-                .progress_chars("##-"),
-        );
-        self.ids.extend((0..count).map(|_| Uuid::new_v4()));
-        let query = "CREATE (n:Record {id: $id, data: $data})";
-        for k in self.ids.clone().into_iter() {
-            let params = json!({"id": k.to_string(), "data": "test_value"});
-            let _ = self.execute_cypher(query, params).await?;
-            pb.inc(1);
-        }
-        pb.finish_with_message("Create complete");
+        let new_ids = self.workload.new_ids();
+        let this = &*self;
+        let query = &this.with_fields(cypher::CREATE_RECORD);
+        let ids = this
+            .runner
+            .run("Create", count, |i| async move {
+                let id = new_ids.id(i);
+                let params = this.record_params(&id.to_string(), this.workload.value(i));
+                this.execute_cypher(query, params).await?;
+                Ok(id)
+            })
+            .await?;
+        self.ids.extend(ids);
         Ok(())
     }
 
     async fn read_records(&self) -> Result<()> {
-        let pb = ProgressBar::new(self.ids.len() as u64);
-        pb.set_style(
-            ProgressStyle::default_bar()
-                .template("[{elapsed_precise}] {bar:40.cyan/blue} {pos}/{len} ({eta}) Read")
-                .unwrap()
-                .progress_chars("##-"),
-        );
-        let query = "MATCH (n:Record {id: $id}) RETURN n";
-        for k in self.ids.clone().into_iter() {
-            let params = json!({"id": k.to_string()});
-            self.execute_cypher(query, params).await?;
-            pb.inc(1);
-        }
-        pb.finish_with_message("Read complete");
+        let query = match self.workload.read_projection {
+            Projection::Id => cypher::READ_RECORD_ID,
+            _ => cypher::READ_RECORD,
+        };
+        let keys = self.workload.read_keys(self.ids.len())?;
+        let keys = &keys;
+        self.runner
+            .run("Read", keys.len(), |i| async move {
+                let id = self.ids[keys[i % keys.len()]];
+                let params = json!({"id": id.to_string()});
+                let response = self.read_cypher(query, params).await?;
+                let schema = self.workload.checked_schema(i);
+                if let (Some(schema), Projection::Full) = (schema, self.workload.read_projection) {
+                    let record = &response["results"][0]["data"][0]["row"][0];
+                    let record = record.as_object().cloned().unwrap_or_default();
+                    schema.validate(&id.to_string(), &record)?;
+                }
+                Ok(())
+            })
+            .await?;
         Ok(())
     }
 
     async fn update_records(&self) -> Result<()> {
-        let pb = ProgressBar::new(self.ids.len() as u64);
-        pb.set_style(
-            ProgressStyle::default_bar()
-                .template("[{elapsed_precise}] {bar:40.cyan/blue} {pos}/{len} ({eta}) Update")
-                .unwrap()
-                .progress_chars("##-"),
-        );
-        for k in self.ids.clone().into_iter() {
-            let query = "MATCH (n:Record {id: $id}) SET n.data = $data";
-            let params = json!({"id": k.to_string(), "data": "updated_value"});
-            self.execute_cypher(query, params).await?;
-            pb.inc(1);
-        }
-        pb.finish_with_message("Update complete");
+        let query = &self.with_fields(cypher::update_record(self.workload.update_mode));
+        let order = &self.workload.working_set(self.ids.len());
+        self.runner
+            .run("Update", self.ids.len(), |i| async move {
+                let id = self.ids[order[i % order.len()]];
+                let params = self.record_params(&id.to_string(), self.workload.updated_value(i));
+                self.execute_cypher(query, params).await?;
+                Ok(())
+            })
+            .await?;
+        Ok(())
+    }
+
+    async fn upsert_records(&mut self, count: usize) -> Result<()> {
+        let new_ids = self.workload.new_ids();
+        let this = &*self;
+        let query = &this.with_fields(cypher::UPSERT_RECORD);
+        let ids = this
+            .runner
+            .run("Upsert", count, |i| async move {
+                let existing = upsert_key(&this.ids, i);
+                let id = existing.unwrap_or_else(|| new_ids.id(i));
+                let params = this.record_params(&id.to_string(), this.workload.value(i));
+                this.execute_cypher(query, params).await?;
+                Ok(existing.is_none().then_some(id))
+            })
+            .await?;
+        self.ids.extend(ids.into_iter().flatten());
         Ok(())
     }
 
     async fn delete_records(&self) -> Result<()> {
-        let pb = ProgressBar::new(self.ids.len() as u64);
-        pb.set_style(
-            ProgressStyle::default_bar()
-                .template("[{elapsed_precise}] {bar:40.cyan/blue} {pos}/{len} ({eta}) Delete")
-                .unwrap()
-                .progress_chars("##-"),
-        );
-        let query = "MATCH (n:Record {id: $id}) DELETE n";
-        for k in self.ids.clone().into_iter() {
-            let params = json!({"id": k.to_string()});
-            self.execute_cypher(query, params).await?;
-            pb.inc(1);
+        let query = cypher::DELETE_RECORD;
+        let order = &self.workload.order(self.ids.len());
+        self.runner
+            .run("Delete", self.ids.len(), |i| async move {
+                let id = self.ids[order[i % order.len()]];
+                let params = json!({"id": id.to_string()});
+                self.execute_cypher(query, params).await?;
+                Ok(())
+            })
+            .await?;
+        Ok(())
+    }
+
+    async fn cleanup(&mut self) -> Result<()> {
+        for batch in self.ids.chunks(CLEANUP_BATCH) {
+            let ids: Vec<String> = batch.iter().map(Uuid::to_string).collect();
+            self.execute_cypher(cypher::DELETE_RECORDS, json!({"ids": ids}))
+                .await
+                .and_then(check_cypher_errors)?;
         }
-        pb.finish_with_message("Delete complete");
+        self.ids.clear();
         Ok(())
     }
 
     async fn scan_records(&self) -> Result<()> {
-        let count = self.ids.len();
-        let pb = ProgressBar::new_spinner();
-        pb.set_style(
-            ProgressStyle::default_spinner()
-                .template("{spinner:.green} [{elapsed_precise}] Running scan...")
-                .unwrap(),
-        );
-        pb.enable_steady_tick(Duration::from_millis(100));
-        let scan = Scan::new(Some(count), None, Projection::Full);
-        let _ = self.scan(&scan).await;
-        pb.finish_with_message("Scan complete");
+        let scan = self.workload.scan(&self.ids);
+        self.runner.scan(scan, |scan| async move { self.scan(&scan).await }).await?;
+        Ok(())
+    }
+
+    async fn read_records_by_data(&self) -> Result<()> {
+        let keys = self.workload.read_keys(self.ids.len())?;
+        let keys = &keys;
+        self.runner
+            .run("Read by data", keys.len(), |i| async move {
+                let data = self.workload.value(keys[i % keys.len()]);
+                let params = json!({"data": data});
+                let response = self.read_cypher(cypher::READ_RECORDS_BY_DATA, params).await?;
+                let matches = response["results"][0]["data"][0]["row"][0].as_u64();
+                if matches.unwrap_or(0) == 0 && self.workload.verifies(i) {
+                    return Err(anyhow::anyhow!("No record holds the data {:?}", data));
+                }
+                Ok(())
+            })
+            .await?;
+        Ok(())
+    }
+
+    // Each transaction is a single request carrying a create, read and update per record, so
+    // the server runs and commits them together
+    async fn transaction(&mut self, count: usize) -> Result<()> {
+        let new_ids = self.workload.new_ids();
+        let ops = self.workload.ops_per_txn;
+        let this = &*self;
+        let create = &this.with_fields(cypher::CREATE_RECORD);
+        let update = &this.with_fields(cypher::update_record(this.workload.update_mode));
+        let ids = this
+            .runner
+            .run("Txn", count, |i| async move {
+                let ids: Vec<Uuid> = (0..ops).map(|j| new_ids.id(i * ops + j)).collect();
+                let mut statements = Vec::with_capacity(ops * 3);
+                for (j, id) in ids.iter().enumerate() {
+                    let (id, n) = (id.to_string(), i * ops + j);
+                    let created = this.record_params(&id, this.workload.value(n));
+                    let updated = this.record_params(&id, this.workload.updated_value(n));
+                    statements.push((create.as_ref(), created));
+                    statements.push((cypher::READ_RECORD, json!({"id": id})));
+                    statements.push((update.as_ref(), updated));
+                }
+                this.execute_statements(statements).await.and_then(check_cypher_errors)?;
+                Ok(ids)
+            })
+            .await?;
+        self.ids.extend(ids.into_iter().flatten());
+        Ok(())
+    }
+
+    async fn batch_read(&self) -> Result<()> {
+        let keys = self.workload.read_keys(self.ids.len())?;
+        let batches: Vec<&[usize]> = keys.chunks(self.workload.batch_size).collect();
+        let batches = &batches;
+        self.runner
+            .run("Batch read", batches.len(), |i| async move {
+                let batch = batches[i % batches.len()];
+                let ids: Vec<String> = batch.iter().map(|key| self.ids[*key].to_string()).collect();
+                let params = json!({"ids": ids});
+                let response = self.read_cypher(cypher::READ_RECORDS, params).await?;
+                let found = response["results"][0]["data"].as_array().map_or(0, Vec::len);
+                if found < batch.len() && self.workload.verifies(i) {
+                    return Err(anyhow::anyhow!(
+                        "Batch read returned {} of {} records",
+                        found,
+                        batch.len()
+                    ));
+                }
+                Ok(())
+            })
+            .await?;
         Ok(())
     }
 
     async fn count_records(&self) -> Result<usize> {
-        let query = "MATCH (n) RETURN count(n) as count";
+        let query = cypher::COUNT_RECORDS;
         let params = json!({});
-        let response = self.execute_cypher(query, params).await?;
-        println!("Count records result: {:?}", response);
+        let response = self.read_cypher(query, params).await?;
+        debug!(%response, "count records");
         Ok(response["results"][0]["data"][0]["row"][0]
             .as_u64()
             .unwrap_or(0) as usize)
     }
 
-    async fn create_vectors(&self, count: usize) -> Result<()> { Ok(()) }
-    async fn search_vectors(&self, count: usize) -> Result<()> { Ok(()) }
+    fn tracked_ids(&self) -> usize {
+        self.ids.len()
+    }
+
+    fn load_ids(&mut self, ids: &[String]) -> Result<()> {
+        self.ids.extend(parse_uuids(ids)?);
+        Ok(())
+    }
+
+    async fn reset(&mut self, purge: bool) -> Result<()> {
+        if purge {
+            self.execute_cypher(cypher::DELETE_ALL_RECORDS, json!({})).await?;
+        }
+        self.ids.clear();
+        Ok(())
+    }
+
+    async fn create_record(&self, data: &str) -> Result<String> {
+        let id = self.workload.new_ids().id(0).to_string();
+        let params = self.record_params(&id, data);
+        self.execute_cypher(&self.with_fields(cypher::CREATE_RECORD), params).await?;
+        Ok(id)
+    }
+
+    async fn read_record(&self, key: &str) -> Result<()> {
+        let query = match self.workload.read_projection {
+            Projection::Id => cypher::READ_RECORD_ID,
+            _ => cypher::READ_RECORD,
+        };
+        self.read_cypher(query, json!({"id": key})).await?;
+        Ok(())
+    }
+
+    async fn update_record(&self, key: &str, data: &str) -> Result<()> {
+        let params = json!({"id": key, "data": data});
+        let query = cypher::update_record(self.workload.update_mode);
+        self.execute_cypher(query, params).await?;
+        Ok(())
+    }
+
+    async fn delete_record(&self, key: &str) -> Result<()> {
+        self.execute_cypher(cypher::DELETE_RECORD, json!({"id": key}))
+            .await?;
+        Ok(())
+    }
+
+    async fn create_vectors(&self, _count: usize) -> Result<()> {
+        Err(anyhow::anyhow!("vectors aren't supported for Neo4j"))
+    }
+
+    async fn search_vectors(&self, _count: usize) -> Result<()> {
+        Err(anyhow::anyhow!("vectors aren't supported for Neo4j"))
+    }
+
+    async fn server_version(&self) -> Option<String> {
+        let response = self.execute_cypher(SERVER_VERSION, json!({})).await;
+        match response.and_then(check_cypher_errors) {
+            Ok(response) => response["results"][0]["data"][0]["row"][0].as_str().map(Into::into),
+            Err(e) => {
+                debug!(error = %e, "no server version");
+                None
+            }
+        }
+    }
+
+    async fn dry_run(&mut self) -> Vec<(String, Result<()>)> {
+        let id = Uuid::new_v4().to_string();
+        let mut checks = Vec::new();
+
+        let query = cypher::CREATE_RECORD;
+        let created = self
+            .execute_cypher(query, json!({"id": id, "data": "test_value"}))
+            .await
+            .and_then(check_cypher_errors)
+            .map(|_| ());
+        let create_ok = created.is_ok();
+        checks.push(("create".to_string(), created));
+
+        let query = cypher::READ_RECORD_DATA;
+        let read = if create_ok {
+            self.execute_cypher(query, json!({"id": id}))
+                .await
+                .and_then(check_cypher_errors)
+                .and_then(|res| {
+                    if res["results"][0]["data"][0]["row"][0] == "test_value" {
+                        Ok(())
+                    } else {
+                        Err(anyhow::anyhow!("unexpected read response: {}", res))
+                    }
+                })
+        } else {
+            Err(anyhow::anyhow!("skipped, create failed"))
+        };
+        checks.push(("read".to_string(), read));
+
+        let query = cypher::DELETE_RECORD;
+        let deleted = if create_ok {
+            self.execute_cypher(query, json!({"id": id}))
+                .await
+                .and_then(check_cypher_errors)
+                .map(|_| ())
+        } else {
+            Err(anyhow::anyhow!("skipped, create failed"))
+        };
+        checks.push(("delete".to_string(), deleted));
+
+        checks
+    }
 
     /*
     async fn bulk_create_string(&self, count: usize, val: Value) -> Result<()> {
@@ -182,7 +506,7 @@ impl BenchmarkClient for Neo4jClient {
         "#;
         let params = json!({});  // count parameter not needed
         let response = self.execute_cypher(query, params).await?;
-        println!("Huge traversal result: {:?}", response);
+        debug!(%response, "huge traversal");
         Ok(())
     }
     */
@@ -193,13 +517,17 @@ impl Neo4jClient {
         let limit = scan.limit.unwrap_or(100);
         let offset = scan.start.unwrap_or(0);
         let (query, params) = match scan.projection()? {
-            Projection::Count => ("MATCH (n:Record) RETURN count(n)", json!({})),
-            _ => (
-                "MATCH (n:Record) RETURN n LIMIT $limit SKIP $offset",
+            Projection::Count => (cypher::COUNT_RECORDS, json!({})),
+            Projection::Id => (
+                cypher::SCAN_RECORD_IDS,
+                json!({"limit": limit, "offset": offset}),
+            ),
+            Projection::Full => (
+                cypher::SCAN_RECORDS,
                 json!({"limit": limit, "offset": offset}),
             ),
         };
-        let response = self.execute_cypher(query, params).await?;
+        let response = self.read_cypher(query, params).await?;
         match scan.projection()? {
             Projection::Count => {
                 let count = response["results"][0]["data"][0]["row"][0]
@@ -207,20 +535,44 @@ impl Neo4jClient {
                     .unwrap_or(0) as usize;
                 Ok(count)
             }
-            _ => {
+            projection => {
                 let rows = response["results"][0]["data"]
                     .as_array()
-                    .map(|arr| arr.len())
-                    .unwrap_or(0);
-                Ok(rows)
+                    .map(Vec::as_slice)
+                    .unwrap_or_default();
+                // A full scan returns the node, whose properties come back as a map
+                let ids = rows.iter().filter_map(|row| {
+                    let id = match projection {
+                        Projection::Id => &row["row"][0],
+                        _ => &row["row"][0]["id"],
+                    };
+                    id.as_str().map(str::to_string)
+                });
+                scan.saw(ids);
+                Ok(rows.len())
             }
         }
     }
 }
 
+// The transactional HTTP API reports Cypher errors with a 200 status, so they have to be
+// pulled out of the response body
+fn check_cypher_errors(response: Value) -> Result<Value> {
+    match response["errors"].as_array() {
+        Some(errors) if !errors.is_empty() => {
+            Err(anyhow::anyhow!("Cypher error: {}", errors[0]["message"]))
+        }
+        _ => Ok(response),
+    }
+}
+
 // Engine for Neo4j
 pub struct Neo4jEngine {
     endpoint: String,
+    client: ReconnectingClient,
+    runner: Runner,
+    workload: Workload,
+    routing: Option<Arc<Routing>>,
 }
 
 #[async_trait]
@@ -231,12 +583,84 @@ impl BenchmarkEngine for Neo4jEngine {
             .as_deref()
             .unwrap_or("http://localhost:7474")
             .to_string();
-        Ok(Self { endpoint })
+        let client = ReconnectingClient::new(options, |builder| builder)?;
+        let routing = match options.neo4j_routing {
+            true => {
+                let seed = Neo4jClient::new(
+                    endpoint.clone(),
+                    client.clone(),
+                    options.runner.clone(),
+                    options.workload.clone(),
+                    None,
+                );
+                let table = seed
+                    .execute_cypher(ROUTING_TABLE, json!({}))
+                    .await
+                    .and_then(check_cypher_errors)
+                    .map_err(|e| e.context(format!("No routing table from {}", endpoint)))?;
+                let routing = Routing::from_table(&endpoint, &table)?;
+                info!(writers = ?routing.writers, readers = ?routing.readers, "Neo4j routing");
+                Some(Arc::new(routing))
+            }
+            false => None,
+        };
+        Ok(Self {
+            endpoint,
+            client,
+            runner: options.runner.clone(),
+            workload: options.workload.clone(),
+            routing,
+        })
     }
 
     async fn create_client(&self) -> Result<Box<dyn BenchmarkClient>> {
-        let client = Neo4jClient::new(self.endpoint.clone());
+        let client = Neo4jClient::new(
+            self.endpoint.clone(),
+            self.client.clone(),
+            self.runner.clone(),
+            self.workload.clone(),
+            self.routing.clone(),
+        );
         client.startup().await?;
         Ok(Box::new(client))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn routing_table(servers: Value) -> Value {
+        json!({"results": [{"data": [{"row": [servers]}]}]})
+    }
+
+    #[test]
+    fn routing_reaches_members_on_the_endpoint_scheme_and_port() {
+        let table = routing_table(json!([
+            {"role": "WRITE", "addresses": ["core1:7687"]},
+            {"role": "READ", "addresses": ["core2:7687", "core3:7687"]},
+            {"role": "ROUTE", "addresses": ["core1:7687", "core2:7687", "core3:7687"]},
+        ]));
+        let routing = Routing::from_table("https://neo4j:7473", &table).unwrap();
+        assert_eq!(routing.writers, ["https://core1:7473"]);
+        assert_eq!(routing.readers, ["https://core2:7473", "https://core3:7473"]);
+        let readers = [routing.reader(), routing.reader(), routing.reader()];
+        assert_eq!(readers[0], readers[2]);
+        assert_ne!(readers[0], readers[1]);
+        assert_eq!(routing.writer(), "https://core1:7473");
+    }
+
+    #[test]
+    fn routing_reads_from_the_leader_without_followers() {
+        let table = routing_table(json!([{"role": "WRITE", "addresses": ["[::1]:7687"]}]));
+        let routing = Routing::from_table("http://localhost", &table).unwrap();
+        assert_eq!(routing.readers, ["http://[::1]:80"]);
+    }
+
+    #[test]
+    fn routing_needs_a_leader() {
+        let table = routing_table(json!([{"role": "READ", "addresses": ["core2:7687"]}]));
+        assert!(Routing::from_table("http://localhost:7474", &table).is_err());
+        assert!(Routing::from_table("http://localhost:7474", &json!({"errors": []})).is_err());
+    }
+}
@@ -0,0 +1,505 @@
+use crate::runner::Runner;
+use crate::types::{
+    Benchmark, BenchmarkClient, BenchmarkEngine, Projection, Scan, StatusError, VectorMetric,
+    Workload, CLEANUP_BATCH,
+};
+use crate::utils::{
+    generate_random_vector_f32, http_client_builder, nearest_neighbours, parse_uuids, upsert_key,
+};
+use anyhow::Result;
+use async_trait::async_trait;
+use reqwest::{Client, Method, StatusCode};
+use serde_json::{json, Value};
+use std::sync::Mutex;
+use tracing::{debug, trace};
+use uuid::Uuid;
+
+// Records are documents keyed by their id, which is also kept in the source so scans can sort
+// on it and page through the same order every time
+const RECORD_INDEX: &str = "helix_records";
+
+// An index's vector dimension and space are fixed in its mapping, so each combination gets an
+// index of its own
+fn vector_index(metric: VectorMetric, dims: usize) -> String {
+    format!("helix_vectors_{}_{}", metric.as_str(), dims)
+}
+
+fn space_type(metric: VectorMetric) -> &'static str {
+    match metric {
+        VectorMetric::Cosine => "cosinesimil",
+        VectorMetric::Euclidean => "l2",
+        VectorMetric::Dot => "innerproduct",
+    }
+}
+
+pub struct OpenSearchClient {
+    endpoint: String,
+    client: Client,
+    runner: Runner,
+    workload: Workload,
+    ids: Vec<Uuid>,
+    // Vectors created, with their ids, when the workload tracks them for recall
+    vectors: Mutex<Vec<(Uuid, Vec<f32>)>>,
+}
+
+impl OpenSearchClient {
+    pub fn new(endpoint: String, client: Client, runner: Runner, workload: Workload) -> Self {
+        Self {
+            endpoint,
+            client,
+            runner,
+            workload,
+            ids: Vec::new(),
+            vectors: Mutex::new(Vec::new()),
+        }
+    }
+
+    async fn send(&self, method: Method, path: &str, body: Option<Value>) -> Result<Value> {
+        let url = format!("{}{}", self.endpoint, path);
+        trace!(%method, %path, ?body, "request");
+        let mut request = self.client.request(method, &url);
+        if let Some(body) = body {
+            request = request.json(&body);
+        }
+        let response = request.send().await?;
+        debug!(%path, status = %response.status(), "response");
+        let status = response.status();
+        let text = response.text().await?;
+        trace!(response = %text, "response body");
+        if !status.is_success() {
+            return Err(StatusError::new(status, text.trim()).into());
+        }
+        Ok(serde_json::from_str(&text)?)
+    }
+
+    // The bulk API takes newline-delimited JSON and answers 200 even when items fail
+    async fn bulk(&self, lines: Vec<Value>) -> Result<()> {
+        let body: String = lines.iter().map(|line| format!("{}\n", line)).collect();
+        let response = self
+            .client
+            .post(format!("{}/_bulk", self.endpoint))
+            .header("Content-Type", "application/x-ndjson")
+            .body(body)
+            .send()
+            .await?;
+        debug!(path = "/_bulk", status = %response.status(), "response");
+        if !response.status().is_success() {
+            return Err(StatusError::new(response.status(), "").into());
+        }
+        let res = response.json::<Value>().await?;
+        match res["errors"].as_bool() {
+            Some(true) => Err(anyhow::anyhow!("Bulk request failed: {}", res["items"][0])),
+            _ => Ok(()),
+        }
+    }
+
+    // Clients start together, so another one may create the index between the check and the
+    // create; that counts as created
+    async fn ensure_index(&self, index: &str, body: Value) -> Result<()> {
+        let url = format!("{}/{}", self.endpoint, index);
+        match self.client.head(&url).send().await?.status() {
+            StatusCode::OK => return Ok(()),
+            StatusCode::NOT_FOUND => {}
+            status => return Err(anyhow::anyhow!("Request failed: {}", status)),
+        }
+        match self.send(Method::PUT, &format!("/{}", index), Some(body)).await {
+            Err(e) if e.to_string().contains("resource_already_exists_exception") => Ok(()),
+            res => res.map(|_| ()),
+        }
+    }
+
+    // Writes are only visible to searches, counts and scans after a refresh, which would
+    // otherwise come once a second
+    async fn refresh(&self, index: &str) -> Result<()> {
+        self.send(Method::POST, &format!("/{}/_refresh", index), None).await?;
+        Ok(())
+    }
+
+    // Indexing by id creates the document or replaces it whole, so this is also the upsert
+    async fn insert(&self, id: &str, data: &str) -> Result<()> {
+        let path = format!("/{}/_doc/{}", RECORD_INDEX, id);
+        self.send(Method::PUT, &path, Some(json!({"id": id, "data": data}))).await?;
+        Ok(())
+    }
+
+    async fn update(&self, id: &str, data: &str) -> Result<()> {
+        let path = format!("/{}/_update/{}", RECORD_INDEX, id);
+        self.send(Method::POST, &path, Some(json!({"doc": {"data": data}}))).await?;
+        Ok(())
+    }
+
+    // Gets are realtime, so a read sees the write just before it without a refresh
+    async fn select(&self, id: &str, projection: Projection) -> Result<Value> {
+        let path = match projection {
+            Projection::Id => format!("/{}/_doc/{}?_source=false", RECORD_INDEX, id),
+            _ => format!("/{}/_doc/{}", RECORD_INDEX, id),
+        };
+        self.send(Method::GET, &path, None).await
+    }
+
+    async fn remove(&self, id: &str) -> Result<()> {
+        self.send(Method::DELETE, &format!("/{}/_doc/{}", RECORD_INDEX, id), None).await?;
+        Ok(())
+    }
+
+    // Ids of the nearest `k` vectors to `vector`, nearest first
+    async fn search(&self, vector: &[f32]) -> Result<Vec<String>> {
+        let k = self.workload.k;
+        let body = json!({
+            "size": k,
+            "_source": false,
+            "query": {"knn": {"vector": {"vector": vector, "k": k}}}
+        });
+        let path = format!("/{}/_search", vector_index(self.workload.metric, self.workload.dims));
+        let res = self.send(Method::POST, &path, Some(body)).await?;
+        Ok(res["hits"]["hits"]
+            .as_array()
+            .map(|hits| {
+                hits.iter()
+                    .filter_map(|hit| hit["_id"].as_str().map(str::to_string))
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+
+    // `from` + `size` can't pass the index's `max_result_window`, 10000 by default, so larger
+    // scans need `--scan-page-size` and a raised window
+    async fn scan(&self, scan: &Scan) -> Result<usize> {
+        let source = match scan.projection()? {
+            Projection::Count => return self.count_records().await,
+            Projection::Id => json!(false),
+            Projection::Full => json!(true),
+        };
+        let body = json!({
+            "from": scan.start.unwrap_or(0),
+            "size": scan.limit.unwrap_or(100),
+            "sort": [{"id": "asc"}],
+            "_source": source,
+            "query": {"match_all": {}}
+        });
+        let path = format!("/{}/_search", RECORD_INDEX);
+        let res = self.send(Method::POST, &path, Some(body)).await?;
+        let hits = res["hits"]["hits"].as_array().map(Vec::as_slice).unwrap_or_default();
+        scan.saw(hits.iter().filter_map(|hit| hit["_id"].as_str().map(str::to_string)));
+        Ok(hits.len())
+    }
+}
+
+#[async_trait]
+impl BenchmarkClient for OpenSearchClient {
+    // Creating the indices when they're missing doubles as the connectivity check
+    async fn startup(&self) -> Result<()> {
+        let records = json!({
+            "mappings": {"properties": {"id": {"type": "keyword"}, "data": {"type": "keyword"}}}
+        });
+        self.ensure_index(RECORD_INDEX, records).await?;
+        let (metric, dims) = (self.workload.metric, self.workload.dims);
+        let vectors = json!({
+            "settings": {"index.knn": true},
+            "mappings": {"properties": {"vector": {
+                "type": "knn_vector",
+                "dimension": dims,
+                "method": {"name": "hnsw", "engine": "lucene", "space_type": space_type(metric)}
+            }}}
+        });
+        self.ensure_index(&vector_index(metric, dims), vectors).await
+    }
+
+    async fn create_records(&mut self, count: usize) -> Result<()> {
+        let new_ids = self.workload.new_ids();
+        let this = &*self;
+        let ids = this
+            .runner
+            .run("Create", count, |i| async move {
+                let id = new_ids.id(i);
+                this.insert(&id.to_string(), this.workload.value(i)).await?;
+                Ok(id)
+            })
+            .await?;
+        self.ids.extend(ids);
+        Ok(())
+    }
+
+    async fn read_records(&self) -> Result<()> {
+        let keys = self.workload.read_keys(self.ids.len())?;
+        let keys = &keys;
+        self.runner
+            .run("Read", keys.len(), |i| async move {
+                let key = keys[i % keys.len()];
+                let projection = self.workload.read_projection;
+                let res = self.select(&self.ids[key].to_string(), projection).await?;
+                let expected = self.workload.checked_value(i, key);
+                if let (Projection::Full, Some(expected)) = (projection, expected) {
+                    if res["_source"]["data"] != expected {
+                        let id = &self.ids[key];
+                        return Err(anyhow::anyhow!("Read of {} returned the wrong data", id));
+                    }
+                }
+                Ok(())
+            })
+            .await?;
+        Ok(())
+    }
+
+    async fn update_records(&self) -> Result<()> {
+        let order = &self.workload.working_set(self.ids.len());
+        self.runner
+            .run("Update", self.ids.len(), |i| async move {
+                let id = self.ids[order[i % order.len()]];
+                self.update(&id.to_string(), self.workload.updated_value(i)).await
+            })
+            .await?;
+        Ok(())
+    }
+
+    async fn upsert_records(&mut self, count: usize) -> Result<()> {
+        let new_ids = self.workload.new_ids();
+        let this = &*self;
+        let ids = this
+            .runner
+            .run("Upsert", count, |i| async move {
+                let existing = upsert_key(&this.ids, i);
+                let id = existing.unwrap_or_else(|| new_ids.id(i));
+                this.insert(&id.to_string(), this.workload.value(i)).await?;
+                Ok(existing.is_none().then_some(id))
+            })
+            .await?;
+        self.ids.extend(ids.into_iter().flatten());
+        Ok(())
+    }
+
+    async fn delete_records(&self) -> Result<()> {
+        let order = &self.workload.order(self.ids.len());
+        self.runner
+            .run("Delete", self.ids.len(), |i| async move {
+                self.remove(&self.ids[order[i % order.len()]].to_string()).await
+            })
+            .await?;
+        Ok(())
+    }
+
+    async fn cleanup(&mut self) -> Result<()> {
+        for batch in self.ids.chunks(CLEANUP_BATCH) {
+            let lines = batch
+                .iter()
+                .map(|id| json!({"delete": {"_index": RECORD_INDEX, "_id": id.to_string()}}))
+                .collect();
+            self.bulk(lines).await?;
+        }
+        self.ids.clear();
+        Ok(())
+    }
+
+    async fn scan_records(&self) -> Result<()> {
+        self.refresh(RECORD_INDEX).await?;
+        let scan = self.workload.scan(&self.ids);
+        self.runner.scan(scan, |scan| async move { self.scan(&scan).await }).await?;
+        Ok(())
+    }
+
+    // `data` is a keyword field, which OpenSearch always indexes, so this is a term lookup
+    // whatever --create-index or --no-index say
+    async fn read_records_by_data(&self) -> Result<()> {
+        self.refresh(RECORD_INDEX).await?;
+        let keys = self.workload.read_keys(self.ids.len())?;
+        let keys = &keys;
+        let path = format!("/{}/_count", RECORD_INDEX);
+        let path = &path;
+        self.runner
+            .run("Read by data", keys.len(), |i| async move {
+                let data = self.workload.value(keys[i % keys.len()]);
+                let body = json!({"query": {"term": {"data": data}}});
+                let res = self.send(Method::POST, path, Some(body)).await?;
+                if res["count"].as_u64().unwrap_or(0) == 0 && self.workload.verifies(i) {
+                    return Err(anyhow::anyhow!("No record holds the data {:?}", data));
+                }
+                Ok(())
+            })
+            .await?;
+        Ok(())
+    }
+
+    async fn transaction(&mut self, _count: usize) -> Result<()> {
+        Err(anyhow::anyhow!("txn isn't supported for OpenSearch"))
+    }
+
+    async fn batch_read(&self) -> Result<()> {
+        Err(anyhow::anyhow!("batch_read isn't supported for OpenSearch"))
+    }
+
+    async fn count_records(&self) -> Result<usize> {
+        self.refresh(RECORD_INDEX).await?;
+        let res = self.send(Method::GET, &format!("/{}/_count", RECORD_INDEX), None).await?;
+        res["count"]
+            .as_u64()
+            .map(|count| count as usize)
+            .ok_or_else(|| anyhow::anyhow!("no count in response: {}", res))
+    }
+
+    fn tracked_ids(&self) -> usize {
+        self.ids.len()
+    }
+
+    fn load_ids(&mut self, ids: &[String]) -> Result<()> {
+        self.ids.extend(parse_uuids(ids)?);
+        Ok(())
+    }
+
+    async fn reset(&mut self, purge: bool) -> Result<()> {
+        if purge {
+            // Delete by query only sees refreshed documents, and refreshes again after so the
+            // next count sees them gone
+            self.refresh(RECORD_INDEX).await?;
+            let path = format!("/{}/_delete_by_query?refresh=true", RECORD_INDEX);
+            let body = json!({"query": {"match_all": {}}});
+            self.send(Method::POST, &path, Some(body)).await?;
+        }
+        self.ids.clear();
+        self.vectors.lock().unwrap().clear();
+        Ok(())
+    }
+
+    async fn create_record(&self, data: &str) -> Result<String> {
+        let id = self.workload.new_ids().id(0).to_string();
+        self.insert(&id, data).await?;
+        Ok(id)
+    }
+
+    async fn read_record(&self, key: &str) -> Result<()> {
+        self.select(key, self.workload.read_projection).await?;
+        Ok(())
+    }
+
+    async fn update_record(&self, key: &str, data: &str) -> Result<()> {
+        self.update(key, data).await
+    }
+
+    async fn delete_record(&self, key: &str) -> Result<()> {
+        self.remove(key).await
+    }
+
+    // knn_vector fields hold f32 whatever the precision they're sent with
+    async fn create_vectors(&self, count: usize) -> Result<()> {
+        let new_ids = self.workload.new_ids();
+        let index = vector_index(self.workload.metric, self.workload.dims);
+        let index = &index;
+        self.runner
+            .run("Create vectors", count, |i| async move {
+                let (id, vector) = (new_ids.id(i), generate_random_vector_f32(self.workload.dims));
+                let path = format!("/{}/_doc/{}", index, id);
+                self.send(Method::PUT, &path, Some(json!({"vector": vector}))).await?;
+                self.workload.vector_created();
+                if self.workload.track_vectors {
+                    self.vectors.lock().unwrap().push((id, vector));
+                }
+                Ok(())
+            })
+            .await?;
+        Ok(())
+    }
+
+    async fn search_vectors(&self, count: usize) -> Result<()> {
+        self.refresh(&vector_index(self.workload.metric, self.workload.dims)).await?;
+        self.runner
+            .run("Search vectors", count, |_| async move {
+                let found = self.search(&generate_random_vector_f32(self.workload.dims)).await?;
+                self.workload.check_search_results(found.len())
+            })
+            .await?;
+        Ok(())
+    }
+
+    async fn server_version(&self) -> Option<String> {
+        let res = self.send(Method::GET, "/", None).await.ok()?;
+        res["version"]["number"].as_str().map(str::to_string)
+    }
+
+    async fn vector_recall(&self, queries: usize) -> Result<Option<f64>> {
+        let vectors = self.vectors.lock().unwrap().clone();
+        if vectors.is_empty() {
+            return Ok(None);
+        }
+        self.refresh(&vector_index(self.workload.metric, self.workload.dims)).await?;
+        let (ids, vectors): (Vec<Uuid>, Vec<Vec<f32>>) = vectors.into_iter().unzip();
+        let k = self.workload.k.min(vectors.len());
+        let mut total = 0.0;
+        for _ in 0..queries {
+            let query = generate_random_vector_f32(self.workload.dims);
+            let exact = nearest_neighbours(self.workload.metric, &vectors, &query, k);
+            let found = self.search(&query).await?;
+            let hits = exact
+                .iter()
+                .filter(|&&i| found.contains(&ids[i].to_string()))
+                .count();
+            total += hits as f64 / k as f64;
+        }
+        Ok(Some(total / queries.max(1) as f64))
+    }
+
+    async fn dry_run(&mut self) -> Vec<(String, Result<()>)> {
+        let id = Uuid::new_v4().to_string();
+        let mut checks = Vec::new();
+
+        let created = self.insert(&id, "test_value").await;
+        let create_ok = created.is_ok();
+        checks.push(("PUT /_doc".to_string(), created));
+
+        let read = if create_ok {
+            self.select(&id, Projection::Full).await.and_then(|res| {
+                if res["_source"]["data"] == "test_value" {
+                    Ok(())
+                } else {
+                    Err(anyhow::anyhow!("unexpected read response: {}", res))
+                }
+            })
+        } else {
+            Err(anyhow::anyhow!("skipped, create failed"))
+        };
+        checks.push(("GET /_doc".to_string(), read));
+
+        let deleted = if create_ok {
+            self.remove(&id).await
+        } else {
+            Err(anyhow::anyhow!("skipped, create failed"))
+        };
+        checks.push(("DELETE /_doc".to_string(), deleted));
+
+        checks
+    }
+}
+
+// Engine for OpenSearch over its REST API, without authentication
+pub struct OpenSearchEngine {
+    endpoint: String,
+    client: Client,
+    runner: Runner,
+    workload: Workload,
+}
+
+#[async_trait]
+impl BenchmarkEngine for OpenSearchEngine {
+    async fn setup(options: &Benchmark) -> Result<Self> {
+        let endpoint = options
+            .endpoint
+            .as_deref()
+            .unwrap_or("http://localhost:9200")
+            .to_string();
+        Ok(Self {
+            endpoint,
+            client: http_client_builder(options)?.build()?,
+            runner: options.runner.clone(),
+            workload: options.workload.clone(),
+        })
+    }
+
+    async fn create_client(&self) -> Result<Box<dyn BenchmarkClient>> {
+        let client = OpenSearchClient::new(
+            self.endpoint.clone(),
+            self.client.clone(),
+            self.runner.clone(),
+            self.workload.clone(),
+        );
+        client.startup().await?;
+        Ok(Box::new(client))
+    }
+}
@@ -0,0 +1,567 @@
+use crate::types::{RequestsFailed, Scan, StatusError, Timeline};
+use anyhow::Result;
+use futures::stream::{self, StreamExt};
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+use rand::Rng;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::future::Future;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tracing::{debug, info, warn};
+
+/// Names of the metrics exported with `--metrics-port`, each labelled with the operation
+pub const REQUESTS_METRIC: &str = "helix_bench_requests_total";
+pub const ERRORS_METRIC: &str = "helix_bench_errors_total";
+pub const LATENCY_METRIC: &str = "helix_bench_request_duration_seconds";
+
+// Redraws per second of the progress bars, well under indicatif's default of 20 so drawing
+// takes as little as possible from the requests at high throughput
+const PROGRESS_HZ: u8 = 4;
+// Completions per progress update, as a fraction of the operation's count
+const PROGRESS_STEPS: usize = 1000;
+
+// Drives the per-request loops of the benchmark operations. Clones share their state, so
+// `main` keeps a handle to cancel a run, adjust it between operations and read its progress
+// while clients use their own.
+#[derive(Clone)]
+pub struct Runner {
+    concurrency: Arc<AtomicUsize>,
+    duration: Arc<Mutex<Option<Duration>>>,
+    cancelled: Arc<AtomicBool>,
+    completed: Arc<AtomicUsize>,
+    errors: Arc<AtomicUsize>,
+    // Failed requests by what went wrong, see `error_kind`
+    error_breakdown: Arc<Mutex<BTreeMap<String, usize>>>,
+    // Failed requests tolerated per operation before it aborts, `None` fails on the first
+    max_errors: Option<usize>,
+    // Requests started per second at most, `None` starts them as fast as slots free up
+    limit_rps: Option<f64>,
+    latency_log: Option<LatencyLog>,
+    // Longest random delay before each of the first `concurrency` requests of a loop
+    start_jitter: Option<Duration>,
+    // Pause a slot takes after each request before starting its next
+    think_time: Option<Duration>,
+    // Permits for requests in flight, shared by every loop and clone, `None` for no cap
+    in_flight: Option<Arc<Semaphore>>,
+    // Whether progress bars are drawn
+    quiet: bool,
+    // Longest an operation's loop keeps starting requests, and whether the current one hit it
+    time_budget: Arc<Mutex<Option<Duration>>>,
+    truncated: Arc<AtomicBool>,
+    latencies: Arc<Mutex<Vec<Duration>>>,
+    // Keeps one successful request's latency in this many
+    sample_every: usize,
+    // Start of the current operation, and its completions per second since then
+    started: Arc<Mutex<Instant>>,
+    timeline: Arc<Mutex<Timeline>>,
+}
+
+impl Runner {
+    pub fn new(concurrency: usize, duration: Option<Duration>) -> Self {
+        Self {
+            concurrency: Arc::new(AtomicUsize::new(concurrency.max(1))),
+            duration: Arc::new(Mutex::new(duration)),
+            cancelled: Arc::new(AtomicBool::new(false)),
+            completed: Arc::new(AtomicUsize::new(0)),
+            errors: Arc::new(AtomicUsize::new(0)),
+            error_breakdown: Arc::new(Mutex::new(BTreeMap::new())),
+            max_errors: None,
+            limit_rps: None,
+            latency_log: None,
+            start_jitter: None,
+            think_time: None,
+            in_flight: None,
+            quiet: false,
+            time_budget: Arc::new(Mutex::new(None)),
+            truncated: Arc::new(AtomicBool::new(false)),
+            latencies: Arc::new(Mutex::new(Vec::new())),
+            sample_every: 1,
+            started: Arc::new(Mutex::new(Instant::now())),
+            timeline: Arc::new(Mutex::new(Timeline::default())),
+        }
+    }
+
+    /// Keeps going past failed requests, counting them, until more than `max_errors` have
+    /// failed in one operation. Without this the first failure aborts the operation.
+    pub fn with_max_errors(mut self, max_errors: Option<usize>) -> Self {
+        self.max_errors = max_errors;
+        self
+    }
+
+    /// Starts at most `limit_rps` requests per second in each loop, so latencies are measured at
+    /// a fixed offered load rather than with requests queueing for the server at saturation
+    pub fn with_limit_rps(mut self, limit_rps: Option<f64>) -> Self {
+        self.limit_rps = limit_rps;
+        self
+    }
+
+    /// Writes every request's latency to `log` as it completes, failed ones included
+    pub fn with_latency_log(mut self, log: Option<LatencyLog>) -> Self {
+        self.latency_log = log;
+        self
+    }
+
+    /// Keeps the latency of only every `sample_every`th successful request, trading percentile
+    /// precision for less time and memory spent collecting them. The latency log and the live
+    /// metrics still see every request.
+    pub fn with_sample_every(mut self, sample_every: Option<usize>) -> Self {
+        self.sample_every = sample_every.unwrap_or(1).max(1);
+        self
+    }
+
+    /// Staggers the first request of each concurrency slot by a random delay of up to
+    /// `start_jitter`, so they don't all hit the server at the same instant. The delay comes
+    /// before the request's timing starts.
+    pub fn with_start_jitter(mut self, start_jitter: Option<Duration>) -> Self {
+        self.start_jitter = start_jitter.filter(|jitter| !jitter.is_zero());
+        self
+    }
+
+    /// Holds each concurrency slot for `think_time` after its request finishes, so the loop
+    /// models `concurrency` clients that pause between requests. The pause is outside the
+    /// request's latency but inside the operation's wall-clock time.
+    pub fn with_think_time(mut self, think_time: Option<Duration>) -> Self {
+        self.think_time = think_time.filter(|think_time| !think_time.is_zero());
+        self
+    }
+
+    /// Caps the requests in flight at once at `max_in_flight` across every loop sharing this
+    /// runner, whatever their concurrency. A request waits for a permit before its timing
+    /// starts, so the wait shows in the throughput rather than the latency.
+    pub fn with_max_in_flight(mut self, max_in_flight: Option<usize>) -> Self {
+        self.in_flight = max_in_flight.map(|permits| Arc::new(Semaphore::new(permits.max(1))));
+        self
+    }
+
+    // Waits for a permit to start a request, when in-flight requests are capped
+    async fn acquire(&self) -> Option<OwnedSemaphorePermit> {
+        let in_flight = self.in_flight.clone()?;
+        // the semaphore is never closed
+        in_flight.acquire_owned().await.ok()
+    }
+
+    /// Hides the progress bars, so nothing is drawn while operations run
+    pub fn with_quiet(mut self, quiet: bool) -> Self {
+        self.quiet = quiet;
+        self
+    }
+
+    fn set_draw_target(&self, pb: &ProgressBar) {
+        pb.set_draw_target(match self.quiet {
+            true => ProgressDrawTarget::hidden(),
+            false => ProgressDrawTarget::stderr_with_hz(PROGRESS_HZ),
+        });
+    }
+
+    /// Stops starting requests once an operation has run for `time_budget`, leaving it short of
+    /// its count. Runs bounded by a duration already stop on time and ignore this.
+    pub fn with_time_budget(self, time_budget: Option<Duration>) -> Self {
+        self.set_time_budget(time_budget);
+        self
+    }
+
+    pub fn set_time_budget(&self, time_budget: Option<Duration>) {
+        *self.time_budget.lock().unwrap() = time_budget;
+    }
+
+    pub fn time_budget(&self) -> Option<Duration> {
+        *self.time_budget.lock().unwrap()
+    }
+
+    pub fn set_concurrency(&self, concurrency: usize) {
+        self.concurrency.store(concurrency.max(1), Ordering::SeqCst);
+    }
+
+    /// When set, loops keep issuing requests until the duration has passed instead of
+    /// stopping after `count`, so operations must wrap their index around
+    pub fn set_duration(&self, duration: Option<Duration>) {
+        *self.duration.lock().unwrap() = duration;
+    }
+
+    pub fn duration(&self) -> Option<Duration> {
+        *self.duration.lock().unwrap()
+    }
+
+    /// Stops every running loop at its next iteration; requests already in flight still finish
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Whether the time budget stopped the current operation before its count
+    pub fn truncated(&self) -> bool {
+        self.truncated.load(Ordering::SeqCst)
+    }
+
+    /// Resets the request counters, latencies and timeline at the start of an operation
+    pub fn reset(&self) {
+        self.completed.store(0, Ordering::SeqCst);
+        self.errors.store(0, Ordering::SeqCst);
+        self.error_breakdown.lock().unwrap().clear();
+        self.truncated.store(false, Ordering::SeqCst);
+        self.latencies.lock().unwrap().clear();
+        *self.started.lock().unwrap() = Instant::now();
+        *self.timeline.lock().unwrap() = Timeline {
+            started: chrono::Utc::now(),
+            windows: Vec::new(),
+        };
+    }
+
+    /// Takes the latencies of the successful requests since the last `reset`, or of the sampled
+    /// ones with `with_sample_every`
+    pub fn take_latencies(&self) -> Vec<Duration> {
+        std::mem::take(&mut *self.latencies.lock().unwrap())
+    }
+
+    /// Takes the successful requests per second since the last `reset`
+    pub fn take_timeline(&self) -> Timeline {
+        std::mem::take(&mut *self.timeline.lock().unwrap())
+    }
+
+    // Counts a successful request and files it under the second it completed in. The live
+    // metrics are no-ops unless `--metrics-port` installed an exporter.
+    fn record_completion(&self, label: &str, latency: Duration) {
+        metrics::counter!(REQUESTS_METRIC, "operation" => label.to_string()).increment(1);
+        metrics::histogram!(LATENCY_METRIC, "operation" => label.to_string())
+            .record(latency.as_secs_f64());
+        let completed = self.completed.fetch_add(1, Ordering::Relaxed);
+        if completed.is_multiple_of(self.sample_every) {
+            self.latencies.lock().unwrap().push(latency);
+        }
+        let window = self.started.lock().unwrap().elapsed().as_secs() as usize;
+        let windows = &mut self.timeline.lock().unwrap().windows;
+        if windows.len() <= window {
+            windows.resize(window + 1, 0);
+        }
+        windows[window] += 1;
+    }
+
+    /// Requests completed since the last `reset`
+    pub fn completed(&self) -> usize {
+        self.completed.load(Ordering::SeqCst)
+    }
+
+    /// Requests failed since the last `reset`
+    pub fn errors(&self) -> usize {
+        self.errors.load(Ordering::SeqCst)
+    }
+
+    /// Takes the failed requests since the last `reset`, counted by what went wrong
+    pub fn take_error_breakdown(&self) -> BTreeMap<String, usize> {
+        std::mem::take(&mut *self.error_breakdown.lock().unwrap())
+    }
+
+    // Counts a failed request under `kind`, handing the error back once the operation should
+    // give up
+    fn record_error(&self, label: &str, kind: &str, error: anyhow::Error) -> Result<()> {
+        metrics::counter!(ERRORS_METRIC, "operation" => label.to_string()).increment(1);
+        let Some(max_errors) = self.max_errors else {
+            // failing before anything went through says the server can't be reached, rather
+            // than that some of its requests failed
+            return match self.completed() {
+                0 => Err(error),
+                _ => Err(error.context(RequestsFailed::FailFast)),
+            };
+        };
+        *self.error_breakdown.lock().unwrap().entry(kind.to_string()).or_default() += 1;
+        let errors = self.errors.fetch_add(1, Ordering::SeqCst) + 1;
+        debug!(%error, errors, "request failed");
+        if errors > max_errors {
+            return Err(error.context(RequestsFailed::Exceeded(errors)));
+        }
+        Ok(())
+    }
+
+    // Writes a request to the latency log, if there is one
+    fn log_latency(
+        &self,
+        label: &str,
+        index: usize,
+        latency: Duration,
+        success: bool,
+    ) -> Result<()> {
+        match &self.latency_log {
+            Some(log) => log.record(label, index, latency, success),
+            None => Ok(()),
+        }
+    }
+
+    /// Times a single request that is not part of a loop, such as a scan
+    pub async fn time<T>(&self, label: &str, fut: impl Future<Output = Result<T>>) -> Result<T> {
+        let permit = self.acquire().await;
+        let start = Instant::now();
+        let res = fut.await;
+        drop(permit);
+        self.log_latency(label, 0, start.elapsed(), res.is_ok())?;
+        let res = match res {
+            Ok(res) => res,
+            Err(e) => {
+                // a lone request has nothing to continue with, but the failure still counts
+                let _ = self.record_error(label, &error_kind(&e), anyhow::anyhow!("{:#}", e));
+                return Err(e);
+            }
+        };
+        self.record_completion(label, start.elapsed());
+        Ok(res)
+    }
+
+    /// Runs a scan page by page behind a spinner showing the rows seen so far and the rate,
+    /// timing the whole traversal as a single request. Returns the total rows seen, or an error
+    /// if the scan is checked and skipped or repeated a record.
+    pub async fn scan<F, Fut>(&self, scan: Scan, page: F) -> Result<usize>
+    where
+        F: Fn(Scan) -> Fut,
+        Fut: Future<Output = Result<usize>>,
+    {
+        let pb = ProgressBar::new_spinner();
+        pb.set_style(
+            ProgressStyle::default_spinner()
+                .template("{spinner:.green} [{elapsed_precise}] Scanning... {human_pos} rows ({per_sec})")
+                .unwrap(),
+        );
+        self.set_draw_target(&pb);
+        pb.enable_steady_tick(Duration::from_millis(100));
+        let check = scan.check();
+        let pages = scan.paginate(|scan| {
+            let fut = page(scan);
+            let pb = &pb;
+            async move {
+                let rows = fut.await?;
+                pb.inc(rows as u64);
+                Ok(rows)
+            }
+        });
+        let res = self.time("Scan", pages).await;
+        match &res {
+            Ok(rows) => {
+                info!(rows, "scan finished");
+                pb.finish_with_message("Scan complete");
+            }
+            Err(_) => pb.abandon_with_message("Scan failed"),
+        }
+        let (Some(check), Ok(rows)) = (check, &res) else {
+            return res;
+        };
+        let rows = *rows;
+        let audit = check.audit();
+        match audit.missing + audit.duplicates {
+            0 => {
+                info!(rows, untracked = audit.untracked, "scan returned every record once");
+                Ok(rows)
+            }
+            _ => {
+                warn!(
+                    missing = audit.missing,
+                    duplicates = audit.duplicates,
+                    untracked = audit.untracked,
+                    "scan skipped or repeated records"
+                );
+                Err(anyhow::anyhow!(
+                    "Scan missed {} records and returned {} more than once",
+                    audit.missing,
+                    audit.duplicates
+                ))
+            }
+        }
+    }
+
+    /// Runs `op` once for every index in `0..count`, keeping up to `concurrency` requests in
+    /// flight. Results are returned in index order regardless of completion order. If the run
+    /// is cancelled, only the results of the requests that were started are returned, and
+    /// with `max_errors` set, only those of the requests that succeeded.
+    ///
+    /// With a duration set the indices keep counting past `count` until it has passed; nothing
+    /// runs when `count` is zero either way.
+    pub async fn run<T, F, Fut>(&self, label: &str, count: usize, op: F) -> Result<Vec<T>>
+    where
+        F: Fn(usize) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let concurrency = self.concurrency.load(Ordering::SeqCst);
+        let deadline = self.duration().map(|duration| Instant::now() + duration);
+        // the budget runs from the start of the operation rather than of this loop
+        let budget = match deadline {
+            Some(_) => None,
+            None => self.time_budget().map(|budget| *self.started.lock().unwrap() + budget),
+        };
+        info!(operation = label, count, concurrency, "starting");
+
+        let pb = match deadline {
+            Some(_) => {
+                let pb = ProgressBar::new_spinner();
+                pb.set_style(
+                    ProgressStyle::default_spinner()
+                        .template(&format!(
+                            "{{spinner:.green}} [{{elapsed_precise}}] {{pos}} {}",
+                            label
+                        ))
+                        .unwrap(),
+                );
+                pb.enable_steady_tick(Duration::from_millis(100));
+                pb
+            }
+            None => {
+                let pb = ProgressBar::new(count as u64);
+                pb.set_style(
+                    ProgressStyle::default_bar()
+                        .template(&format!(
+                            "[{{elapsed_precise}}] {{bar:40.cyan/blue}} {{pos}}/{{len}} ({{eta}}) {}",
+                            label
+                        ))
+                        .unwrap()
+                        .progress_chars("##-"),
+                );
+                pb
+            }
+        };
+        self.set_draw_target(&pb);
+
+        let limit = match (count, deadline) {
+            (0, _) => 0,
+            (_, Some(_)) => usize::MAX,
+            (count, None) => count,
+        };
+        // Ticks spaced by the rate limit, or an endless run of immediate ones without it. Ticks
+        // missed while every slot was busy are skipped rather than sent as a burst.
+        let interval = self.limit_rps.map(|rps| {
+            let mut interval = tokio::time::interval(Duration::from_secs_f64(1.0 / rps));
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            interval
+        });
+        let ticks = stream::unfold(interval, |mut interval| async move {
+            if let Some(interval) = &mut interval {
+                interval.tick().await;
+            }
+            Some(((), interval))
+        });
+        let mut requests = std::pin::pin!(stream::iter(0..limit)
+            .zip(ticks)
+            .map(|(i, _)| i)
+            .take_while(|_| {
+                let expired = deadline.is_some_and(|deadline| Instant::now() >= deadline);
+                if budget.is_some_and(|budget| Instant::now() >= budget) {
+                    self.truncated.store(true, Ordering::SeqCst);
+                }
+                futures::future::ready(!expired && !self.truncated() && !self.is_cancelled())
+            })
+            .map(|i| {
+                let fut = op(i);
+                let jitter = self
+                    .start_jitter
+                    .filter(|_| i < concurrency)
+                    .map(|max| rand::thread_rng().gen_range(Duration::ZERO..=max));
+                let think_time = self.think_time;
+                async move {
+                    if let Some(jitter) = jitter {
+                        tokio::time::sleep(jitter).await;
+                    }
+                    let permit = self.acquire().await;
+                    let start = Instant::now();
+                    let res = fut.await;
+                    let latency = start.elapsed();
+                    drop(permit);
+                    if let Some(think_time) = think_time {
+                        tokio::time::sleep(think_time).await;
+                    }
+                    (i, latency, res)
+                }
+            })
+            .buffer_unordered(concurrency));
+        let mut results: Vec<(usize, T)> = Vec::new();
+        // the bar moves in steps, so a run of millions of requests doesn't update it for each
+        let step = (count / PROGRESS_STEPS).max(1) as u64;
+        let mut pending = 0;
+        while let Some((i, latency, res)) = requests.next().await {
+            if let Err(e) = self.log_latency(label, i, latency, res.is_ok()) {
+                pb.abandon_with_message(format!("{} failed", label));
+                return Err(e);
+            }
+            match res {
+                Ok(res) => {
+                    self.record_completion(label, latency);
+                    pending += 1;
+                    if pending == step {
+                        pb.inc(step);
+                        pending = 0;
+                    }
+                    results.push((i, res));
+                }
+                Err(e) => {
+                    if let Err(e) = self.record_error(label, &error_kind(&e), e) {
+                        pb.abandon_with_message(format!("{} failed", label));
+                        return Err(e);
+                    }
+                }
+            }
+        }
+        pb.inc(pending);
+        if self.is_cancelled() {
+            pb.abandon_with_message(format!("{} interrupted", label));
+        } else if self.truncated() {
+            info!(operation = label, completed = self.completed(), "time budget ran out");
+            pb.abandon_with_message(format!("{} truncated", label));
+        } else {
+            pb.finish_with_message(format!("{} complete", label));
+        }
+
+        results.sort_unstable_by_key(|(i, _)| *i);
+        Ok(results.into_iter().map(|(_, res)| res).collect())
+    }
+}
+
+// What a failed request is counted under: the HTTP status the server answered with, `timeout`
+// or `connection` when no answer came back, and `other` for failures that aren't HTTP's
+fn error_kind(error: &anyhow::Error) -> String {
+    for cause in error.chain() {
+        if let Some(e) = cause.downcast_ref::<StatusError>() {
+            return e.status.as_u16().to_string();
+        }
+        if let Some(e) = cause.downcast_ref::<reqwest::Error>() {
+            if let Some(status) = e.status() {
+                return status.as_u16().to_string();
+            }
+            if e.is_timeout() {
+                return "timeout".to_string();
+            }
+            if e.is_connect() || e.is_request() {
+                return "connection".to_string();
+            }
+        }
+    }
+    "other".to_string()
+}
+
+/// CSV of every request's latency, streamed through a buffer so long runs don't hold the rows
+/// in memory. Clones write to the same file.
+#[derive(Clone)]
+pub struct LatencyLog(Arc<Mutex<BufWriter<File>>>);
+
+impl LatencyLog {
+    pub fn create(path: &Path) -> Result<Self> {
+        let file = File::create(path)
+            .map_err(|e| anyhow::anyhow!("Failed to create {}: {}", path.display(), e))?;
+        let mut writer = BufWriter::new(file);
+        writeln!(writer, "operation,index,latency_us,success")?;
+        Ok(Self(Arc::new(Mutex::new(writer))))
+    }
+
+    fn record(&self, label: &str, index: usize, latency: Duration, success: bool) -> Result<()> {
+        let mut writer = self.0.lock().unwrap();
+        writeln!(writer, "{},{},{},{}", label, index, latency.as_micros(), success)?;
+        Ok(())
+    }
+
+    /// Writes out whatever is still buffered
+    pub fn flush(&self) -> Result<()> {
+        self.0.lock().unwrap().flush()?;
+        Ok(())
+    }
+}
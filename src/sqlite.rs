@@ -0,0 +1,629 @@
+use crate::runner::Runner;
+use crate::types::{
+    Benchmark, BenchmarkClient, BenchmarkEngine, FieldType, Projection, RecordSchema, Scan,
+    Workload, CLEANUP_BATCH,
+};
+use crate::utils::{parse_uuids, upsert_key};
+use anyhow::Result;
+use async_trait::async_trait;
+use rusqlite::types::Value as SqlValue;
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension};
+use serde_json::{Map, Value};
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use uuid::Uuid;
+
+// How a schema field is stored. SQLite has no boolean or date types, so bools are integers and
+// timestamps their RFC 3339 text.
+fn column_type(kind: FieldType) -> &'static str {
+    match kind {
+        FieldType::Int | FieldType::Bool => "INTEGER",
+        FieldType::Float => "REAL",
+        FieldType::String | FieldType::Timestamp => "TEXT",
+    }
+}
+
+fn sql_value(value: &Value) -> SqlValue {
+    match value {
+        Value::Bool(b) => SqlValue::Integer(*b as i64),
+        Value::Number(n) => match n.as_i64() {
+            Some(n) => SqlValue::Integer(n),
+            None => SqlValue::Real(n.as_f64().unwrap_or_default()),
+        },
+        Value::String(s) => SqlValue::Text(s.clone()),
+        _ => SqlValue::Null,
+    }
+}
+
+// A column read back as the JSON the schema checks, with integers in bool columns as bools
+fn json_value(kind: FieldType, value: SqlValue) -> Value {
+    match (kind, value) {
+        (FieldType::Bool, SqlValue::Integer(n)) => Value::from(n != 0),
+        (_, SqlValue::Integer(n)) => Value::from(n),
+        (_, SqlValue::Real(f)) => Value::from(f),
+        (_, SqlValue::Text(s)) => Value::from(s),
+        _ => Value::Null,
+    }
+}
+
+// The schema columns of `fields` as `, a, b` and their values, in the same order
+fn schema_columns(fields: &Map<String, Value>) -> (String, Vec<SqlValue>) {
+    let columns = fields.keys().map(|name| format!(", {}", name)).collect();
+    (columns, fields.values().map(sql_value).collect())
+}
+
+pub struct SqliteClient {
+    conn: Arc<Mutex<Connection>>,
+    runner: Runner,
+    workload: Workload,
+    ids: Vec<Uuid>,
+}
+
+impl SqliteClient {
+    pub fn new(conn: Connection, runner: Runner, workload: Workload) -> Self {
+        Self {
+            conn: Arc::new(Mutex::new(conn)),
+            runner,
+            workload,
+            ids: Vec::new(),
+        }
+    }
+
+    fn table(&self) -> &str {
+        self.workload.table()
+    }
+
+    // rusqlite is synchronous, so every statement runs on the blocking thread pool
+    async fn with_conn<T, F>(&self, f: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Connection) -> rusqlite::Result<T> + Send + 'static,
+    {
+        let conn = self.conn.clone();
+        let res = tokio::task::spawn_blocking(move || f(&conn.lock().unwrap())).await?;
+        res.map_err(Into::into)
+    }
+
+    async fn insert(&self, id: Uuid, data: &str) -> Result<()> {
+        let data = data.to_string();
+        if let Some(fields) = self.workload.fields() {
+            let (columns, values) = schema_columns(&fields);
+            let statement = format!(
+                "INSERT INTO {} (id, data{}) VALUES (?, ?{})",
+                self.table(),
+                columns,
+                ", ?".repeat(values.len())
+            );
+            let params = [SqlValue::Text(id.to_string()), SqlValue::Text(data)];
+            return self
+                .with_conn(move |conn| {
+                    conn.prepare_cached(&statement)?
+                        .execute(params_from_iter(params.into_iter().chain(values)))
+                        .map(|_| ())
+                })
+                .await;
+        }
+        let statement = format!("INSERT INTO {} (id, data) VALUES (?1, ?2)", self.table());
+        self.with_conn(move |conn| {
+            conn.prepare_cached(&statement)?
+                .execute(params![id.to_string(), data])
+                .map(|_| ())
+        })
+        .await
+    }
+
+    async fn update_fields(
+        &self,
+        id: Uuid,
+        data: String,
+        fields: &Map<String, Value>,
+    ) -> Result<()> {
+        let (columns, mut values) = schema_columns(fields);
+        let statement = format!(
+            "UPDATE {} SET (data{}) = (?{}) WHERE id = ?",
+            self.table(),
+            columns,
+            ", ?".repeat(values.len())
+        );
+        values.insert(0, SqlValue::Text(data));
+        values.push(SqlValue::Text(id.to_string()));
+        self.with_conn(move |conn| {
+            conn.prepare_cached(&statement)?.execute(params_from_iter(values)).map(|_| ())
+        })
+        .await
+    }
+
+    async fn upsert_fields(
+        &self,
+        id: Uuid,
+        data: String,
+        fields: &Map<String, Value>,
+    ) -> Result<usize> {
+        let (columns, values) = schema_columns(fields);
+        let statement = format!(
+            "INSERT INTO {} (id, data{}) VALUES (?, ?{}) \
+            ON CONFLICT(id) DO UPDATE SET (data{}) = (excluded.data{})",
+            self.table(),
+            columns,
+            ", ?".repeat(values.len()),
+            columns,
+            columns.replace(", ", ", excluded."),
+        );
+        let params = [SqlValue::Text(id.to_string()), SqlValue::Text(data)];
+        self.with_conn(move |conn| {
+            conn.prepare_cached(&statement)?
+                .execute(params_from_iter(params.into_iter().chain(values)))
+        })
+        .await
+    }
+
+    // The record's data and schema fields, read back for checking
+    async fn select_fields(
+        &self,
+        schema: &RecordSchema,
+        id: Uuid,
+    ) -> Result<Option<(String, Map<String, Value>)>> {
+        let columns: String = schema.names().map(|name| format!(", {}", name)).collect();
+        let statement = format!("SELECT data{} FROM {} WHERE id = ?1", columns, self.table());
+        let fields = schema.fields.clone();
+        self.with_conn(move |conn| {
+            conn.prepare_cached(&statement)?
+                .query_row(params![id.to_string()], |row| {
+                    let mut record = Map::new();
+                    for (i, field) in fields.iter().enumerate() {
+                        let value = json_value(field.kind, row.get(i + 1)?);
+                        record.insert(field.name.clone(), value);
+                    }
+                    Ok((row.get(0)?, record))
+                })
+                .optional()
+        })
+        .await
+    }
+
+    async fn select(&self, id: Uuid) -> Result<Option<String>> {
+        let statement = format!("SELECT data FROM {} WHERE id = ?1", self.table());
+        self.with_conn(move |conn| {
+            conn.prepare_cached(&statement)?
+                .query_row(params![id.to_string()], |row| row.get(0))
+                .optional()
+        })
+        .await
+    }
+
+    async fn select_id(&self, id: Uuid) -> Result<Option<String>> {
+        let statement = format!("SELECT id FROM {} WHERE id = ?1", self.table());
+        self.with_conn(move |conn| {
+            conn.prepare_cached(&statement)?
+                .query_row(params![id.to_string()], |row| row.get(0))
+                .optional()
+        })
+        .await
+    }
+
+    async fn remove(&self, id: Uuid) -> Result<()> {
+        let statement = format!("DELETE FROM {} WHERE id = ?1", self.table());
+        self.with_conn(move |conn| {
+            conn.prepare_cached(&statement)?
+                .execute(params![id.to_string()])
+                .map(|_| ())
+        })
+        .await
+    }
+}
+
+#[async_trait]
+impl BenchmarkClient for SqliteClient {
+    async fn startup(&self) -> Result<()> {
+        let table = self.table().to_string();
+        // the index is named after the table, so each dataset gets its own
+        let index = match self.workload.data_index {
+            Some(true) => Some(format!("CREATE INDEX IF NOT EXISTS {0}_data ON {0} (data)", table)),
+            Some(false) => Some(format!("DROP INDEX IF EXISTS {}_data", table)),
+            None => None,
+        };
+        let schema = self.workload.schema.clone();
+        self.with_conn(move |conn| {
+            conn.execute(
+                &format!("CREATE TABLE IF NOT EXISTS {} (id TEXT PRIMARY KEY, data TEXT)", table),
+                [],
+            )?;
+            if let Some(index) = index {
+                conn.execute(&index, [])?;
+            }
+            // schema fields are added as nullable columns, leaving records without them be
+            if let Some(schema) = schema {
+                let existing: HashSet<String> = conn
+                    .prepare(&format!("PRAGMA table_info({})", table))?
+                    .query_map([], |row| row.get::<_, String>(1))?
+                    .map(|name| name.map(|name| name.to_ascii_lowercase()))
+                    .collect::<rusqlite::Result<_>>()?;
+                for field in &schema.fields {
+                    if !existing.contains(&field.name.to_ascii_lowercase()) {
+                        let ty = column_type(field.kind);
+                        let statement =
+                            format!("ALTER TABLE {} ADD COLUMN {} {}", table, field.name, ty);
+                        conn.execute(&statement, [])?;
+                    }
+                }
+            }
+            Ok(())
+        })
+        .await
+    }
+
+    async fn create_records(&mut self, count: usize) -> Result<()> {
+        let new_ids = self.workload.new_ids();
+        let this = &*self;
+        let ids = this
+            .runner
+            .run("Create", count, |i| async move {
+                let id = new_ids.id(i);
+                this.insert(id, this.workload.value(i)).await?;
+                Ok(id)
+            })
+            .await?;
+        self.ids.extend(ids);
+        Ok(())
+    }
+
+    async fn read_records(&self) -> Result<()> {
+        let keys = self.workload.read_keys(self.ids.len())?;
+        let keys = &keys;
+        self.runner
+            .run("Read", keys.len(), |i| async move {
+                let key = keys[i % keys.len()];
+                if let Projection::Id = self.workload.read_projection {
+                    let id = self.select_id(self.ids[key]).await?;
+                    if id.is_none() && self.workload.verifies(i) {
+                        return Err(anyhow::anyhow!("Record {} wasn't found", self.ids[key]));
+                    }
+                    return Ok(());
+                }
+                let data = match &self.workload.schema {
+                    Some(schema) => {
+                        let record = self.select_fields(schema, self.ids[key]).await?;
+                        if let (Some(schema), Some((_, fields))) =
+                            (self.workload.checked_schema(i), &record)
+                        {
+                            schema.validate(&self.ids[key].to_string(), fields)?;
+                        }
+                        record.map(|(data, _)| data)
+                    }
+                    None => self.select(self.ids[key]).await?,
+                };
+                if let Some(expected) = self.workload.checked_value(i, key) {
+                    if data.as_deref() != Some(expected) {
+                        let id = &self.ids[key];
+                        return Err(anyhow::anyhow!("Read of {} returned the wrong data", id));
+                    }
+                }
+                Ok(())
+            })
+            .await?;
+        Ok(())
+    }
+
+    async fn update_records(&self) -> Result<()> {
+        let order = &self.workload.working_set(self.ids.len());
+        self.runner
+            .run("Update", self.ids.len(), |i| async move {
+                let id = self.ids[order[i % order.len()]];
+                let data = self.workload.updated_value(i).to_string();
+                if let Some(fields) = self.workload.fields() {
+                    return self.update_fields(id, data, &fields).await;
+                }
+                let statement = format!("UPDATE {} SET data = ?1 WHERE id = ?2", self.table());
+                self.with_conn(move |conn| {
+                    conn.prepare_cached(&statement)?
+                        .execute(params![data, id.to_string()])
+                        .map(|_| ())
+                })
+                .await
+            })
+            .await?;
+        Ok(())
+    }
+
+    async fn upsert_records(&mut self, count: usize) -> Result<()> {
+        let new_ids = self.workload.new_ids();
+        let this = &*self;
+        let ids = this
+            .runner
+            .run("Upsert", count, |i| async move {
+                let existing = upsert_key(&this.ids, i);
+                let id = existing.unwrap_or_else(|| new_ids.id(i));
+                let data = this.workload.value(i).to_string();
+                match this.workload.fields() {
+                    Some(fields) => this.upsert_fields(id, data, &fields).await?,
+                    None => {
+                        let statement = format!(
+                            "INSERT INTO {} (id, data) VALUES (?1, ?2) \
+                            ON CONFLICT(id) DO UPDATE SET data = excluded.data",
+                            this.table()
+                        );
+                        this.with_conn(move |conn| {
+                            conn.prepare_cached(&statement)?
+                                .execute(params![id.to_string(), data])
+                        })
+                        .await?
+                    }
+                };
+                Ok(existing.is_none().then_some(id))
+            })
+            .await?;
+        self.ids.extend(ids.into_iter().flatten());
+        Ok(())
+    }
+
+    async fn delete_records(&self) -> Result<()> {
+        let order = &self.workload.order(self.ids.len());
+        self.runner
+            .run("Delete", self.ids.len(), |i| {
+                self.remove(self.ids[order[i % order.len()]])
+            })
+            .await?;
+        Ok(())
+    }
+
+    // One transaction per batch, so each batch costs a single sync
+    async fn cleanup(&mut self) -> Result<()> {
+        for batch in self.ids.chunks(CLEANUP_BATCH) {
+            let ids: Vec<String> = batch.iter().map(Uuid::to_string).collect();
+            let statement = format!("DELETE FROM {} WHERE id = ?1", self.table());
+            self.with_conn(move |conn| {
+                conn.execute_batch("BEGIN")?;
+                let mut stmt = conn.prepare_cached(&statement)?;
+                for id in &ids {
+                    stmt.execute(params![id])?;
+                }
+                conn.execute_batch("COMMIT")
+            })
+            .await?;
+        }
+        self.ids.clear();
+        Ok(())
+    }
+
+    async fn scan_records(&self) -> Result<()> {
+        let scan = self.workload.scan(&self.ids);
+        self.runner.scan(scan, |scan| async move { self.scan(&scan).await }).await?;
+        Ok(())
+    }
+
+    async fn read_records_by_data(&self) -> Result<()> {
+        let keys = self.workload.read_keys(self.ids.len())?;
+        let keys = &keys;
+        self.runner
+            .run("Read by data", keys.len(), |i| async move {
+                let data = self.workload.value(keys[i % keys.len()]).to_string();
+                let value = data.clone();
+                let statement = format!("SELECT COUNT(*) FROM {} WHERE data = ?1", self.table());
+                let matches: i64 = self
+                    .with_conn(move |conn| {
+                        conn.prepare_cached(&statement)?
+                            .query_row(params![data], |row| row.get(0))
+                    })
+                    .await?;
+                if matches == 0 && self.workload.verifies(i) {
+                    return Err(anyhow::anyhow!("No record holds the data {:?}", value));
+                }
+                Ok(())
+            })
+            .await?;
+        Ok(())
+    }
+
+    async fn transaction(&mut self, _count: usize) -> Result<()> {
+        Err(anyhow::anyhow!("txn isn't supported for SQLite"))
+    }
+
+    async fn batch_read(&self) -> Result<()> {
+        Err(anyhow::anyhow!("batch_read isn't supported for SQLite"))
+    }
+
+    async fn count_records(&self) -> Result<usize> {
+        let statement = format!("SELECT COUNT(*) FROM {}", self.table());
+        self.with_conn(move |conn| {
+            conn.query_row(&statement, [], |row| row.get::<_, i64>(0))
+                .map(|count| count as usize)
+        })
+        .await
+    }
+
+    fn tracked_ids(&self) -> usize {
+        self.ids.len()
+    }
+
+    fn load_ids(&mut self, ids: &[String]) -> Result<()> {
+        self.ids.extend(parse_uuids(ids)?);
+        Ok(())
+    }
+
+    async fn reset(&mut self, purge: bool) -> Result<()> {
+        if purge {
+            return Err(anyhow::anyhow!("--purge isn't supported for SQLite"));
+        }
+        self.ids.clear();
+        Ok(())
+    }
+
+    async fn create_record(&self, data: &str) -> Result<String> {
+        let id = self.workload.new_ids().id(0);
+        self.insert(id, data).await?;
+        Ok(id.to_string())
+    }
+
+    async fn read_record(&self, key: &str) -> Result<()> {
+        let id = key.parse::<Uuid>()?;
+        match self.workload.read_projection {
+            Projection::Id => self.select_id(id).await?,
+            _ => self.select(id).await?,
+        };
+        Ok(())
+    }
+
+    async fn update_record(&self, key: &str, data: &str) -> Result<()> {
+        let (id, data) = (key.parse::<Uuid>()?, data.to_string());
+        let statement = format!("UPDATE {} SET data = ?1 WHERE id = ?2", self.table());
+        self.with_conn(move |conn| {
+            conn.prepare_cached(&statement)?
+                .execute(params![data, id.to_string()])
+                .map(|_| ())
+        })
+        .await
+    }
+
+    async fn delete_record(&self, key: &str) -> Result<()> {
+        self.remove(key.parse::<Uuid>()?).await
+    }
+
+    async fn create_vectors(&self, _count: usize) -> Result<()> {
+        Err(anyhow::anyhow!("vectors aren't supported for SQLite"))
+    }
+
+    async fn search_vectors(&self, _count: usize) -> Result<()> {
+        Err(anyhow::anyhow!("vectors aren't supported for SQLite"))
+    }
+
+    async fn dry_run(&mut self) -> Vec<(String, Result<()>)> {
+        let id = Uuid::new_v4();
+        let mut checks = Vec::new();
+
+        let created = self.insert(id, "test_value").await;
+        let create_ok = created.is_ok();
+        checks.push(("INSERT".to_string(), created));
+
+        let read = if create_ok {
+            match self.select(id).await {
+                Ok(Some(data)) if data == "test_value" => Ok(()),
+                Ok(data) => Err(anyhow::anyhow!("unexpected row: {:?}", data)),
+                Err(e) => Err(e),
+            }
+        } else {
+            Err(anyhow::anyhow!("skipped, create failed"))
+        };
+        checks.push(("SELECT".to_string(), read));
+
+        let deleted = if create_ok {
+            self.remove(id).await
+        } else {
+            Err(anyhow::anyhow!("skipped, create failed"))
+        };
+        checks.push(("DELETE".to_string(), deleted));
+
+        checks
+    }
+}
+
+impl SqliteClient {
+    async fn scan(&self, scan: &Scan) -> Result<usize> {
+        let limit = scan.limit.unwrap_or(100) as i64;
+        let offset = scan.start.unwrap_or(0) as i64;
+        let columns = match scan.projection()? {
+            Projection::Count => return self.count_records().await,
+            Projection::Id => "id",
+            Projection::Full => "id, data",
+        };
+        let query = format!("SELECT {} FROM {} LIMIT ?1 OFFSET ?2", columns, self.table());
+        let checking = scan.checking();
+        let (count, ids) = self
+            .with_conn(move |conn| {
+                let mut stmt = conn.prepare_cached(&query)?;
+                let mut rows = stmt.query(params![limit, offset])?;
+                let (mut count, mut ids) = (0, Vec::new());
+                while let Some(row) = rows.next()? {
+                    count += 1;
+                    if checking {
+                        ids.push(row.get(0)?);
+                    }
+                }
+                Ok((count, ids))
+            })
+            .await?;
+        scan.saw(ids);
+        Ok(count)
+    }
+}
+
+// Engine for SQLite, the endpoint is the database file path
+pub struct SqliteEngine {
+    path: String,
+    runner: Runner,
+    workload: Workload,
+}
+
+#[async_trait]
+impl BenchmarkEngine for SqliteEngine {
+    async fn setup(options: &Benchmark) -> Result<Self> {
+        let path = options
+            .endpoint
+            .as_deref()
+            .unwrap_or(":memory:")
+            .to_string();
+        Ok(Self {
+            path,
+            runner: options.runner.clone(),
+            workload: options.workload.clone(),
+        })
+    }
+
+    async fn create_client(&self) -> Result<Box<dyn BenchmarkClient>> {
+        let conn = Connection::open(&self.path)?;
+        let client = SqliteClient::new(conn, self.runner.clone(), self.workload.clone());
+        client.startup().await?;
+        Ok(Box::new(client))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{BenchConfig, Database, SchemaField};
+
+    // A client on a fresh in-memory database, keeping records in `table`
+    async fn client(table: &str) -> SqliteClient {
+        let options = BenchConfig {
+            dataset_name: Some(table.to_string()),
+            ..Default::default()
+        };
+        let runner = Runner::new(1, None).with_quiet(true);
+        let benchmark = crate::resolve_benchmark(Database::Sqlite, &options, runner).unwrap();
+        let conn = Connection::open_in_memory().unwrap();
+        SqliteClient::new(conn, benchmark.runner, benchmark.workload)
+    }
+
+    #[tokio::test]
+    async fn statements_use_the_dataset_table() {
+        let mut client = client("items").await;
+        client.startup().await.unwrap();
+        client.create_records(3).await.unwrap();
+        client.update_records().await.unwrap();
+        let tables: Vec<String> = client
+            .with_conn(|conn| {
+                conn.prepare("SELECT name FROM sqlite_master WHERE type = 'table'")?
+                    .query_map([], |row| row.get(0))?
+                    .collect()
+            })
+            .await
+            .unwrap();
+        assert_eq!(tables, ["items"]);
+        assert_eq!(client.count_records().await.unwrap(), 3);
+    }
+
+    #[tokio::test]
+    async fn a_schema_field_named_records_stays_a_column() {
+        let mut client = client("items").await;
+        let field = SchemaField {
+            name: "records".to_string(),
+            kind: FieldType::Int,
+            length: None,
+        };
+        client.workload.schema = Some(Arc::new(RecordSchema { fields: vec![field] }));
+        client.startup().await.unwrap();
+        client.create_records(2).await.unwrap();
+        client.upsert_records(2).await.unwrap();
+        client.read_records().await.unwrap();
+        assert_eq!(client.count_records().await.unwrap(), 3);
+    }
+}
@@ -1,11 +1,615 @@
+use crate::runner::Runner;
+use crate::utils::{
+    generate_field, is_identifier, seeded_rng, ConnectionCounter, IdSource, PayloadTemplate,
+    PhaseTimer, Zipf,
+};
 use anyhow::Result;
 use async_trait::async_trait;
+use clap::{Args, ValueEnum};
+use rand::seq::SliceRandom;
+use rand::Rng;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
-// Represents the database to benchmark
-#[derive(Clone, Copy, PartialEq)]
+// Represents the database to benchmark. Names are given explicitly, since clap would
+// otherwise split `HelixDB` into `helix-db`.
+#[derive(Clone, Copy, PartialEq, Default, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Database {
+    #[default]
+    #[value(name = "helixdb")]
     HelixDB,
     Neo4j,
+    Sqlite,
+    Memgraph,
+    Dgraph,
+    #[value(name = "clickhouse")]
+    ClickHouse,
+    Weaviate,
+    /// MySQL or MariaDB
+    #[value(name = "mysql", alias = "mariadb")]
+    #[serde(alias = "mariadb")]
+    MySql,
+    /// Cassandra or ScyllaDB
+    #[value(alias = "scylla", alias = "scylladb")]
+    #[serde(alias = "scylla", alias = "scylladb")]
+    Cassandra,
+    Milvus,
+    #[value(name = "opensearch")]
+    OpenSearch,
+    #[value(name = "duckdb")]
+    DuckDB,
+    #[value(name = "libsql")]
+    LibSQL,
+}
+
+// Floating point precision used for generated vectors
+#[derive(Clone, Copy, PartialEq, Default, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Precision {
+    F32,
+    #[default]
+    F64,
+}
+
+// Distance that vector searches rank results by
+#[derive(Clone, Copy, PartialEq, Default, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VectorMetric {
+    #[default]
+    Cosine,
+    /// Squared L2 distance
+    Euclidean,
+    /// Negated dot product, so larger products rank nearer
+    Dot,
+}
+
+impl VectorMetric {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            VectorMetric::Cosine => "cosine",
+            VectorMetric::Euclidean => "euclidean",
+            VectorMetric::Dot => "dot",
+        }
+    }
+}
+
+// What an update writes
+#[derive(Clone, Copy, PartialEq, Default, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UpdateMode {
+    /// Only the changed `data` field
+    #[default]
+    Partial,
+    /// The whole record, replacing every field
+    Full,
+}
+
+// An operation `bench all` can run
+#[derive(Clone, Copy, PartialEq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Operation {
+    Create,
+    Read,
+    Update,
+    Upsert,
+    Delete,
+    Scan,
+    #[value(name = "read_by_data")]
+    ReadByData,
+    #[value(name = "create_vectors")]
+    CreateVectors,
+    #[value(name = "search_vectors")]
+    SearchVectors,
+    /// Creates, reads back and updates `--ops-per-txn` records in one transaction
+    Txn,
+    /// Reads `--batch-size` records per request
+    #[value(name = "batch_read", alias = "batch-read")]
+    BatchRead,
+}
+
+impl Operation {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Operation::Create => "create",
+            Operation::Read => "read",
+            Operation::Update => "update",
+            Operation::Upsert => "upsert",
+            Operation::Delete => "delete",
+            Operation::Scan => "scan",
+            Operation::ReadByData => "read_by_data",
+            Operation::CreateVectors => "create_vectors",
+            Operation::SearchVectors => "search_vectors",
+            Operation::Txn => "txn",
+            Operation::BatchRead => "batch_read",
+        }
+    }
+
+    /// Whether the operation works on records created earlier in the run
+    pub fn needs_records(&self) -> bool {
+        matches!(
+            self,
+            Operation::Read
+                | Operation::Update
+                | Operation::Delete
+                | Operation::Scan
+                | Operation::ReadByData
+                | Operation::BatchRead
+        )
+    }
+
+    /// Whether the operation writes or searches vectors, which not every engine supports
+    pub fn is_vector(&self) -> bool {
+        matches!(self, Operation::CreateVectors | Operation::SearchVectors)
+    }
+}
+
+// How the byte lengths of generated values vary
+#[derive(Clone, Copy, PartialEq, Default, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ValueSizeDistribution {
+    /// Every value is `--value-size` bytes
+    #[default]
+    Const,
+    /// Evenly spread within `--value-size-spread` bytes either side
+    Uniform,
+    /// Normally distributed, with `--value-size-spread` as the standard deviation
+    Normal,
+    /// Exponentially distributed with `--value-size` as the mean: mostly small, a few large
+    Exponential,
+}
+
+// Which records reads go to
+#[derive(Clone, Copy, PartialEq, Default, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReadDistribution {
+    /// Every record in creation order, wrapping around
+    #[default]
+    Sequential,
+    /// Records picked uniformly at random
+    Uniform,
+    /// A few hot records take most of the reads
+    Zipf,
+}
+
+// Type of a `--schema` field, and what its generated values look like
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FieldType {
+    /// Integers from 0 to 1,000,000
+    Int,
+    /// Random alphanumeric text of the field's `length`
+    String,
+    /// Floats from 0 to 1,000,000
+    Float,
+    Bool,
+    /// A moment in the past year, as RFC 3339 text
+    Timestamp,
+}
+
+impl FieldType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FieldType::Int => "int",
+            FieldType::String => "string",
+            FieldType::Float => "float",
+            FieldType::Bool => "bool",
+            FieldType::Timestamp => "timestamp",
+        }
+    }
+
+    /// Whether `value` could have been generated for a field of this type
+    pub fn matches(&self, value: &Value) -> bool {
+        match self {
+            FieldType::Int => value.is_i64() || value.is_u64(),
+            FieldType::String => value.is_string(),
+            FieldType::Float => value.is_number(),
+            FieldType::Bool => value.is_boolean(),
+            FieldType::Timestamp => value
+                .as_str()
+                .is_some_and(|s| chrono::DateTime::parse_from_rfc3339(s).is_ok()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SchemaField {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub kind: FieldType,
+    /// Characters in generated strings [default: 16]
+    #[serde(default)]
+    pub length: Option<usize>,
+}
+
+/// Fields records carry besides `data`, from the YAML or JSON file given to `--schema`:
+///
+///     fields:
+///       - {name: title, type: string, length: 32}
+///       - {name: price, type: float}
+///       - {name: created, type: timestamp}
+///
+/// Engines that support it store each field as a property or column of its own.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RecordSchema {
+    pub fields: Vec<SchemaField>,
+}
+
+impl RecordSchema {
+    /// Loads a schema, which YAML or JSON alike parse as, and checks its field names can be
+    /// used as column names
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("Failed to read schema {}: {}", path.display(), e))?;
+        let schema: Self = serde_yaml::from_str(&contents)
+            .map_err(|e| anyhow::anyhow!("Invalid schema {}: {}", path.display(), e))?;
+        if schema.fields.is_empty() {
+            return Err(anyhow::anyhow!("Schema {} has no fields", path.display()));
+        }
+        let mut names = HashSet::new();
+        for field in &schema.fields {
+            let name = field.name.as_str();
+            if !is_identifier(name) {
+                return Err(anyhow::anyhow!(
+                    "Schema field {:?} must be letters, digits and underscores",
+                    name
+                ));
+            }
+            if name.eq_ignore_ascii_case("id") || name.eq_ignore_ascii_case("data") {
+                return Err(anyhow::anyhow!("Schema field {:?} clashes with a built-in one", name));
+            }
+            if !names.insert(name.to_ascii_lowercase()) {
+                return Err(anyhow::anyhow!("Schema field {:?} is declared twice", name));
+            }
+        }
+        Ok(schema)
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.fields.iter().map(|field| field.name.as_str())
+    }
+
+    /// Fresh values for every field, for a record being created or updated
+    pub fn record(&self) -> Map<String, Value> {
+        let mut rng = rand::thread_rng();
+        self.fields
+            .iter()
+            .map(|field| (field.name.clone(), generate_field(field, &mut rng)))
+            .collect()
+    }
+
+    /// Checks that the record `id` was read back with a value of the right type in every field
+    pub fn validate(&self, id: &str, record: &Map<String, Value>) -> Result<()> {
+        for field in &self.fields {
+            match record.get(&field.name) {
+                Some(value) if field.kind.matches(value) => {}
+                Some(value) => {
+                    return Err(anyhow::anyhow!(
+                        "Read of {} returned {} for the {} field {}",
+                        id,
+                        value,
+                        field.kind.as_str(),
+                        field.name
+                    ));
+                }
+                None => {
+                    return Err(anyhow::anyhow!("Read of {} has no field {}", id, field.name));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// Shape of the generated workload, shared by every client
+#[derive(Clone, Default)]
+pub struct Workload {
+    pub projection: Projection,
+    /// What reads return, `Id` to time the lookup without serializing the record
+    pub read_projection: Projection,
+    pub scan_page_size: Option<usize>,
+    /// Gather the ids scans return and check them against the tracked ones
+    pub verify_scan: bool,
+    /// Pool that record data is drawn from, `None` keeps the fixed test values
+    pub values: Option<Arc<Vec<String>>>,
+    /// Body HelixDB creates records with instead of the single `data` field
+    pub payload: Option<PayloadTemplate>,
+    /// Fields records carry besides `data`, on the engines that support it
+    pub schema: Option<Arc<RecordSchema>>,
+    /// Table or label records are kept under instead of the engine's own, `None` for that
+    pub dataset_name: Option<String>,
+    /// Records are tracked from `--ids-file`, so what data they hold isn't known
+    pub external_ids: bool,
+    pub read_verify: ReadVerify,
+    /// Reads per checked read when sampling
+    pub read_verify_every: usize,
+    pub read_distribution: ReadDistribution,
+    pub zipf_exponent: f64,
+    /// Reads per read operation, `None` reads as many as there are records
+    pub read_count: Option<usize>,
+    /// Go through the tracked records in a seeded random order rather than as they were created
+    pub shuffle: bool,
+    /// Records reads and updates are confined to, `None` for all of them
+    pub keyspace_size: Option<usize>,
+    pub seed: Option<u64>,
+    /// Seed new record ids are derived from, `None` for random ones
+    pub id_seed: Option<u64>,
+    /// Operations that have taken new ids so far, shared by every client
+    pub id_sources: Arc<AtomicU64>,
+    pub update_mode: UpdateMode,
+    /// Create (`Some(true)`) or drop (`Some(false)`) the secondary index on `data` at startup,
+    /// or leave whatever is there
+    pub data_index: Option<bool>,
+    /// Components per generated vector
+    pub dims: usize,
+    /// Results requested per vector search
+    pub k: usize,
+    pub metric: VectorMetric,
+    /// Keep created vectors client-side so recall can be checked against them
+    pub track_vectors: bool,
+    /// How searches check the number of results they get back
+    pub search_results: SearchResults,
+    /// Vectors created so far in the run, shared by every client
+    pub vectors_created: Arc<AtomicUsize>,
+    /// Records each `txn` transaction creates, reads back and updates
+    pub ops_per_txn: usize,
+    /// Records each `batch_read` request asks for
+    pub batch_size: usize,
+}
+
+impl Workload {
+    /// The table records are kept in by the SQL and CQL engines, `--dataset-name` or `records`
+    pub fn table(&self) -> &str {
+        self.dataset_name.as_deref().unwrap_or("records")
+    }
+
+    /// Data written by the `i`th create
+    pub fn value(&self, i: usize) -> &str {
+        match &self.values {
+            Some(values) => &values[i % values.len()],
+            None => "test_value",
+        }
+    }
+
+    /// Data the `key`th tracked record should hold when read back, `None` if the benchmark
+    /// didn't write it from the value pool
+    pub fn expected_value(&self, key: usize) -> Option<&str> {
+        match self.external_ids || self.payload.is_some() {
+            true => None,
+            false => Some(self.value(key)),
+        }
+    }
+
+    /// Whether the `i`th read of an operation checks what it got back
+    pub fn verifies(&self, i: usize) -> bool {
+        match self.read_verify {
+            ReadVerify::Off => false,
+            ReadVerify::Sample => i.is_multiple_of(self.read_verify_every.max(1)),
+            ReadVerify::Strict => true,
+        }
+    }
+
+    /// Data the `i`th read, of the `key`th tracked record, should find, `None` if that read
+    /// isn't checked
+    pub fn checked_value(&self, i: usize, key: usize) -> Option<&str> {
+        self.expected_value(key).filter(|_| self.verifies(i))
+    }
+
+    /// The schema the `i`th read checks the fields it got back against, `None` if there is no
+    /// schema or that read isn't checked
+    pub fn checked_schema(&self, i: usize) -> Option<&RecordSchema> {
+        self.schema.as_deref().filter(|_| self.verifies(i))
+    }
+
+    /// Counts a vector created in the run toward the results searches expect
+    pub fn vector_created(&self) {
+        self.vectors_created.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Checks the number of results a vector search got back: never fewer than `k`, or than
+    /// the vectors created in the run when there are fewer of those, and under `exact` never
+    /// more than `k`
+    pub fn check_search_results(&self, found: usize) -> Result<()> {
+        let created = self.vectors_created.load(Ordering::Relaxed);
+        let expected = match created {
+            0 => self.k,
+            created => self.k.min(created),
+        };
+        let too_many = self.search_results == SearchResults::Exact && found > self.k;
+        if self.search_results != SearchResults::Off && (found < expected || too_many) {
+            return Err(anyhow::anyhow!(
+                "Vector search returned {} results, expected {}",
+                found,
+                expected
+            ));
+        }
+        Ok(())
+    }
+
+    /// Ids for the records an operation creates, each call counting as the next operation
+    pub fn new_ids(&self) -> IdSource {
+        IdSource::new(self.id_seed, self.id_sources.fetch_add(1, Ordering::SeqCst))
+    }
+
+    /// Values for the schema's fields in a record being written, `None` without a schema
+    pub fn fields(&self) -> Option<Map<String, Value>> {
+        self.schema.as_ref().map(|schema| schema.record())
+    }
+
+    /// Data written by the `i`th update, always different from what the create wrote when
+    /// the pool has more than one value
+    pub fn updated_value(&self, i: usize) -> &str {
+        match &self.values {
+            Some(values) => &values[(i + 1) % values.len()],
+            None => "updated_value",
+        }
+    }
+
+    /// Indices of the `keys` tracked records in the order an operation goes through them:
+    /// as they were created, or shuffled under `--shuffle`
+    pub fn order(&self, keys: usize) -> Vec<usize> {
+        let mut order: Vec<usize> = (0..keys).collect();
+        if self.shuffle {
+            order.shuffle(&mut seeded_rng(self.seed));
+        }
+        order
+    }
+
+    /// The tracked records reads and updates go through, in `order`: all `keys` of them, or
+    /// only the first `--keyspace-size`
+    pub fn working_set(&self, keys: usize) -> Vec<usize> {
+        let mut order = self.order(keys);
+        order.truncate(self.keyspace_size.unwrap_or(keys));
+        order
+    }
+
+    /// Indices into the `keys` tracked records for a read operation, sampled up front so the
+    /// sampling stays out of the timings
+    pub fn read_keys(&self, keys: usize) -> Result<Vec<usize>> {
+        if keys == 0 {
+            return Ok(Vec::new());
+        }
+        let count = self.read_count.unwrap_or(keys);
+        let hot = self.working_set(keys);
+        let mut rng = seeded_rng(self.seed);
+        Ok(match self.read_distribution {
+            ReadDistribution::Sequential => (0..count).map(|i| hot[i % hot.len()]).collect(),
+            ReadDistribution::Uniform => {
+                (0..count).map(|_| hot[rng.gen_range(0..hot.len())]).collect()
+            }
+            ReadDistribution::Zipf => {
+                let zipf = Zipf::new(hot.len(), self.zipf_exponent)?;
+                (0..count).map(|_| hot[zipf.sample(&mut rng)]).collect()
+            }
+        })
+    }
+
+    /// The first page of a scan over the tracked `ids`: all of them at once unless a page size
+    /// is set
+    pub fn scan<T: ToString>(&self, ids: &[T]) -> Scan {
+        let scan = match self.scan_page_size {
+            Some(size) => Scan::new(Some(size), Some(0), self.projection),
+            None => Scan::new(Some(ids.len()), None, self.projection),
+        };
+        match (self.verify_scan, self.projection) {
+            (true, Projection::Id | Projection::Full) => scan.checked(ids),
+            _ => scan,
+        }
+    }
+}
+
+// How results are printed
+#[derive(Clone, Copy, PartialEq, Default, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+// How requests to HelixDB are shaped
+#[derive(Clone, Copy, PartialEq, Default, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Protocol {
+    /// A POST per query to its own route
+    #[default]
+    Rest,
+    /// Every query as a GraphQL operation on the `graphql` route
+    Graphql,
+    /// Every query through the gRPC service in `helixdb-cfg/helix.proto`
+    Grpc,
+}
+
+// How HelixDB request bodies are serialized over REST
+#[derive(Clone, Copy, PartialEq, Default, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BodyEncoding {
+    #[default]
+    Json,
+    Msgpack,
+    Cbor,
+}
+
+impl BodyEncoding {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            BodyEncoding::Json => "application/json",
+            BodyEncoding::Msgpack => "application/msgpack",
+            BodyEncoding::Cbor => "application/cbor",
+        }
+    }
+}
+
+// Route of each HelixDB query. The defaults match the queries in `helixdb-cfg/queries.hx`;
+// a config file can point any of them elsewhere, e.g. `read_record = "/get_record"` under `[paths]`.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EndpointMap {
+    pub create_record: String,
+    pub read_record: String,
+    pub read_record_id: String,
+    pub update_record: String,
+    pub upsert_record: String,
+    pub delete_record: String,
+    pub delete_records: String,
+    /// Deletes every record, for `--purge`
+    pub delete_all_records: String,
+    pub scan_records: String,
+    pub scan_record_ids: String,
+    /// Looks records up by `data` through the secondary index
+    pub read_records_by_data: String,
+    /// Looks records up by `data` by filtering every record, for `--no-index`
+    pub filter_records_by_data: String,
+    pub count_records: String,
+    pub create_vector: String,
+    pub search_vector: String,
+    /// Creates, reads back and updates a batch of records in one query, for `txn`
+    pub transaction: String,
+    /// Reads the records of a list of ids in one query, for `batch_read`
+    pub read_records_batch: String,
+    pub graphql: String,
+    /// Takes the queries of `--hql-file` with their parameters
+    pub hql: String,
+    /// Server stats polled by `--sample-memory`
+    pub stats: String,
+    /// Server version, reported with the results
+    pub version: String,
+}
+
+impl Default for EndpointMap {
+    fn default() -> Self {
+        Self {
+            create_record: "/create_record".to_string(),
+            read_record: "/read_record".to_string(),
+            read_record_id: "/read_record_id".to_string(),
+            update_record: "/update_record".to_string(),
+            upsert_record: "/upsert_record".to_string(),
+            delete_record: "/delete_record".to_string(),
+            delete_records: "/delete_records".to_string(),
+            delete_all_records: "/delete_all_records".to_string(),
+            scan_records: "/scan_records".to_string(),
+            scan_record_ids: "/scan_record_ids".to_string(),
+            read_records_by_data: "/read_records_by_data".to_string(),
+            filter_records_by_data: "/filter_records_by_data".to_string(),
+            count_records: "/count_records".to_string(),
+            create_vector: "/create_vector".to_string(),
+            search_vector: "/search_vector".to_string(),
+            transaction: "/transaction".to_string(),
+            read_records_batch: "/read_records_batch".to_string(),
+            graphql: "/graphql".to_string(),
+            hql: "/hql".to_string(),
+            stats: "/stats".to_string(),
+            version: "/version".to_string(),
+        }
+    }
 }
 
 // Configuration for the benchmark
@@ -13,6 +617,798 @@ pub enum Database {
 pub struct Benchmark {
     pub database: Database,
     pub endpoint: Option<String>,
+    /// How long to keep retrying the first connection while the server comes up
+    pub wait_for_ready: Option<Duration>,
+    pub precision: Precision,
+    pub http2: bool,
+    pub compress: bool,
+    pub encoding: BodyEncoding,
+    pub ca_cert: Option<PathBuf>,
+    pub accept_invalid_certs: bool,
+    /// Counts connections opened by the HTTP clients, with `--report-connections`
+    pub connections: Option<ConnectionCounter>,
+    /// Times the phases of HelixDB requests, with `--report-phases`
+    pub phases: Option<PhaseTimer>,
+    /// Times the HelixDB and Neo4j HTTP clients were rebuilt after the transport failed
+    pub reconnects: Arc<AtomicUsize>,
+    pub pool_max_idle_per_host: usize,
+    pub pool_idle_timeout: Option<Duration>,
+    pub protocol: Protocol,
+    pub paths: EndpointMap,
+    /// HelixDB queries sent in place of the routes they're named after
+    pub hql_file: Option<PathBuf>,
+    /// Send Neo4j writes to the cluster leader and reads to the followers
+    pub neo4j_routing: bool,
+    pub workload: Workload,
+    pub runner: Runner,
+}
+
+// Options for a benchmark run, read from the command line and optionally a config file.
+// Everything is optional here so that flags can override file values, on/off flags included,
+// which take `--flag=false`; defaults are applied once both sources are merged.
+#[derive(Args, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BenchConfig {
+    /// Operation to benchmark: create, read, update, upsert, delete, scan, txn, batch_read
+    /// [default: all]
+    pub operation: Option<String>,
+    /// Operations `all` runs, in this order, e.g. `create,read,scan`
+    /// [default: create,read,update,scan,create_vectors,search_vectors]
+    #[arg(long, value_enum, value_delimiter = ',')]
+    pub ops: Option<Vec<Operation>>,
+    /// Number of operations to perform [default: 500000]
+    #[arg(short, long)]
+    pub count: Option<usize>,
+    /// Create this many records before the measured operations, so reads and scans run
+    /// against a dataset sized independently of --count
+    #[arg(long)]
+    pub prefill: Option<usize>,
+    /// Track the existing records listed in this file, one id per line, so reads, updates,
+    /// deletes and scans can run against a dataset loaded by something else
+    #[arg(long)]
+    pub ids_file: Option<PathBuf>,
+    /// Database to benchmark [default: helixdb]
+    #[arg(short, long, value_enum, ignore_case = true)]
+    pub database: Option<Database>,
+    /// Endpoint URL (optional)
+    #[arg(short, long)]
+    pub endpoint: Option<String>,
+    /// Keep retrying the connection, with backoff, for up to this many seconds while the
+    /// server comes up, rather than fail on the first attempt
+    #[arg(long)]
+    pub wait_for_ready: Option<u64>,
+    /// Number of requests kept in flight at once [default: 1]
+    #[arg(long)]
+    pub concurrency: Option<usize>,
+    /// Most requests in flight at once across every loop, whatever the concurrency. Slots
+    /// wait for their turn outside the latency [default: no cap]
+    #[arg(long)]
+    pub max_in_flight: Option<usize>,
+    /// Delay the first request of each concurrent slot by a random 0 to this many
+    /// milliseconds, so they don't all start at once
+    #[arg(long)]
+    pub start_jitter_ms: Option<u64>,
+    /// Pause each concurrent slot this many milliseconds after every request before its next,
+    /// like a client's think time. The pause isn't in the latency but is in the throughput.
+    #[arg(long)]
+    pub think_time_ms: Option<u64>,
+    /// Start at most this many requests per second, to measure latency at a fixed load below
+    /// saturation
+    #[arg(long)]
+    pub limit_rps: Option<f64>,
+    /// Run each operation for this many seconds instead of a fixed number of operations
+    #[arg(long)]
+    pub duration: Option<u64>,
+    /// Stop an operation once it has run for this many seconds, reporting the requests made by
+    /// then as truncated. Ignored with --duration
+    #[arg(long)]
+    pub operation_timeout_budget: Option<u64>,
+    /// Components per generated vector [default: 768]
+    #[arg(long)]
+    pub dims: Option<usize>,
+    /// Results requested per vector search [default: 7]
+    #[arg(long)]
+    pub k: Option<usize>,
+    /// Distance vector searches rank by; engines that fix it server-side ignore this
+    /// [default: cosine]
+    #[arg(long, value_enum)]
+    pub metric: Option<VectorMetric>,
+    /// Precision of generated vector components: f32 or f64 [default: f64]
+    #[arg(long, value_enum)]
+    pub precision: Option<Precision>,
+    /// What scans return: id, full records or just a count [default: full]
+    #[arg(long, value_enum)]
+    pub projection: Option<Projection>,
+    /// What reads return: the id alone or the full record [default: full]
+    #[arg(long, value_enum)]
+    pub read_projection: Option<Projection>,
+    /// Scan in pages of this many records until the whole dataset has been read
+    #[arg(long)]
+    pub scan_page_size: Option<usize>,
+    /// Check that a scan returns every tracked record exactly once, to catch pages that skip or
+    /// repeat records at their boundaries
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    pub verify_scan: Option<bool>,
+    /// Print results as a text table or as JSON with run metadata [default: text]
+    #[arg(long, value_enum)]
+    pub output: Option<OutputFormat>,
+    /// Print nothing but the JSON report, on a single line, for scripts: no progress bars,
+    /// tables or status lines (bench, bench-vector and replay)
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    pub quiet: Option<bool>,
+    /// Repeat the whole benchmark this many times and report mean and spread [default: 1]
+    #[arg(long)]
+    pub iterations: Option<usize>,
+    /// Average reported over iterations: mean or median [default: mean]
+    #[arg(long, value_enum)]
+    pub average: Option<Average>,
+    /// Leave the slowest this many iterations, by total time, out of the averages, as warmup
+    /// or outliers [default: 0]
+    #[arg(long)]
+    pub drop_slowest: Option<usize>,
+    /// Cycle record data through this many distinct random strings instead of fixed values
+    #[arg(long)]
+    pub distinct_values: Option<usize>,
+    /// Mean byte length of generated values; implies 1000 distinct values unless
+    /// --distinct-values is set [default: 16]
+    #[arg(long)]
+    pub value_size: Option<usize>,
+    /// How value lengths vary around --value-size: const, uniform, normal or exponential
+    /// [default: const]
+    #[arg(long, value_enum)]
+    pub value_size_dist: Option<ValueSizeDistribution>,
+    /// Spread of value lengths in bytes: the half-width for uniform, the std dev for normal
+    /// [default: a quarter of --value-size]
+    #[arg(long)]
+    pub value_size_spread: Option<usize>,
+    /// What updates write: partial sends only the changed field, full rewrites the whole
+    /// record [default: partial]
+    #[arg(long, value_enum)]
+    pub update_mode: Option<UpdateMode>,
+    /// JSON body for HelixDB creates, with {{uuid}}, {{seq}} and {{rand:N}} filled in per
+    /// record [default: {"data": <value>}]
+    #[arg(long)]
+    pub payload_template: Option<String>,
+    /// YAML or JSON file listing fields (int, string, float, bool or timestamp) that records
+    /// carry besides `data`, generated per write and type-checked on reads (HelixDB, SQLite,
+    /// MySQL and Neo4j)
+    #[arg(long)]
+    pub schema: Option<PathBuf>,
+    /// Table (SQLite, DuckDB, libSQL, MySQL, ClickHouse, Cassandra) or node label (Neo4j,
+    /// Memgraph) to keep records under, so runs against one server stay apart
+    /// [default: records / Record]
+    #[arg(long)]
+    pub dataset_name: Option<String>,
+    /// Create a secondary index on the record data at startup, for `read_by_data`
+    #[arg(
+        long,
+        conflicts_with = "no_index",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true"
+    )]
+    pub create_index: Option<bool>,
+    /// Drop the secondary index on the record data at startup, so `read_by_data` filters every
+    /// record
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    pub no_index: Option<bool>,
+    /// How reads pick records: sequential, uniform or zipf [default: sequential]
+    #[arg(long, value_enum)]
+    pub read_distribution: Option<ReadDistribution>,
+    /// Skew of the zipf read distribution, between 0 and 1 [default: 0.99]
+    #[arg(long)]
+    pub zipf_exponent: Option<f64>,
+    /// Number of reads per read operation [default: one per record]
+    #[arg(long)]
+    pub read_count: Option<usize>,
+    /// Read, update and delete records in a random order, seeded by --seed, rather than the
+    /// order they were created in
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    pub shuffle: Option<bool>,
+    /// Confine reads and updates to this many of the created records, the first ones or, with
+    /// --shuffle, a seeded random choice, so a working set smaller than the dataset stays hot
+    #[arg(long)]
+    pub keyspace_size: Option<usize>,
+    /// Records each `txn` transaction creates, reads back and updates before committing
+    /// (HelixDB and Neo4j) [default: 1]
+    #[arg(long)]
+    pub ops_per_txn: Option<usize>,
+    /// Records each `batch_read` request fetches (HelixDB and Neo4j) [default: 100]
+    #[arg(long)]
+    pub batch_size: Option<usize>,
+    /// Seed for generated data, so runs can be repeated exactly
+    #[arg(long)]
+    pub seed: Option<u64>,
+    /// Derive the ids of created records from --seed (0 without one) rather than drawing them
+    /// at random, so repeated runs create the same keys. Engines that assign ids themselves
+    /// (HelixDB and Dgraph) ignore it
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    pub deterministic_ids: Option<bool>,
+    /// Send HelixDB queries as REST calls, through a GraphQL gateway or over gRPC, falling back
+    /// to REST if the endpoint doesn't speak gRPC [default: rest]
+    #[arg(long, value_enum)]
+    pub protocol: Option<Protocol>,
+    /// File of HQL queries, each sent to HelixDB's `hql` route in place of the route it is
+    /// named after, with its parameters bound from the body that route would get (REST only)
+    #[arg(long)]
+    pub hql_file: Option<PathBuf>,
+    /// Look up the routing table of the Neo4j cluster at --endpoint, then send writes to the
+    /// leader and spread reads over the followers, each on the endpoint's HTTP port
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    pub neo4j_routing: Option<bool>,
+    /// Talk to HelixDB over cleartext HTTP/2 (h2c) without an upgrade; the server must support h2c
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    pub http2: Option<bool>,
+    /// Gzip the bodies of HelixDB's vector inserts and searches, which run to kilobytes of JSON
+    /// at high dimensions. The server must accept `Content-Encoding: gzip`
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    pub compress: Option<bool>,
+    /// Serialize HelixDB REST request bodies as JSON, MessagePack or CBOR, with the matching
+    /// Content-Type. Responses are decoded by their own Content-Type [default: json]
+    #[arg(long, value_enum)]
+    pub encoding: Option<BodyEncoding>,
+    /// Trust this PEM certificate, e.g. a self-signed CA, for HTTPS endpoints
+    #[arg(long)]
+    pub ca_cert: Option<PathBuf>,
+    /// Skip TLS certificate verification entirely. For testing only: the connection is no
+    /// longer protected against interception
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    pub danger_accept_invalid_certs: Option<bool>,
+    /// Idle connections the HTTP clients keep open per host for reuse [default: the concurrency]
+    #[arg(long)]
+    pub pool_max_idle_per_host: Option<usize>,
+    /// Seconds an idle pooled connection is kept before it's closed [default: 90]
+    #[arg(long)]
+    pub pool_idle_timeout: Option<u64>,
+    /// Count the TCP connections opened to HTTP endpoints and report requests per connection
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    pub report_connections: Option<bool>,
+    /// Break HelixDB request time down into serializing, network and deserializing, per route
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    pub report_phases: Option<bool>,
+    /// Fail the run if any operation's p99 latency exceeds this many milliseconds
+    #[arg(long)]
+    pub max_p99_ms: Option<f64>,
+    /// Fail the run if any operation's throughput falls below this many ops/s
+    #[arg(long)]
+    pub min_throughput: Option<f64>,
+    /// Cross-check the server's record count against the tracked ids after create and delete
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    pub verify: Option<bool>,
+    /// How reads check the data they get back: `off` skips the check, `sample` checks one read
+    /// in every `--read-verify-every`, `strict` checks them all [default: strict]
+    #[arg(long, value_enum)]
+    pub read_verify: Option<ReadVerify>,
+    /// Reads per checked read under `--read-verify sample` [default: 100]
+    #[arg(long)]
+    pub read_verify_every: Option<usize>,
+    /// How vector searches check the number of results they get back: `exact` expects `--k`
+    /// of them, `at-least` allows more, `off` skips the check. Fewer are expected when the run
+    /// has created fewer vectors than that [default: exact]
+    #[arg(long, value_enum)]
+    pub search_results: Option<SearchResults>,
+    /// Abort an operation on its first failed request; pass false to count failures and go on
+    /// [default: true unless --max-errors is set]
+    #[arg(long)]
+    pub fail_fast: Option<bool>,
+    /// Failed requests tolerated per operation before it aborts [default: unlimited with
+    /// --fail-fast=false]
+    #[arg(long)]
+    pub max_errors: Option<usize>,
+    /// Delete every record created during the run once it finishes
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    pub cleanup: Option<bool>,
+    /// Delete every record in the database before each iteration, including ones left by
+    /// earlier runs (HelixDB, Neo4j and Memgraph)
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    pub purge: Option<bool>,
+    /// Run everything on one thread with tokio's current-thread scheduler, to tell whether the
+    /// client or the server is the bottleneck
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    pub single_thread: Option<bool>,
+    /// Pin the benchmark's threads to these CPU cores, e.g. `0,1,2,3`, running one worker per
+    /// core, so the OS scheduler moving them around doesn't show in the latencies. Best-effort:
+    /// where the platform doesn't allow pinning the threads run unpinned
+    #[arg(long, value_delimiter = ',')]
+    pub cpu_affinity: Option<Vec<usize>>,
+    /// Serve live request counts and latency histograms for Prometheus at /metrics on this port
+    #[arg(long)]
+    pub metrics_port: Option<u16>,
+    /// Write every request's latency to this CSV file: operation, index, microseconds and
+    /// whether it succeeded
+    #[arg(long)]
+    pub raw_latencies: Option<PathBuf>,
+    /// Keep only every Nth successful request's latency for the percentiles and histogram, to
+    /// cut the cost of collecting them on very fast runs [default: 1, every request]
+    #[arg(long)]
+    pub sample_every: Option<usize>,
+    /// Write the JSON results, the resolved config and CSVs of raw latencies, a latency
+    /// histogram and throughput per second to a new timestamped directory under this one
+    #[arg(long)]
+    pub output_dir: Option<PathBuf>,
+    /// Write completed operations per second over the run to this CSV file
+    #[arg(long)]
+    pub timeseries: Option<PathBuf>,
+    /// Add latency std dev and coefficient of variation columns to the results table
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    pub detailed: Option<bool>,
+    /// Poll the server's stats endpoint every second and report its memory use over the run
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    pub sample_memory: Option<bool>,
+    /// HelixDB query routes, only settable from a config file
+    #[arg(skip)]
+    pub paths: Option<EndpointMap>,
+    /// Check connectivity with a single create/read/delete round-trip and exit
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    pub dry_run: Option<bool>,
+}
+
+impl BenchConfig {
+    /// Loads a config file, picking TOML or YAML from the file extension
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("Failed to read config {}: {}", path.display(), e))?;
+        let config = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => toml::from_str(&contents)?,
+            Some("yaml") | Some("yml") => serde_yaml::from_str(&contents)?,
+            _ => {
+                return Err(anyhow::anyhow!(
+                    "Unsupported config format: {} (expected .toml, .yaml or .yml)",
+                    path.display()
+                ))
+            }
+        };
+        Ok(config)
+    }
+
+    /// Fills every option not set in `self` from `fallback`
+    pub fn or(self, fallback: BenchConfig) -> Self {
+        // either index flag on the command line replaces whichever the file set
+        let (create_index, no_index) = match self.create_index.or(self.no_index) {
+            Some(_) => (self.create_index, self.no_index),
+            None => (fallback.create_index, fallback.no_index),
+        };
+        Self {
+            operation: self.operation.or(fallback.operation),
+            ops: self.ops.or(fallback.ops),
+            count: self.count.or(fallback.count),
+            prefill: self.prefill.or(fallback.prefill),
+            ids_file: self.ids_file.or(fallback.ids_file),
+            database: self.database.or(fallback.database),
+            endpoint: self.endpoint.or(fallback.endpoint),
+            wait_for_ready: self.wait_for_ready.or(fallback.wait_for_ready),
+            concurrency: self.concurrency.or(fallback.concurrency),
+            limit_rps: self.limit_rps.or(fallback.limit_rps),
+            start_jitter_ms: self.start_jitter_ms.or(fallback.start_jitter_ms),
+            think_time_ms: self.think_time_ms.or(fallback.think_time_ms),
+            max_in_flight: self.max_in_flight.or(fallback.max_in_flight),
+            duration: self.duration.or(fallback.duration),
+            operation_timeout_budget: self
+                .operation_timeout_budget
+                .or(fallback.operation_timeout_budget),
+            dims: self.dims.or(fallback.dims),
+            k: self.k.or(fallback.k),
+            metric: self.metric.or(fallback.metric),
+            precision: self.precision.or(fallback.precision),
+            projection: self.projection.or(fallback.projection),
+            read_projection: self.read_projection.or(fallback.read_projection),
+            scan_page_size: self.scan_page_size.or(fallback.scan_page_size),
+            verify_scan: self.verify_scan.or(fallback.verify_scan),
+            output: self.output.or(fallback.output),
+            quiet: self.quiet.or(fallback.quiet),
+            iterations: self.iterations.or(fallback.iterations),
+            average: self.average.or(fallback.average),
+            drop_slowest: self.drop_slowest.or(fallback.drop_slowest),
+            distinct_values: self.distinct_values.or(fallback.distinct_values),
+            update_mode: self.update_mode.or(fallback.update_mode),
+            payload_template: self.payload_template.or(fallback.payload_template),
+            schema: self.schema.or(fallback.schema),
+            dataset_name: self.dataset_name.or(fallback.dataset_name),
+            create_index,
+            no_index,
+            value_size: self.value_size.or(fallback.value_size),
+            value_size_dist: self.value_size_dist.or(fallback.value_size_dist),
+            value_size_spread: self.value_size_spread.or(fallback.value_size_spread),
+            seed: self.seed.or(fallback.seed),
+            deterministic_ids: self.deterministic_ids.or(fallback.deterministic_ids),
+            read_distribution: self.read_distribution.or(fallback.read_distribution),
+            zipf_exponent: self.zipf_exponent.or(fallback.zipf_exponent),
+            read_count: self.read_count.or(fallback.read_count),
+            shuffle: self.shuffle.or(fallback.shuffle),
+            keyspace_size: self.keyspace_size.or(fallback.keyspace_size),
+            ops_per_txn: self.ops_per_txn.or(fallback.ops_per_txn),
+            batch_size: self.batch_size.or(fallback.batch_size),
+            max_p99_ms: self.max_p99_ms.or(fallback.max_p99_ms),
+            min_throughput: self.min_throughput.or(fallback.min_throughput),
+            protocol: self.protocol.or(fallback.protocol),
+            hql_file: self.hql_file.or(fallback.hql_file),
+            neo4j_routing: self.neo4j_routing.or(fallback.neo4j_routing),
+            http2: self.http2.or(fallback.http2),
+            compress: self.compress.or(fallback.compress),
+            encoding: self.encoding.or(fallback.encoding),
+            report_connections: self.report_connections.or(fallback.report_connections),
+            report_phases: self.report_phases.or(fallback.report_phases),
+            pool_max_idle_per_host: self.pool_max_idle_per_host.or(fallback.pool_max_idle_per_host),
+            pool_idle_timeout: self.pool_idle_timeout.or(fallback.pool_idle_timeout),
+            ca_cert: self.ca_cert.or(fallback.ca_cert),
+            danger_accept_invalid_certs: self
+                .danger_accept_invalid_certs
+                .or(fallback.danger_accept_invalid_certs),
+            verify: self.verify.or(fallback.verify),
+            read_verify: self.read_verify.or(fallback.read_verify),
+            read_verify_every: self.read_verify_every.or(fallback.read_verify_every),
+            search_results: self.search_results.or(fallback.search_results),
+            cleanup: self.cleanup.or(fallback.cleanup),
+            purge: self.purge.or(fallback.purge),
+            sample_memory: self.sample_memory.or(fallback.sample_memory),
+            detailed: self.detailed.or(fallback.detailed),
+            metrics_port: self.metrics_port.or(fallback.metrics_port),
+            single_thread: self.single_thread.or(fallback.single_thread),
+            cpu_affinity: self.cpu_affinity.or(fallback.cpu_affinity),
+            raw_latencies: self.raw_latencies.or(fallback.raw_latencies),
+            sample_every: self.sample_every.or(fallback.sample_every),
+            output_dir: self.output_dir.or(fallback.output_dir),
+            timeseries: self.timeseries.or(fallback.timeseries),
+            fail_fast: self.fail_fast.or(fallback.fail_fast),
+            max_errors: self.max_errors.or(fallback.max_errors),
+            paths: self.paths.or(fallback.paths),
+            dry_run: self.dry_run.or(fallback.dry_run),
+        }
+    }
+}
+
+// Durations are written out as fractional seconds, the same unit as `avg_time`
+fn as_secs<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64(duration.as_secs_f64())
+}
+
+fn from_secs<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+    let secs = f64::deserialize(deserializer)?;
+    Duration::try_from_secs_f64(secs).map_err(serde::de::Error::custom)
+}
+
+// Latency percentiles over the individual requests of an operation
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct LatencyStats {
+    #[serde(serialize_with = "as_secs", deserialize_with = "from_secs")]
+    pub mean: Duration,
+    #[serde(serialize_with = "as_secs", deserialize_with = "from_secs")]
+    pub p50: Duration,
+    #[serde(serialize_with = "as_secs", deserialize_with = "from_secs")]
+    pub p95: Duration,
+    #[serde(serialize_with = "as_secs", deserialize_with = "from_secs")]
+    pub p99: Duration,
+    #[serde(serialize_with = "as_secs", deserialize_with = "from_secs")]
+    pub max: Duration,
+    /// Population standard deviation
+    #[serde(serialize_with = "as_secs", deserialize_with = "from_secs")]
+    pub std_dev: Duration,
+    /// Coefficient of variation, std dev over mean. High values mean unstable timings.
+    pub cv: f64,
+}
+
+impl LatencyStats {
+    /// Returns `None` when no latencies were recorded
+    pub fn from_latencies(mut latencies: Vec<Duration>) -> Option<Self> {
+        if latencies.is_empty() {
+            return None;
+        }
+        latencies.sort_unstable();
+        let percentile = |p: f64| {
+            let rank = (p / 100.0 * latencies.len() as f64).ceil() as usize;
+            latencies[rank.clamp(1, latencies.len()) - 1]
+        };
+        let mean = latencies.iter().sum::<Duration>() / latencies.len() as u32;
+        let variance = latencies
+            .iter()
+            .map(|l| (l.as_secs_f64() - mean.as_secs_f64()).powi(2))
+            .sum::<f64>()
+            / latencies.len() as f64;
+        let std_dev = Duration::from_secs_f64(variance.sqrt());
+        Some(Self {
+            mean,
+            p50: percentile(50.0),
+            p95: percentile(95.0),
+            p99: percentile(99.0),
+            max: latencies[latencies.len() - 1],
+            std_dev,
+            cv: match mean.is_zero() {
+                true => 0.0,
+                false => std_dev.as_secs_f64() / mean.as_secs_f64(),
+            },
+        })
+    }
+}
+
+// Successful requests in each one-second window of an operation, for `--timeseries`
+#[derive(Clone, Default)]
+pub struct Timeline {
+    pub started: chrono::DateTime<chrono::Utc>,
+    pub windows: Vec<usize>,
+}
+
+// Successful requests of an operation by latency, for `--output-dir`. Bucket `i` counts the
+// requests that took more than 2^(i-1) and at most 2^i microseconds, bucket 0 those under 1µs.
+#[derive(Clone, Default)]
+pub struct Histogram {
+    pub buckets: Vec<usize>,
+}
+
+impl Histogram {
+    pub fn from_latencies(latencies: &[Duration]) -> Self {
+        let mut buckets = Vec::new();
+        for latency in latencies {
+            let micros = latency.as_micros() as u64;
+            let bucket = match micros {
+                0 | 1 => 0,
+                micros => (64 - (micros - 1).leading_zeros()) as usize,
+            };
+            if buckets.len() <= bucket {
+                buckets.resize(bucket + 1, 0);
+            }
+            buckets[bucket] += 1;
+        }
+        Self { buckets }
+    }
+}
+
+// An HTTP request the server answered with a failure status, kept as its own error rather than
+// flattened into a message so the runner can count failures by status
+#[derive(Debug)]
+pub struct StatusError {
+    pub status: reqwest::StatusCode,
+    // The response body, for servers that explain the failure there
+    pub body: String,
+}
+
+impl StatusError {
+    pub fn new(status: reqwest::StatusCode, body: impl Into<String>) -> Self {
+        Self { status, body: body.into() }
+    }
+}
+
+impl std::fmt::Display for StatusError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.body.is_empty() {
+            true => write!(f, "Request failed: {}", self.status),
+            false => write!(f, "Request failed: {}: {}", self.status, self.body),
+        }
+    }
+}
+
+impl std::error::Error for StatusError {}
+
+// A run that finished but missed a limit set for it: a latency or throughput threshold, a
+// regression in `diff` or no sustainable load in `stress`. Kept as its own error so the exit
+// code can tell it apart from a run that failed.
+#[derive(Debug)]
+pub struct ThresholdError(pub String);
+
+impl std::fmt::Display for ThresholdError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for ThresholdError {}
+
+// `--verify` found the database holding a different number of records than the run tracked.
+// Kept apart from a failed run, as every request went through but the data didn't add up.
+#[derive(Debug)]
+pub struct VerifyError(pub String);
+
+impl std::fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for VerifyError {}
+
+// Why an operation gave up on failed requests after others had gone through, attached to the
+// error that tipped it over
+#[derive(Debug)]
+pub enum RequestsFailed {
+    FailFast,
+    Exceeded(usize),
+}
+
+impl std::fmt::Display for RequestsFailed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RequestsFailed::FailFast => write!(f, "aborting on the first failed request"),
+            RequestsFailed::Exceeded(errors) => {
+                write!(f, "aborting after {} failed requests", errors)
+            }
+        }
+    }
+}
+
+// Results of a single benchmarked operation, read back from JSON reports by `diff`
+#[derive(Clone, Serialize, Deserialize)]
+pub struct OperationResult {
+    pub operation: String,
+    #[serde(serialize_with = "as_secs", deserialize_with = "from_secs")]
+    pub total_time: Duration,
+    /// Average seconds per request
+    pub avg_time: f64,
+    /// Requests per second
+    pub throughput: f64,
+    /// Megabytes of payload per second, for operations whose requests carry a known amount
+    #[serde(default)]
+    pub mb_per_sec: Option<f64>,
+    /// Per-request latencies, absent for operations that are not a loop of requests
+    pub latency: Option<LatencyStats>,
+    /// Requests that succeeded
+    pub completed: usize,
+    /// Failed requests, only non-zero when errors are tolerated
+    pub errors: usize,
+    /// Failed requests by HTTP status, or `timeout`, `connection` or `other`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub error_breakdown: BTreeMap<String, usize>,
+    /// The time budget ran out before every request was made, so `completed` falls short of
+    /// the count and the throughput is theirs alone
+    #[serde(default)]
+    pub truncated: bool,
+    #[serde(skip)]
+    pub timeline: Timeline,
+    #[serde(skip)]
+    pub histogram: Histogram,
+}
+
+impl OperationResult {
+    /// Mean seconds per request and requests per second of `count` requests over `total_time`,
+    /// both 0 when none completed rather than the NaN or infinity JSON can't hold
+    pub fn rates(count: usize, total_time: Duration) -> (f64, f64) {
+        let secs = total_time.as_secs_f64();
+        match count > 0 && secs > 0.0 {
+            true => (secs / count as f64, count as f64 / secs),
+            false => (0.0, 0.0),
+        }
+    }
+
+    /// Sets the bandwidth from the payload bytes each request carried, `None` if not known
+    pub fn with_payload(mut self, bytes: Option<f64>) -> Self {
+        self.mb_per_sec = bytes.map(|bytes| bytes * self.throughput / 1_000_000.0);
+        self
+    }
+
+    /// Percentage of the requests made that failed
+    pub fn error_rate(&self) -> f64 {
+        match self.completed + self.errors {
+            0 => 0.0,
+            total => self.errors as f64 / total as f64 * 100.0,
+        }
+    }
+}
+
+// Which average of the iterations `--iterations` reports
+#[derive(Clone, Copy, PartialEq, Default, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Average {
+    #[default]
+    Mean,
+    /// Less swayed by a single unusually fast or slow iteration
+    Median,
+}
+
+// Spread of one metric across iterations
+#[derive(Clone, Copy, Serialize)]
+pub struct Spread {
+    pub mean: f64,
+    pub median: f64,
+    /// Sample standard deviation, zero with a single value
+    pub std_dev: f64,
+    pub min: f64,
+    pub max: f64,
+}
+
+impl Spread {
+    /// Returns `None` when there are no values
+    pub fn from_values(values: &[f64]) -> Option<Self> {
+        if values.is_empty() {
+            return None;
+        }
+        let n = values.len() as f64;
+        let mean = values.iter().sum::<f64>() / n;
+        let mut sorted = values.to_vec();
+        sorted.sort_unstable_by(f64::total_cmp);
+        let mid = sorted.len() / 2;
+        let median = match sorted.len() % 2 {
+            0 => (sorted[mid - 1] + sorted[mid]) / 2.0,
+            _ => sorted[mid],
+        };
+        let variance = match values.len() {
+            1 => 0.0,
+            _ => values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (n - 1.0),
+        };
+        Some(Self {
+            mean,
+            median,
+            std_dev: variance.sqrt(),
+            min: values.iter().copied().fold(f64::INFINITY, f64::min),
+            max: values.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+        })
+    }
+
+    pub fn average(&self, average: Average) -> f64 {
+        match average {
+            Average::Mean => self.mean,
+            Average::Median => self.median,
+        }
+    }
+}
+
+// One operation's results across every iteration of a run. Times are in seconds.
+#[derive(Clone, Serialize)]
+pub struct OperationAggregate {
+    pub operation: String,
+    pub runs: usize,
+    pub throughput: Spread,
+    pub avg_time: Spread,
+    pub p99: Option<Spread>,
+}
+
+impl OperationAggregate {
+    /// Groups results by operation, in the order operations first appear
+    pub fn from_runs(runs: &[Vec<OperationResult>]) -> Vec<Self> {
+        let mut operations: Vec<&str> = Vec::new();
+        for result in runs.iter().flatten() {
+            if !operations.contains(&result.operation.as_str()) {
+                operations.push(&result.operation);
+            }
+        }
+        operations
+            .into_iter()
+            .filter_map(|operation| {
+                let results: Vec<&OperationResult> = runs
+                    .iter()
+                    .flatten()
+                    .filter(|result| result.operation == operation)
+                    .collect();
+                let metric = |f: &dyn Fn(&OperationResult) -> Option<f64>| {
+                    let values: Vec<f64> = results.iter().filter_map(|r| f(r)).collect();
+                    Spread::from_values(&values)
+                };
+                Some(Self {
+                    operation: operation.to_string(),
+                    runs: results.len(),
+                    throughput: metric(&|r| Some(r.throughput))?,
+                    avg_time: metric(&|r| Some(r.avg_time))?,
+                    p99: metric(&|r| r.latency.map(|l| l.p99.as_secs_f64())),
+                })
+            })
+            .collect()
+    }
+}
+
+// One operation of a replay trace. Trace files have one per line, `<operation> <id> [data]`,
+// with the data running to the end of the line; blank lines and `#` comments are skipped:
+//
+//     create user-1 {"name": "a"}
+//     read user-1
+//     update user-1 {"name": "b"}
+//     delete user-1
+//
+// Ids name records within the trace. Those the trace creates are mapped to the keys the
+// database assigns, any others are passed to the database as they are.
+#[derive(Clone)]
+pub enum ReplayOp {
+    Create { id: String, data: String },
+    Read { id: String },
+    Update { id: String, data: String },
+    Delete { id: String },
+}
+
+impl ReplayOp {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ReplayOp::Create { .. } => "create",
+            ReplayOp::Read { .. } => "read",
+            ReplayOp::Update { .. } => "update",
+            ReplayOp::Delete { .. } => "delete",
+        }
+    }
 }
 
 // Parameters for scan operations
@@ -21,40 +1417,199 @@ pub struct Scan {
     pub limit: Option<usize>,
     pub start: Option<usize>,
     projection: Projection,
+    check: Option<Arc<ScanCheck>>,
+}
+
+/// Ids a scan returned across all of its pages, held against the ids the client tracks once
+/// the scan is done
+pub struct ScanCheck {
+    tracked: HashSet<String>,
+    seen: Mutex<HashMap<String, usize>>,
 }
 
-#[derive(Clone, Copy)]
+/// What a checked scan got wrong. Records other clients or runs created are counted apart, as
+/// they aren't tracked but belong in the scan all the same
+#[derive(Debug, Default)]
+pub struct ScanAudit {
+    /// Tracked records no page returned
+    pub missing: usize,
+    /// Returns of a record beyond its first
+    pub duplicates: usize,
+    /// Records returned that this client doesn't track
+    pub untracked: usize,
+}
+
+impl ScanCheck {
+    pub fn audit(&self) -> ScanAudit {
+        let seen = self.seen.lock().unwrap();
+        ScanAudit {
+            missing: self.tracked.iter().filter(|id| !seen.contains_key(*id)).count(),
+            duplicates: seen.values().map(|returns| returns - 1).sum(),
+            untracked: seen.keys().filter(|id| !self.tracked.contains(*id)).count(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Projection {
     Id,
+    #[default]
     Full,
     Count,
 }
 
+#[derive(Clone, Copy, Default, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReadVerify {
+    /// Reads aren't checked
+    Off,
+    /// One read in every `--read-verify-every` is checked
+    Sample,
+    /// Every read is checked
+    #[default]
+    Strict,
+}
+
+#[derive(Clone, Copy, Default, PartialEq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SearchResults {
+    /// Result counts aren't checked
+    Off,
+    /// Searches must return at least `k` results
+    AtLeast,
+    /// Searches must return exactly `k` results
+    #[default]
+    Exact,
+}
+
 impl Scan {
     pub fn new(limit: Option<usize>, start: Option<usize>, projection: Projection) -> Self {
         Self {
             limit,
             start,
             projection,
+            check: None,
+        }
+    }
+
+    // Gathers the returned ids to check against `tracked` afterwards
+    fn checked<T: ToString>(self, tracked: &[T]) -> Self {
+        let check = ScanCheck {
+            tracked: tracked.iter().map(ToString::to_string).collect(),
+            seen: Mutex::new(HashMap::new()),
+        };
+        Self {
+            check: Some(Arc::new(check)),
+            ..self
         }
     }
 
     pub fn projection(&self) -> Result<Projection> {
         Ok(self.projection)
     }
+
+    /// Whether the ids pages return are wanted, so engines only pick them out of the rows
+    /// when they are
+    pub fn checking(&self) -> bool {
+        self.check.is_some()
+    }
+
+    /// Notes ids a page returned, if the scan is being checked
+    pub fn saw(&self, ids: impl IntoIterator<Item = String>) {
+        if let Some(check) = &self.check {
+            let mut seen = check.seen.lock().unwrap();
+            for id in ids {
+                *seen.entry(id).or_default() += 1;
+            }
+        }
+    }
+
+    pub fn check(&self) -> Option<Arc<ScanCheck>> {
+        self.check.clone()
+    }
+
+    /// Runs `page` from this scan's start until a page comes back short, moving the start along
+    /// by the limit each time, and returns the total rows seen. Without a limit, or when only
+    /// counting, a single page covers everything.
+    pub async fn paginate<F, Fut>(self, page: F) -> Result<usize>
+    where
+        F: Fn(Scan) -> Fut,
+        Fut: Future<Output = Result<usize>>,
+    {
+        let mut scan = self;
+        let mut total = 0;
+        loop {
+            let rows = page(scan.clone()).await?;
+            total += rows;
+            match (scan.limit, scan.projection) {
+                (Some(limit), Projection::Id | Projection::Full) if limit > 0 && rows >= limit => {
+                    scan.start = Some(scan.start.unwrap_or(0) + limit);
+                }
+                _ => return Ok(total),
+            }
+        }
+    }
 }
 
+/// Ids deleted per request by `BenchmarkClient::cleanup`
+pub const CLEANUP_BATCH: usize = 1000;
+
 #[async_trait]
-pub trait BenchmarkClient {
+pub trait BenchmarkClient: Send + Sync {
     async fn startup(&self) -> Result<()>;
+    /// The server's version, for the results; `None` if the engine can't ask or the server
+    /// doesn't say
+    async fn server_version(&self) -> Option<String> {
+        None
+    }
     async fn create_records(&mut self, count: usize) -> Result<()>;
     async fn read_records(&self) -> Result<()>;
     async fn update_records(&self) -> Result<()>;
+    /// Inserts or updates `count` records by id, half of them against ids that already exist
+    async fn upsert_records(&mut self, count: usize) -> Result<()>;
     async fn delete_records(&self) -> Result<()>;
+    /// Deletes every record this client created, in batches of `CLEANUP_BATCH` ids, and
+    /// stops tracking them. Not timed; this is teardown rather than a benchmark.
+    async fn cleanup(&mut self) -> Result<()>;
     async fn scan_records(&self) -> Result<()>;
+    /// Looks records up by their `data` rather than their id, which only an index on `data`
+    /// keeps from reading every record
+    async fn read_records_by_data(&self) -> Result<()>;
+    /// Runs `count` transactions, each creating `Workload::ops_per_txn` records, reading them
+    /// back and updating them before it commits. Engines without multi-statement transactions
+    /// return an error.
+    async fn transaction(&mut self, count: usize) -> Result<()>;
+    /// Reads the tracked records `Workload::batch_size` at a time, one request per batch,
+    /// going through the same keys as `read_records`. Engines without a multi-get return an
+    /// error.
+    async fn batch_read(&self) -> Result<()>;
     async fn count_records(&self) -> Result<usize>;
+    /// Number of record ids the client is tracking from its own creates
+    fn tracked_ids(&self) -> usize;
+    /// Tracks records that already exist, by the ids `--ids-file` lists, so the operations on
+    /// tracked records work on them
+    fn load_ids(&mut self, ids: &[String]) -> Result<()>;
+    /// Forgets the tracked ids so the client starts over, and with `purge` also deletes every
+    /// record on the server, including those other runs left behind. Engines that can't purge
+    /// return an error when asked to.
+    async fn reset(&mut self, purge: bool) -> Result<()>;
+    /// Creates a single record, returning the key the other single-record operations take.
+    /// These are untimed and untracked, for `replay` to drive one operation at a time.
+    async fn create_record(&self, data: &str) -> Result<String>;
+    async fn read_record(&self, key: &str) -> Result<()>;
+    async fn update_record(&self, key: &str, data: &str) -> Result<()>;
+    async fn delete_record(&self, key: &str) -> Result<()>;
     async fn create_vectors(&self, count: usize) -> Result<()>;
     async fn search_vectors(&self, count: usize) -> Result<()>;
+    /// Mean recall@k of `queries` random searches against the exact nearest neighbours of the
+    /// vectors this client created, found by brute force. Untimed, and only possible when the
+    /// workload tracks vectors; `None` for engines whose searches don't say which vectors matched.
+    async fn vector_recall(&self, _queries: usize) -> Result<Option<f64>> {
+        Ok(None)
+    }
+    /// Runs a single create/read/delete round-trip, returning the outcome of each step
+    async fn dry_run(&mut self) -> Vec<(String, Result<()>)>;
 
     //async fn bulk_create(&self, count: usize) -> Result<()>;
     //async fn huge_traversal(&self, count: usize) -> Result<()>;
@@ -66,4 +1621,119 @@ pub trait BenchmarkEngine {
     where
         Self: Sized;
     async fn create_client(&self) -> Result<Box<dyn BenchmarkClient>>;
+    /// URL reporting the server's memory use, as JSON or Prometheus text, if it has one
+    fn stats_url(&self) -> Option<String> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Pages through `rows` rows, noting where each page started
+    async fn paginate(scan: Scan, rows: usize) -> (usize, Vec<Option<usize>>) {
+        let starts = Mutex::new(Vec::new());
+        let total = scan
+            .paginate(|page| {
+                starts.lock().unwrap().push(page.start);
+                let left = rows - page.start.unwrap_or(0).min(rows);
+                async move { Ok(page.limit.map_or(left, |limit| left.min(limit))) }
+            })
+            .await
+            .unwrap();
+        (total, starts.into_inner().unwrap())
+    }
+
+    #[test]
+    fn scan_check_counts_missing_repeated_and_untracked_ids() {
+        let scan = Scan::new(Some(2), Some(0), Projection::Id).checked(&["a", "b", "c"]);
+        assert!(scan.checking());
+        scan.saw(["a".to_string(), "a".to_string()]);
+        scan.clone().saw(["d".to_string(), "b".to_string()]);
+        let audit = scan.check().unwrap().audit();
+        assert_eq!((audit.missing, audit.duplicates, audit.untracked), (1, 1, 1));
+    }
+
+    #[test]
+    fn unchecked_scans_gather_nothing() {
+        let scan = Scan::new(Some(2), Some(0), Projection::Id);
+        scan.saw(["a".to_string()]);
+        assert!(!scan.checking() && scan.check().is_none());
+    }
+
+    #[tokio::test]
+    async fn paginate_moves_the_start_along_until_a_page_comes_back_short() {
+        let scan = Scan::new(Some(100), Some(0), Projection::Full);
+        assert_eq!(paginate(scan, 250).await, (250, vec![Some(0), Some(100), Some(200)]));
+        // a last page that is exactly full takes one more, empty, page to notice
+        let scan = Scan::new(Some(100), Some(0), Projection::Id);
+        assert_eq!(paginate(scan, 200).await, (200, vec![Some(0), Some(100), Some(200)]));
+    }
+
+    #[tokio::test]
+    async fn paginate_takes_one_page_without_a_limit_or_when_counting() {
+        assert_eq!(paginate(Scan::new(None, None, Projection::Full), 250).await, (250, vec![None]));
+        let scan = Scan::new(Some(100), Some(0), Projection::Count);
+        assert_eq!(paginate(scan, 250).await.1, [Some(0)]);
+    }
+
+    #[test]
+    fn bench_config_or_keeps_its_own_options_and_fills_the_rest() {
+        let cli = BenchConfig {
+            count: Some(10),
+            ops: Some(vec![Operation::Read]),
+            quiet: Some(true),
+            ..Default::default()
+        };
+        let file = BenchConfig {
+            count: Some(20),
+            concurrency: Some(4),
+            database: Some(Database::Sqlite),
+            ..Default::default()
+        };
+        let config = cli.or(file);
+        assert_eq!(config.count, Some(10));
+        assert_eq!(config.concurrency, Some(4));
+        assert!(config.database == Some(Database::Sqlite));
+        assert!(matches!(config.ops.as_deref(), Some([Operation::Read])));
+        assert!(config.quiet == Some(true) && config.prefill.is_none());
+    }
+
+    #[test]
+    fn bench_config_or_lets_the_command_line_turn_off_a_flag_the_file_sets() {
+        let file = BenchConfig { verify: Some(true), cleanup: Some(true), ..Default::default() };
+        let cli = BenchConfig { verify: Some(false), ..Default::default() };
+        let config = cli.or(file);
+        assert_eq!((config.verify, config.cleanup), (Some(false), Some(true)));
+    }
+
+    #[test]
+    fn boolean_flags_take_an_optional_value() {
+        #[derive(clap::Parser)]
+        struct Cli {
+            #[command(flatten)]
+            options: BenchConfig,
+        }
+        let parse = |args: &[&str]| {
+            let args = std::iter::once("bench").chain(args.iter().copied());
+            <Cli as clap::Parser>::try_parse_from(args).map(|cli| cli.options.verify)
+        };
+        assert_eq!(parse(&[]).unwrap(), None);
+        assert_eq!(parse(&["--verify"]).unwrap(), Some(true));
+        assert_eq!(parse(&["--verify=false"]).unwrap(), Some(false));
+        assert_eq!(parse(&["--verify", "read"]).unwrap(), Some(true));
+    }
+
+    #[test]
+    fn bench_config_or_lets_either_index_flag_override_the_fallback() {
+        let file = BenchConfig { create_index: Some(true), ..Default::default() };
+        let config = BenchConfig { no_index: Some(true), ..Default::default() }.or(file);
+        assert_eq!((config.create_index, config.no_index), (None, Some(true)));
+        let file = BenchConfig { no_index: Some(true), ..Default::default() };
+        let config = BenchConfig { create_index: Some(true), ..Default::default() }.or(file);
+        assert_eq!((config.create_index, config.no_index), (Some(true), None));
+        let file = BenchConfig { create_index: Some(true), ..Default::default() };
+        assert_eq!(BenchConfig::default().or(file).create_index, Some(true));
+    }
 }
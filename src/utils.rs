@@ -1,15 +1,626 @@
-use rand::Rng;
+use crate::types::{Benchmark, FieldType, SchemaField, ValueSizeDistribution, VectorMetric};
+use anyhow::Result;
+use rand::distributions::Alphanumeric;
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
+use hyper::client::connect::dns::Name;
+use reqwest::dns::{Addrs, Resolve, Resolving};
+use reqwest::{Certificate, Client, ClientBuilder, RequestBuilder, Response};
+use serde_json::Value;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+use tracing::{debug, warn};
+use uuid::Uuid;
 
-pub fn generate_random_vectors(count: usize, dims: usize) -> Vec<Vec<f64>> {
+/// Key for the `i`th upsert: every other one reuses a tracked id so it updates, the rest
+/// (or all of them, with nothing tracked yet) get `None` and insert a new key
+pub fn upsert_key<T: Clone>(ids: &[T], i: usize) -> Option<T> {
+    match i % 2 {
+        0 if !ids.is_empty() => Some(ids[(i / 2) % ids.len()].clone()),
+        _ => None,
+    }
+}
+
+pub fn generate_random_vector(dims: usize) -> Vec<f64> {
+    let mut rng = rand::thread_rng();
+    (0..dims).map(|_| rng.gen_range(0.0..1.0)).collect()
+}
+
+pub fn generate_random_vector_f32(dims: usize) -> Vec<f32> {
     let mut rng = rand::thread_rng();
-    let mut result = Vec::with_capacity(count);
+    (0..dims).map(|_| rng.gen_range(0.0..1.0)).collect()
+}
 
-    for _ in 0..count {
-        let vector: Vec<f64> = (0..dims)
-            .map(|_| rng.gen_range(0.0..1.0))
+/// Distance between two vectors under `metric`, smaller being nearer
+pub fn vector_distance(metric: VectorMetric, a: &[f32], b: &[f32]) -> f32 {
+    let dot = || a.iter().zip(b).map(|(x, y)| x * y).sum::<f32>();
+    match metric {
+        VectorMetric::Cosine => {
+            let norm = |v: &[f32]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+            1.0 - dot() / (norm(a) * norm(b)).max(f32::MIN_POSITIVE)
+        }
+        VectorMetric::Euclidean => a.iter().zip(b).map(|(x, y)| (x - y).powi(2)).sum(),
+        VectorMetric::Dot => -dot(),
+    }
+}
+
+/// Indices of the `k` vectors nearest `query`, nearest first, by exhaustive search
+pub fn nearest_neighbours(
+    metric: VectorMetric,
+    vectors: &[Vec<f32>],
+    query: &[f32],
+    k: usize,
+) -> Vec<usize> {
+    let mut distances: Vec<(usize, f32)> = vectors
+        .iter()
+        .enumerate()
+        .map(|(i, vector)| (i, vector_distance(metric, vector, query)))
+        .collect();
+    distances.sort_unstable_by(|a, b| a.1.total_cmp(&b.1));
+    distances.into_iter().take(k).map(|(i, _)| i).collect()
+}
+
+/// Lengths of generated values in bytes, drawn from a distribution around `size`
+#[derive(Clone, Copy)]
+pub struct ValueSizes {
+    pub distribution: ValueSizeDistribution,
+    pub size: usize,
+    /// Half-width of the uniform range, or the standard deviation of the normal distribution
+    pub spread: usize,
+}
+
+impl ValueSizes {
+    /// A length of at least one byte
+    pub fn sample<R: Rng>(&self, rng: &mut R) -> usize {
+        let (size, spread) = (self.size as f64, self.spread as f64);
+        let length = match self.distribution {
+            ValueSizeDistribution::Const => size,
+            ValueSizeDistribution::Uniform => rng.gen_range(size - spread..=size + spread),
+            // Box-Muller, to avoid pulling in rand_distr for one distribution
+            ValueSizeDistribution::Normal => {
+                let (u1, u2): (f64, f64) = (1.0 - rng.gen::<f64>(), rng.gen());
+                size + spread * (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos()
+            }
+            ValueSizeDistribution::Exponential => -size * (1.0 - rng.gen::<f64>()).ln(),
+        };
+        (length.round() as usize).max(1)
+    }
+}
+
+/// `count` distinct random strings with lengths drawn from `sizes`, reproducible when a seed
+/// is given. Short lengths have few distinct strings, so fewer may come back in that case.
+pub fn generate_values(count: usize, sizes: ValueSizes, seed: Option<u64>) -> Vec<String> {
+    let mut rng = seeded_rng(seed);
+    let mut values = std::collections::HashSet::with_capacity(count);
+    let mut attempts = 0;
+    while values.len() < count && attempts < count.saturating_mul(100) {
+        let length = sizes.sample(&mut rng);
+        let value: String = (&mut rng)
+            .sample_iter(Alphanumeric)
+            .take(length)
+            .map(char::from)
             .collect();
-        result.push(vector);
+        values.insert(value);
+        attempts += 1;
+    }
+    let mut values: Vec<String> = values.into_iter().collect();
+    values.sort_unstable();
+    values
+}
+
+/// A value for a `--schema` field, drawn afresh each time
+pub fn generate_field<R: Rng>(field: &SchemaField, rng: &mut R) -> Value {
+    match field.kind {
+        FieldType::Int => Value::from(rng.gen_range(0..=1_000_000)),
+        FieldType::String => {
+            let length = field.length.unwrap_or(16);
+            let text: String = rng.sample_iter(Alphanumeric).take(length).map(char::from).collect();
+            Value::from(text)
+        }
+        FieldType::Float => Value::from(rng.gen_range(0.0..1_000_000.0)),
+        FieldType::Bool => Value::from(rng.gen_bool(0.5)),
+        FieldType::Timestamp => {
+            let age = chrono::Duration::seconds(rng.gen_range(0..365 * 24 * 60 * 60));
+            Value::from((chrono::Utc::now() - age).to_rfc3339())
+        }
+    }
+}
+
+/// Whether `name` can be spliced into a statement as a table, column or label name unquoted
+pub fn is_identifier(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Ids given as text, such as from `--ids-file`, for an engine that keys records by UUID
+pub fn parse_uuids(ids: &[String]) -> Result<Vec<Uuid>> {
+    ids.iter()
+        .map(|id| id.parse().map_err(|_| anyhow::anyhow!("{:?} is not a UUID", id)))
+        .collect()
+}
+
+/// A record body with `{{uuid}}`, `{{seq}}` and `{{rand:N}}` placeholders in its strings,
+/// filled in per record. A string that is only `{{seq}}` becomes the number itself.
+#[derive(Clone)]
+pub struct PayloadTemplate(Value);
+
+impl PayloadTemplate {
+    /// Parses a template, which must be a JSON object using only known placeholders
+    pub fn parse(template: &str) -> Result<Self> {
+        let value: Value = serde_json::from_str(template)
+            .map_err(|e| anyhow::anyhow!("--payload-template is not valid JSON: {}", e))?;
+        if !value.is_object() {
+            return Err(anyhow::anyhow!("--payload-template must be a JSON object"));
+        }
+        Self::check(&value)?;
+        Ok(Self(value))
+    }
+
+    fn check(value: &Value) -> Result<()> {
+        match value {
+            Value::String(s) => {
+                let mut rest = s.as_str();
+                while let Some((name, after)) = next_placeholder(rest)? {
+                    Placeholder::parse(name)?;
+                    rest = after;
+                }
+                Ok(())
+            }
+            Value::Array(items) => items.iter().try_for_each(Self::check),
+            Value::Object(fields) => fields.values().try_for_each(Self::check),
+            _ => Ok(()),
+        }
+    }
+
+    /// The body for the `seq`th record
+    pub fn render(&self, seq: usize) -> Value {
+        let mut rng = rand::thread_rng();
+        render(&self.0, seq, &mut rng)
+    }
+}
+
+enum Placeholder {
+    Uuid,
+    Seq,
+    Rand(usize),
+}
+
+impl Placeholder {
+    fn parse(name: &str) -> Result<Self> {
+        match name.split_once(':') {
+            None if name == "uuid" => Ok(Placeholder::Uuid),
+            None if name == "seq" => Ok(Placeholder::Seq),
+            Some(("rand", length)) => match length.parse() {
+                Ok(length) if length > 0 => Ok(Placeholder::Rand(length)),
+                _ => Err(anyhow::anyhow!("{{{{rand:N}}}} needs a length of at least 1")),
+            },
+            _ => Err(anyhow::anyhow!(
+                "unknown placeholder {{{{{}}}}}, expected uuid, seq or rand:N",
+                name
+            )),
+        }
     }
+}
+
+// The name inside the next `{{...}}` in `s` and what follows it
+fn next_placeholder(s: &str) -> Result<Option<(&str, &str)>> {
+    let Some(start) = s.find("{{") else {
+        return Ok(None);
+    };
+    let rest = &s[start + 2..];
+    let end = rest
+        .find("}}")
+        .ok_or_else(|| anyhow::anyhow!("unclosed placeholder in {:?}", s))?;
+    Ok(Some((&rest[..end], &rest[end + 2..])))
+}
+
+fn render<R: Rng>(value: &Value, seq: usize, rng: &mut R) -> Value {
+    match value {
+        Value::String(s) if s == "{{seq}}" => Value::from(seq),
+        Value::String(s) => {
+            let mut out = String::with_capacity(s.len());
+            let mut rest = s.as_str();
+            // placeholders were checked when the template was parsed
+            while let Ok(Some((name, after))) = next_placeholder(rest) {
+                out.push_str(&rest[..rest.len() - after.len() - name.len() - 4]);
+                match Placeholder::parse(name) {
+                    Ok(Placeholder::Uuid) => out.push_str(&Uuid::new_v4().to_string()),
+                    Ok(Placeholder::Seq) => out.push_str(&seq.to_string()),
+                    Ok(Placeholder::Rand(length)) => out.extend(
+                        (&mut *rng).sample_iter(Alphanumeric).take(length).map(char::from),
+                    ),
+                    Err(_) => {}
+                }
+                rest = after;
+            }
+            out.push_str(rest);
+            Value::String(out)
+        }
+        Value::Array(items) => items.iter().map(|item| render(item, seq, rng)).collect(),
+        Value::Object(fields) => Value::Object(
+            fields
+                .iter()
+                .map(|(name, field)| (name.clone(), render(field, seq, rng)))
+                .collect(),
+        ),
+        other => other.clone(),
+    }
+}
+
+/// Zipfian sampler over ranks `0..n`, with rank 0 the most popular. This is the generator from
+/// Gray et al., "Quickly Generating Billion-Record Synthetic Databases", as used by YCSB, which
+/// needs the exponent strictly between 0 and 1.
+pub struct Zipf {
+    n: usize,
+    theta: f64,
+    alpha: f64,
+    zetan: f64,
+    eta: f64,
+}
+
+impl Zipf {
+    pub fn new(n: usize, theta: f64) -> Result<Self> {
+        if n == 0 || !(theta > 0.0 && theta < 1.0) {
+            return Err(anyhow::anyhow!(
+                "Zipf needs at least one key and an exponent between 0 and 1, got {} and {}",
+                n,
+                theta
+            ));
+        }
+        let zeta = |n: usize| (1..=n).map(|i| 1.0 / (i as f64).powf(theta)).sum::<f64>();
+        let zetan = zeta(n);
+        Ok(Self {
+            n,
+            theta,
+            alpha: 1.0 / (1.0 - theta),
+            zetan,
+            eta: (1.0 - (2.0 / n as f64).powf(1.0 - theta)) / (1.0 - zeta(2) / zetan),
+        })
+    }
+
+    pub fn sample<R: Rng>(&self, rng: &mut R) -> usize {
+        let u: f64 = rng.gen();
+        let uz = u * self.zetan;
+        if uz < 1.0 {
+            return 0;
+        }
+        if uz < 1.0 + 0.5f64.powf(self.theta) {
+            return 1.min(self.n - 1);
+        }
+        let rank = (self.n as f64 * (self.eta * u - self.eta + 1.0).powf(self.alpha)) as usize;
+        rank.min(self.n - 1)
+    }
+}
+
+/// Ids for the records one operation creates: random, or with `--deterministic-ids` derived
+/// from the seed, the operation's place in the run and each request's index, so a run creates
+/// the same keys as the last one whatever order its requests complete in
+#[derive(Clone, Copy)]
+pub struct IdSource(Option<(u64, u64)>);
+
+impl IdSource {
+    pub fn new(seed: Option<u64>, operation: u64) -> Self {
+        Self(seed.map(|seed| (seed, operation)))
+    }
+
+    /// The id of the `i`th record created
+    pub fn id(&self, i: usize) -> Uuid {
+        let Some((seed, operation)) = self.0 else {
+            return Uuid::new_v4();
+        };
+        // mix is a bijection, so ids within an operation never collide
+        let key = mix(mix(seed) ^ operation);
+        let high = mix(key ^ i as u64);
+        let low = mix(high ^ key);
+        let bytes = ((high as u128) << 64 | low as u128).to_be_bytes();
+        uuid::Builder::from_random_bytes(bytes).into_uuid()
+    }
+}
+
+// The splitmix64 finalizer
+fn mix(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
 
-    result
+pub fn seeded_rng(seed: Option<u64>) -> SmallRng {
+    match seed {
+        Some(seed) => SmallRng::seed_from_u64(seed),
+        None => SmallRng::from_entropy(),
+    }
+}
+
+/// Builder for an engine's HTTP client with the pool sized by the run's options, trusting
+/// `--ca-cert` and skipping certificate checks with `--danger-accept-invalid-certs`
+pub fn http_client_builder(options: &Benchmark) -> Result<ClientBuilder> {
+    let mut builder = Client::builder().pool_max_idle_per_host(options.pool_max_idle_per_host);
+    if let Some(timeout) = options.pool_idle_timeout {
+        builder = builder.pool_idle_timeout(timeout);
+    }
+    if let Some(path) = &options.ca_cert {
+        let pem = std::fs::read(path).map_err(|e| {
+            anyhow::anyhow!("Failed to read CA certificate {}: {}", path.display(), e)
+        })?;
+        builder = builder.add_root_certificate(Certificate::from_pem(&pem)?);
+    }
+    if options.accept_invalid_certs {
+        warn!("TLS certificate verification is disabled, use this for testing only");
+        builder = builder.danger_accept_invalid_certs(true);
+    }
+    if let Some(counter) = &options.connections {
+        builder = builder.dns_resolver(Arc::new(counter.clone()));
+    }
+    Ok(builder)
+}
+
+/// Counts the TCP connections HTTP clients open. hyper resolves the host afresh for every new
+/// connection and not at all for reused ones, so a resolver that counts its lookups counts
+/// connections. Hosts given as IP addresses skip resolution and aren't counted.
+#[derive(Clone, Default)]
+pub struct ConnectionCounter(Arc<AtomicUsize>);
+
+impl ConnectionCounter {
+    pub fn count(&self) -> usize {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+impl Resolve for ConnectionCounter {
+    fn resolve(&self, name: Name) -> Resolving {
+        let opened = self.0.fetch_add(1, Ordering::SeqCst) + 1;
+        debug!(host = name.as_str(), opened, "opening connection");
+        let host = name.as_str().to_string();
+        Box::pin(async move {
+            let addrs: Vec<_> = tokio::net::lookup_host((host.as_str(), 0)).await?.collect();
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// An engine's HTTP client, rebuilt with a fresh pool when the transport goes bad. Over a
+/// long run idle connections get dropped and servers restart, so a request that fails to
+/// connect or send rebuilds the client for the requests after it. The failed request itself
+/// is only sent again when the caller says it is safe to, as a connection can drop after the
+/// server has applied a write. A client is only rebuilt once a request on it has succeeded,
+/// so a server that stays down doesn't rebuild it for every failed request.
+#[derive(Clone)]
+pub struct ReconnectingClient {
+    build: Arc<dyn Fn() -> Result<Client> + Send + Sync>,
+    // The client, with how many times it has been rebuilt
+    current: Arc<RwLock<(usize, Client)>>,
+    // One more than the last generation a request succeeded on, 0 before any has
+    working: Arc<AtomicUsize>,
+    reconnects: Arc<AtomicUsize>,
+}
+
+impl ReconnectingClient {
+    /// Builds the client from `http_client_builder`, with `configure` applied on top each time
+    pub fn new(
+        options: &Benchmark,
+        configure: impl Fn(ClientBuilder) -> ClientBuilder + Send + Sync + 'static,
+    ) -> Result<Self> {
+        let build_options = options.clone();
+        let build = move || Ok(configure(http_client_builder(&build_options)?).build()?);
+        let client = build()?;
+        Ok(Self {
+            build: Arc::new(build),
+            current: Arc::new(RwLock::new((0, client))),
+            working: Arc::default(),
+            reconnects: options.reconnects.clone(),
+        })
+    }
+
+    /// The current client, for requests that shouldn't reconnect when they fail
+    pub fn client(&self) -> Client {
+        self.current.read().unwrap().1.clone()
+    }
+
+    /// Sends the request `request` builds, rebuilding the client if the transport fails. The
+    /// error is returned either way, so the request counts as failed.
+    pub async fn send(
+        &self,
+        request: impl Fn(&Client) -> RequestBuilder,
+    ) -> reqwest::Result<Response> {
+        let (generation, client) = self.current.read().unwrap().clone();
+        let result = request(&client).send().await;
+        self.settle(generation, &result);
+        result
+    }
+
+    /// Like `send`, but sends the request once more on the rebuilt client, for requests that
+    /// change nothing and can safely run twice
+    pub async fn send_idempotent(
+        &self,
+        request: impl Fn(&Client) -> RequestBuilder,
+    ) -> reqwest::Result<Response> {
+        let (generation, client) = self.current.read().unwrap().clone();
+        let result = request(&client).send().await;
+        match self.settle(generation, &result) {
+            Some(client) => request(&client).send().await,
+            None => result,
+        }
+    }
+
+    // Notes a request's outcome on the client of `generation`, returning the client to retry
+    // on when the transport failed
+    fn settle(&self, generation: usize, result: &reqwest::Result<Response>) -> Option<Client> {
+        match result {
+            Err(e) if is_transport_error(e) => Some(self.reconnect(generation, e)),
+            Err(_) => None,
+            Ok(_) => {
+                self.working.fetch_max(generation + 1, Ordering::SeqCst);
+                None
+            }
+        }
+    }
+
+    // Rebuilds the client, unless another request already has since `generation` was current
+    // or no request has succeeded on it yet
+    fn reconnect(&self, generation: usize, error: &reqwest::Error) -> Client {
+        let mut current = self.current.write().unwrap();
+        if current.0 == generation && self.working.load(Ordering::SeqCst) == generation + 1 {
+            match (self.build)() {
+                Ok(client) => {
+                    let reconnects = self.reconnects.fetch_add(1, Ordering::SeqCst) + 1;
+                    warn!(%error, reconnects, "connection lost, rebuilding the HTTP client");
+                    *current = (generation + 1, client);
+                }
+                Err(e) => warn!(error = %e, "couldn't rebuild the HTTP client"),
+            }
+        }
+        current.1.clone()
+    }
+}
+
+// Whether a request failed because of the connection rather than the server's answer.
+// Timeouts are left out, as a slow server isn't a broken transport.
+fn is_transport_error(error: &reqwest::Error) -> bool {
+    (error.is_connect() || error.is_request()) && !error.is_timeout()
+}
+
+/// Time spent in each phase of the requests to one route
+#[derive(Clone, Copy, Default)]
+pub struct PhaseTotals {
+    pub requests: usize,
+    /// Encoding the request body
+    pub serialize: Duration,
+    /// From sending the request until the last byte of the response arrived
+    pub network: Duration,
+    /// Decoding the response body
+    pub deserialize: Duration,
+}
+
+/// Sums the request phases per route for `--report-phases`, shared by every client
+#[derive(Clone, Default)]
+pub struct PhaseTimer(Arc<Mutex<BTreeMap<String, PhaseTotals>>>);
+
+impl PhaseTimer {
+    pub fn record(
+        &self,
+        route: &str,
+        serialize: Duration,
+        network: Duration,
+        deserialize: Duration,
+    ) {
+        let mut routes = self.0.lock().unwrap();
+        let totals = routes.entry(route.to_string()).or_default();
+        totals.requests += 1;
+        totals.serialize += serialize;
+        totals.network += network;
+        totals.deserialize += deserialize;
+    }
+
+    /// Totals so far, by route
+    pub fn totals(&self) -> Vec<(String, PhaseTotals)> {
+        let routes = self.0.lock().unwrap();
+        routes.iter().map(|(route, totals)| (route.clone(), *totals)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::Runner;
+    use crate::types::{BenchConfig, Database};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[test]
+    fn zipf_needs_keys_and_an_exponent_between_0_and_1() {
+        assert!(Zipf::new(0, 0.99).is_err());
+        assert!(Zipf::new(10, 0.0).is_err());
+        assert!(Zipf::new(10, 1.0).is_err());
+        assert!(Zipf::new(10, f64::NAN).is_err());
+        assert!(Zipf::new(1, 0.5).is_ok());
+    }
+
+    #[test]
+    fn zipf_samples_stay_in_range_and_favour_low_ranks() {
+        let zipf = Zipf::new(100, 0.99).unwrap();
+        let mut rng = seeded_rng(Some(1));
+        let mut hits = [0; 100];
+        for _ in 0..100_000 {
+            hits[zipf.sample(&mut rng)] += 1;
+        }
+        assert!(hits[0] > hits[1] && hits[1] > hits[10] && hits[10] > hits[99]);
+        // the top tenth of the keys takes most of the samples
+        assert!(hits[..10].iter().sum::<usize>() > 50_000, "{:?}", hits);
+    }
+
+    #[test]
+    fn zipf_over_one_key_always_picks_it() {
+        let zipf = Zipf::new(1, 0.5).unwrap();
+        let mut rng = seeded_rng(Some(1));
+        assert!((0..1000).all(|_| zipf.sample(&mut rng) == 0));
+    }
+
+    #[test]
+    fn payload_template_fills_in_each_placeholder() {
+        let template = r#"{"id": "{{uuid}}", "n": "{{seq}}", "tags": ["t-{{seq}}-{{rand:4}}"],
+            "x": 1}"#;
+        let body = PayloadTemplate::parse(template).unwrap().render(7);
+        assert!(body["id"].as_str().unwrap().parse::<Uuid>().is_ok(), "{}", body);
+        assert_eq!(body["n"], 7);
+        let tag = body["tags"][0].as_str().unwrap();
+        assert!(tag.starts_with("t-7-") && tag.len() == 8, "{}", tag);
+        assert_eq!(body["x"], 1);
+    }
+
+    #[test]
+    fn payload_template_rejects_bad_templates() {
+        for template in [
+            "not json",
+            r#"["{{seq}}"]"#,
+            r#"{"a": "{{nope}}"}"#,
+            r#"{"a": "{{rand:0}}"}"#,
+            r#"{"a": "{{seq"}"#,
+        ] {
+            assert!(PayloadTemplate::parse(template).is_err(), "{}", template);
+        }
+    }
+
+    fn reconnecting_client() -> ReconnectingClient {
+        let runner = Runner::new(1, None).with_quiet(true);
+        let options = crate::resolve_benchmark(Database::HelixDB, &BenchConfig::default(), runner);
+        ReconnectingClient::new(&options.unwrap(), |builder| builder).unwrap()
+    }
+
+    #[tokio::test]
+    async fn a_refused_post_is_not_sent_again() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/create_record", listener.local_addr().unwrap());
+        drop(listener);
+        let client = reconnecting_client();
+        let sent = AtomicUsize::new(0);
+        let error = client
+            .send(|client| {
+                sent.fetch_add(1, Ordering::SeqCst);
+                client.post(&url)
+            })
+            .await
+            .unwrap_err();
+        assert!(error.is_connect(), "{}", error);
+        assert_eq!(sent.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn a_server_that_stays_down_rebuilds_the_client_once() {
+        // Built rather than started, as pooled servers keep listening when dropped
+        let server = MockServer::builder().start().await;
+        Mock::given(wiremock::matchers::any())
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&server)
+            .await;
+        let url = server.uri();
+        let client = reconnecting_client();
+        client.send(|client| client.get(&url)).await.unwrap();
+        drop(server);
+        for _ in 0..3 {
+            assert!(client.send(|client| client.get(&url)).await.is_err());
+        }
+        assert_eq!(client.reconnects.load(Ordering::SeqCst), 1);
+    }
 }
@@ -0,0 +1,487 @@
+use crate::runner::Runner;
+use crate::types::{
+    Benchmark, BenchmarkClient, BenchmarkEngine, Projection, Scan, StatusError, VectorMetric,
+    Workload, CLEANUP_BATCH,
+};
+use crate::utils::{
+    generate_random_vector_f32, http_client_builder, nearest_neighbours, parse_uuids, upsert_key,
+};
+use anyhow::Result;
+use async_trait::async_trait;
+use reqwest::{Client, Method, StatusCode};
+use serde_json::{json, Value};
+use std::sync::Mutex;
+use tracing::{debug, trace};
+use uuid::Uuid;
+
+// Records and vectors live in separate classes, neither with a vectorizer, so Weaviate stores
+// exactly what is sent
+const RECORD_CLASS: &str = "Record";
+
+// A class's distance is fixed when it's created, so each metric gets a class of its own
+fn vector_class(metric: VectorMetric) -> &'static str {
+    match metric {
+        VectorMetric::Cosine => "Vector",
+        VectorMetric::Euclidean => "VectorL2",
+        VectorMetric::Dot => "VectorDot",
+    }
+}
+
+fn distance_name(metric: VectorMetric) -> &'static str {
+    match metric {
+        VectorMetric::Cosine => "cosine",
+        VectorMetric::Euclidean => "l2-squared",
+        VectorMetric::Dot => "dot",
+    }
+}
+
+pub struct WeaviateClient {
+    endpoint: String,
+    client: Client,
+    runner: Runner,
+    workload: Workload,
+    ids: Vec<Uuid>,
+    // Vectors created, with their ids, when the workload tracks them for recall
+    vectors: Mutex<Vec<(Uuid, Vec<f32>)>>,
+}
+
+impl WeaviateClient {
+    pub fn new(endpoint: String, client: Client, runner: Runner, workload: Workload) -> Self {
+        Self {
+            endpoint,
+            client,
+            runner,
+            workload,
+            ids: Vec::new(),
+            vectors: Mutex::new(Vec::new()),
+        }
+    }
+
+    // Returns the response body, or null for the empty bodies of updates and deletes
+    async fn send(&self, method: Method, path: &str, body: Option<Value>) -> Result<Value> {
+        let url = format!("{}{}", self.endpoint, path);
+        trace!(%method, %path, ?body, "request");
+        let mut request = self.client.request(method, &url);
+        if let Some(body) = body {
+            request = request.json(&body);
+        }
+        let response = request.send().await?;
+        debug!(%path, status = %response.status(), "response");
+        let status = response.status();
+        let text = response.text().await?;
+        trace!(response = %text, "response body");
+        if !status.is_success() {
+            return Err(StatusError::new(status, text.trim()).into());
+        }
+        match text.is_empty() {
+            true => Ok(Value::Null),
+            false => Ok(serde_json::from_str(&text)?),
+        }
+    }
+
+    // GraphQL reports query errors with a 200 status, so they are pulled out of the body
+    async fn graphql(&self, query: String) -> Result<Value> {
+        let res = self
+            .send(Method::POST, "/v1/graphql", Some(json!({"query": query})))
+            .await?;
+        match res["errors"].as_array() {
+            Some(errors) if !errors.is_empty() => {
+                Err(anyhow::anyhow!("GraphQL error: {}", errors[0]["message"]))
+            }
+            _ => Ok(res["data"].clone()),
+        }
+    }
+
+    async fn ensure_class(&self, class: &str, properties: Value, distance: &str) -> Result<()> {
+        let url = format!("{}/v1/schema/{}", self.endpoint, class);
+        let response = self.client.get(&url).send().await?;
+        match response.status() {
+            StatusCode::OK => Ok(()),
+            StatusCode::NOT_FOUND => {
+                let schema = json!({
+                    "class": class,
+                    "vectorizer": "none",
+                    "vectorIndexConfig": {"distance": distance},
+                    "properties": properties
+                });
+                self.send(Method::POST, "/v1/schema", Some(schema)).await?;
+                Ok(())
+            }
+            status => Err(anyhow::anyhow!("Request failed: {}", status)),
+        }
+    }
+
+    async fn insert(&self, id: Uuid, data: &str) -> Result<()> {
+        let object = json!({
+            "class": RECORD_CLASS,
+            "id": id.to_string(),
+            "properties": {"data": data}
+        });
+        self.send(Method::POST, "/v1/objects", Some(object)).await?;
+        Ok(())
+    }
+
+    async fn select(&self, id: &str) -> Result<Value> {
+        let path = format!("/v1/objects/{}/{}", RECORD_CLASS, id);
+        self.send(Method::GET, &path, None).await
+    }
+
+    // There's no way to leave the properties out of an object GET, so id-only reads go
+    // through GraphQL instead
+    async fn select_id(&self, id: &str) -> Result<()> {
+        let query = format!(
+            r#"{{ Get {{ {}(where: {{path: ["id"], operator: Equal, valueText: "{}"}}) {{ _additional {{ id }} }} }} }}"#,
+            RECORD_CLASS, id
+        );
+        self.graphql(query).await?;
+        Ok(())
+    }
+
+    async fn read(&self, id: &str) -> Result<()> {
+        match self.workload.read_projection {
+            Projection::Id => self.select_id(id).await,
+            _ => self.select(id).await.map(|_| ()),
+        }
+    }
+
+    async fn update(&self, id: &str, data: &str) -> Result<()> {
+        let path = format!("/v1/objects/{}/{}", RECORD_CLASS, id);
+        let object = json!({"class": RECORD_CLASS, "properties": {"data": data}});
+        self.send(Method::PATCH, &path, Some(object)).await?;
+        Ok(())
+    }
+
+    async fn remove(&self, id: &str) -> Result<()> {
+        let path = format!("/v1/objects/{}/{}", RECORD_CLASS, id);
+        self.send(Method::DELETE, &path, None).await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl BenchmarkClient for WeaviateClient {
+    // Creating the classes when they're missing doubles as the connectivity check
+    async fn startup(&self) -> Result<()> {
+        let record = json!([{"name": "data", "dataType": ["text"]}]);
+        self.ensure_class(RECORD_CLASS, record, "cosine").await?;
+        let metric = self.workload.metric;
+        self.ensure_class(vector_class(metric), json!([]), distance_name(metric)).await
+    }
+
+    async fn create_records(&mut self, count: usize) -> Result<()> {
+        let new_ids = self.workload.new_ids();
+        let this = &*self;
+        let ids = this
+            .runner
+            .run("Create", count, |i| async move {
+                let id = new_ids.id(i);
+                this.insert(id, this.workload.value(i)).await?;
+                Ok(id)
+            })
+            .await?;
+        self.ids.extend(ids);
+        Ok(())
+    }
+
+    async fn read_records(&self) -> Result<()> {
+        let keys = self.workload.read_keys(self.ids.len())?;
+        let keys = &keys;
+        self.runner
+            .run("Read", keys.len(), |i| async move {
+                self.read(&self.ids[keys[i % keys.len()]].to_string()).await
+            })
+            .await?;
+        Ok(())
+    }
+
+    async fn update_records(&self) -> Result<()> {
+        let order = &self.workload.working_set(self.ids.len());
+        self.runner
+            .run("Update", self.ids.len(), |i| async move {
+                let id = self.ids[order[i % order.len()]];
+                self.update(&id.to_string(), self.workload.updated_value(i))
+                    .await
+            })
+            .await?;
+        Ok(())
+    }
+
+    // Batch imports overwrite an object that already has the id, which makes a one-object
+    // batch an upsert. Failures come back per object rather than as a status.
+    async fn upsert_records(&mut self, count: usize) -> Result<()> {
+        let new_ids = self.workload.new_ids();
+        let this = &*self;
+        let ids = this
+            .runner
+            .run("Upsert", count, |i| async move {
+                let existing = upsert_key(&this.ids, i);
+                let id = existing.unwrap_or_else(|| new_ids.id(i));
+                let object = json!({
+                    "class": RECORD_CLASS,
+                    "id": id.to_string(),
+                    "properties": {"data": this.workload.value(i)}
+                });
+                let res = this
+                    .send(Method::POST, "/v1/batch/objects", Some(json!({"objects": [object]})))
+                    .await?;
+                if let Some(error) = res[0]["result"]["errors"]["error"].get(0) {
+                    return Err(anyhow::anyhow!("Weaviate error: {}", error["message"]));
+                }
+                Ok(existing.is_none().then_some(id))
+            })
+            .await?;
+        self.ids.extend(ids.into_iter().flatten());
+        Ok(())
+    }
+
+    async fn delete_records(&self) -> Result<()> {
+        let order = &self.workload.order(self.ids.len());
+        self.runner
+            .run("Delete", self.ids.len(), |i| async move {
+                self.remove(&self.ids[order[i % order.len()]].to_string()).await
+            })
+            .await?;
+        Ok(())
+    }
+
+    async fn cleanup(&mut self) -> Result<()> {
+        for batch in self.ids.chunks(CLEANUP_BATCH) {
+            let ids: Vec<String> = batch.iter().map(Uuid::to_string).collect();
+            let body = json!({
+                "match": {
+                    "class": RECORD_CLASS,
+                    "where": {"path": ["id"], "operator": "ContainsAny", "valueTextArray": ids}
+                }
+            });
+            self.send(Method::DELETE, "/v1/batch/objects", Some(body))
+                .await?;
+        }
+        self.ids.clear();
+        Ok(())
+    }
+
+    async fn scan_records(&self) -> Result<()> {
+        let scan = self.workload.scan(&self.ids);
+        self.runner.scan(scan, |scan| async move { self.scan(&scan).await }).await?;
+        Ok(())
+    }
+
+    async fn read_records_by_data(&self) -> Result<()> {
+        Err(anyhow::anyhow!("read_by_data isn't supported for Weaviate"))
+    }
+
+    async fn transaction(&mut self, _count: usize) -> Result<()> {
+        Err(anyhow::anyhow!("txn isn't supported for Weaviate"))
+    }
+
+    async fn batch_read(&self) -> Result<()> {
+        Err(anyhow::anyhow!("batch_read isn't supported for Weaviate"))
+    }
+
+    async fn count_records(&self) -> Result<usize> {
+        let query = format!("{{ Aggregate {{ {} {{ meta {{ count }} }} }} }}", RECORD_CLASS);
+        let res = self.graphql(query).await?;
+        res["Aggregate"][RECORD_CLASS][0]["meta"]["count"]
+            .as_u64()
+            .map(|count| count as usize)
+            .ok_or_else(|| anyhow::anyhow!("no count in response: {}", res))
+    }
+
+    fn tracked_ids(&self) -> usize {
+        self.ids.len()
+    }
+
+    fn load_ids(&mut self, ids: &[String]) -> Result<()> {
+        self.ids.extend(parse_uuids(ids)?);
+        Ok(())
+    }
+
+    async fn reset(&mut self, purge: bool) -> Result<()> {
+        if purge {
+            return Err(anyhow::anyhow!("--purge isn't supported for Weaviate"));
+        }
+        self.ids.clear();
+        self.vectors.lock().unwrap().clear();
+        Ok(())
+    }
+
+    async fn create_record(&self, data: &str) -> Result<String> {
+        let id = self.workload.new_ids().id(0);
+        self.insert(id, data).await?;
+        Ok(id.to_string())
+    }
+
+    async fn read_record(&self, key: &str) -> Result<()> {
+        self.read(key).await
+    }
+
+    async fn update_record(&self, key: &str, data: &str) -> Result<()> {
+        self.update(key, data).await
+    }
+
+    async fn delete_record(&self, key: &str) -> Result<()> {
+        self.remove(key).await
+    }
+
+    // Weaviate stores vectors as f32 whatever the precision they're sent with
+    async fn create_vectors(&self, count: usize) -> Result<()> {
+        let new_ids = self.workload.new_ids();
+        self.runner
+            .run("Create vectors", count, |i| async move {
+                let (id, vector) = (new_ids.id(i), generate_random_vector_f32(self.workload.dims));
+                let object = json!({
+                    "class": vector_class(self.workload.metric),
+                    "id": id.to_string(),
+                    "vector": vector
+                });
+                self.send(Method::POST, "/v1/objects", Some(object)).await?;
+                self.workload.vector_created();
+                if self.workload.track_vectors {
+                    self.vectors.lock().unwrap().push((id, vector));
+                }
+                Ok(())
+            })
+            .await?;
+        Ok(())
+    }
+
+    async fn search_vectors(&self, count: usize) -> Result<()> {
+        self.runner
+            .run("Search vectors", count, |_| async move {
+                let found = self.search(&generate_random_vector_f32(self.workload.dims)).await?;
+                self.workload.check_search_results(found.len())
+            })
+            .await?;
+        Ok(())
+    }
+
+    async fn vector_recall(&self, queries: usize) -> Result<Option<f64>> {
+        let vectors = self.vectors.lock().unwrap().clone();
+        if vectors.is_empty() {
+            return Ok(None);
+        }
+        let (ids, vectors): (Vec<Uuid>, Vec<Vec<f32>>) = vectors.into_iter().unzip();
+        let k = self.workload.k.min(vectors.len());
+        let mut total = 0.0;
+        for _ in 0..queries {
+            let query = generate_random_vector_f32(self.workload.dims);
+            let exact = nearest_neighbours(self.workload.metric, &vectors, &query, k);
+            let found = self.search(&query).await?;
+            let hits = exact
+                .iter()
+                .filter(|&&i| found.contains(&ids[i].to_string()))
+                .count();
+            total += hits as f64 / k as f64;
+        }
+        Ok(Some(total / queries.max(1) as f64))
+    }
+
+    async fn dry_run(&mut self) -> Vec<(String, Result<()>)> {
+        let id = Uuid::new_v4();
+        let mut checks = Vec::new();
+
+        let created = self.insert(id, "test_value").await;
+        let create_ok = created.is_ok();
+        checks.push(("POST /v1/objects".to_string(), created));
+
+        let read = if create_ok {
+            self.select(&id.to_string()).await.and_then(|res| {
+                if res["properties"]["data"] == "test_value" {
+                    Ok(())
+                } else {
+                    Err(anyhow::anyhow!("unexpected read response: {}", res))
+                }
+            })
+        } else {
+            Err(anyhow::anyhow!("skipped, create failed"))
+        };
+        checks.push(("GET /v1/objects".to_string(), read));
+
+        let deleted = if create_ok {
+            self.remove(&id.to_string()).await
+        } else {
+            Err(anyhow::anyhow!("skipped, create failed"))
+        };
+        checks.push(("DELETE /v1/objects".to_string(), deleted));
+
+        checks
+    }
+}
+
+impl WeaviateClient {
+    // Ids of the nearest `k` vectors to `vector`, nearest first
+    async fn search(&self, vector: &[f32]) -> Result<Vec<String>> {
+        let class = vector_class(self.workload.metric);
+        let query = format!(
+            "{{ Get {{ {}(nearVector: {{vector: {}}}, limit: {}) {{ _additional {{ id distance }} }} }} }}",
+            class,
+            json!(vector),
+            self.workload.k
+        );
+        let res = self.graphql(query).await?;
+        Ok(res["Get"][class]
+            .as_array()
+            .map(|hits| {
+                hits.iter()
+                    .filter_map(|hit| hit["_additional"]["id"].as_str().map(str::to_string))
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+
+    async fn scan(&self, scan: &Scan) -> Result<usize> {
+        let limit = scan.limit.unwrap_or(100);
+        let offset = scan.start.unwrap_or(0);
+        let fields = match scan.projection()? {
+            Projection::Count => return self.count_records().await,
+            Projection::Id => "_additional { id }",
+            Projection::Full => "data _additional { id }",
+        };
+        let query = format!(
+            "{{ Get {{ {}(limit: {}, offset: {}) {{ {} }} }} }}",
+            RECORD_CLASS, limit, offset, fields
+        );
+        let res = self.graphql(query).await?;
+        let rows = res["Get"][RECORD_CLASS].as_array().map(Vec::as_slice).unwrap_or_default();
+        scan.saw(rows.iter().filter_map(|row| {
+            row["_additional"]["id"].as_str().map(str::to_string)
+        }));
+        Ok(rows.len())
+    }
+}
+
+// Engine for Weaviate over its REST and GraphQL APIs, with anonymous access
+pub struct WeaviateEngine {
+    endpoint: String,
+    client: Client,
+    runner: Runner,
+    workload: Workload,
+}
+
+#[async_trait]
+impl BenchmarkEngine for WeaviateEngine {
+    async fn setup(options: &Benchmark) -> Result<Self> {
+        let endpoint = options
+            .endpoint
+            .as_deref()
+            .unwrap_or("http://localhost:8080")
+            .to_string();
+        Ok(Self {
+            endpoint,
+            client: http_client_builder(options)?.build()?,
+            runner: options.runner.clone(),
+            workload: options.workload.clone(),
+        })
+    }
+
+    async fn create_client(&self) -> Result<Box<dyn BenchmarkClient>> {
+        let client = WeaviateClient::new(
+            self.endpoint.clone(),
+            self.client.clone(),
+            self.runner.clone(),
+            self.workload.clone(),
+        );
+        client.startup().await?;
+        Ok(Box::new(client))
+    }
+}
@@ -0,0 +1,5 @@
+#!/bin/sh
+docker run -d \
+    --name cassandra \
+    -p 9042:9042 \
+    cassandra:5
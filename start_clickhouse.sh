@@ -0,0 +1,7 @@
+#!/bin/sh
+docker run -d \
+    --name clickhouse \
+    -p 8123:8123 \
+    -e CLICKHOUSE_SKIP_USER_SETUP=1 \
+    --ulimit nofile=262144:262144 \
+    clickhouse/clickhouse-server:latest
@@ -0,0 +1,6 @@
+#!/bin/sh
+docker run -d \
+    --name dgraph \
+    -p 8080:8080 \
+    -p 9080:9080 \
+    dgraph/standalone:latest
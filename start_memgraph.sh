@@ -0,0 +1,5 @@
+#!/bin/sh
+docker run -d \
+    --name memgraph \
+    -p 7687:7687 \
+    memgraph/memgraph:latest
@@ -0,0 +1,17 @@
+#!/bin/sh
+# Standalone Milvus with its etcd embedded and data on local storage
+cat > embedEtcd.yaml <<CONFIG
+listen-client-urls: http://0.0.0.0:2379
+advertise-client-urls: http://0.0.0.0:2379
+CONFIG
+docker run -d \
+    --name milvus \
+    --security-opt seccomp:unconfined \
+    -p 19530:19530 \
+    -e ETCD_USE_EMBED=true \
+    -e ETCD_DATA_DIR=/var/lib/milvus/etcd \
+    -e ETCD_CONFIG_PATH=/milvus/configs/embedEtcd.yaml \
+    -e COMMON_STORAGETYPE=local \
+    -v "$(pwd)/embedEtcd.yaml:/milvus/configs/embedEtcd.yaml" \
+    milvusdb/milvus:v2.5.4 \
+    milvus run standalone
@@ -0,0 +1,7 @@
+#!/bin/sh
+docker run -d \
+    --name mysql \
+    -p 3306:3306 \
+    -e MYSQL_ROOT_PASSWORD=password \
+    -e MYSQL_DATABASE=helix \
+    mysql:8
@@ -0,0 +1,9 @@
+#!/bin/sh
+# Single-node OpenSearch with the security plugin off, so it serves plain HTTP without auth
+docker run -d \
+    --name opensearch \
+    -p 9200:9200 \
+    -e discovery.type=single-node \
+    -e DISABLE_SECURITY_PLUGIN=true \
+    -e DISABLE_INSTALL_DEMO_CONFIG=true \
+    opensearchproject/opensearch:2.19.1
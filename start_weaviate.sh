@@ -0,0 +1,8 @@
+#!/bin/sh
+docker run -d \
+    --name weaviate \
+    -p 8080:8080 \
+    -e AUTHENTICATION_ANONYMOUS_ACCESS_ENABLED=true \
+    -e DEFAULT_VECTORIZER_MODULE=none \
+    -e PERSISTENCE_DATA_PATH=/var/lib/weaviate \
+    cr.weaviate.io/semitechnologies/weaviate:latest